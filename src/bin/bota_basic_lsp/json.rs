@@ -0,0 +1,267 @@
+//! Just enough JSON for the language server's messages.
+
+use std::fmt::Display;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Json {
+	Null,
+	Bool(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<Json>),
+	/// Keys are kept in the order they were written
+	Object(Vec<(String, Json)>)
+}
+
+impl Json {
+	/// An object from its keys and values
+	pub fn object<'a>(entries: impl IntoIterator<Item = (&'a str, Json)>) -> Self {
+		Json::Object(entries.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+	}
+
+	/// The value of a key, or null if this isn't an object or the key isn't in it
+	pub fn get(&self, key: &str) -> &Json {
+		match self {
+			Json::Object(entries) => entries.iter().find(|(name, _)| name == key).map_or(&Json::Null, |(_, value)| value),
+			_ => &Json::Null
+		}
+	}
+
+	/// The last value, or null if this isn't an array or it's empty
+	pub fn last(&self) -> &Json {
+		match self {
+			Json::Array(values) => values.last().unwrap_or(&Json::Null),
+			_ => &Json::Null
+		}
+	}
+
+	pub fn as_str(&self) -> Option<&str> {
+		match self {
+			Json::String(string) => Some(string),
+			_ => None
+		}
+	}
+
+	pub fn as_usize(&self) -> Option<usize> {
+		match self {
+			Json::Number(number) if *number >= 0.0 && number.fract() == 0.0 => Some(*number as usize),
+			_ => None
+		}
+	}
+
+	pub fn as_bool(&self) -> Option<bool> {
+		match self {
+			Json::Bool(bool) => Some(*bool),
+			_ => None
+		}
+	}
+
+	pub fn parse(text: &str) -> Result<Self, String> {
+		let mut parser = Parser {chars: text.chars().collect(), index: 0};
+		let value = parser.value()?;
+		parser.skip_whitespace();
+		match parser.chars.get(parser.index) {
+			None => Ok(value),
+			Some(c) => Err(format!("unexpected {:?} after the value", c))
+		}
+	}
+}
+
+impl From<&str> for Json {
+	fn from(string: &str) -> Self {
+		Json::String(string.to_string())
+	}
+}
+
+impl From<String> for Json {
+	fn from(string: String) -> Self {
+		Json::String(string)
+	}
+}
+
+impl From<usize> for Json {
+	fn from(number: usize) -> Self {
+		Json::Number(number as f64)
+	}
+}
+
+impl From<bool> for Json {
+	fn from(bool: bool) -> Self {
+		Json::Bool(bool)
+	}
+}
+
+impl Display for Json {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Json::Null => write!(f, "null"),
+			Json::Bool(bool) => write!(f, "{}", bool),
+			Json::Number(number) => write!(f, "{}", number),
+			Json::String(string) => write_string(f, string),
+			Json::Array(values) => {
+				write!(f, "[")?;
+				for (index, value) in values.iter().enumerate() {
+					if index > 0 {
+						write!(f, ",")?;
+					}
+					write!(f, "{}", value)?;
+				}
+				write!(f, "]")
+			},
+			Json::Object(entries) => {
+				write!(f, "{{")?;
+				for (index, (key, value)) in entries.iter().enumerate() {
+					if index > 0 {
+						write!(f, ",")?;
+					}
+					write_string(f, key)?;
+					write!(f, ":{}", value)?;
+				}
+				write!(f, "}}")
+			}
+		}
+	}
+}
+
+fn write_string(f: &mut std::fmt::Formatter<'_>, string: &str) -> std::fmt::Result {
+	write!(f, "\"")?;
+	for c in string.chars() {
+		match c {
+			'"' => write!(f, "\\\"")?,
+			'\\' => write!(f, "\\\\")?,
+			'\n' => write!(f, "\\n")?,
+			'\r' => write!(f, "\\r")?,
+			'\t' => write!(f, "\\t")?,
+			c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+			c => write!(f, "{}", c)?
+		}
+	}
+	write!(f, "\"")
+}
+
+struct Parser {
+	chars: Vec<char>,
+	index: usize
+}
+
+impl Parser {
+	fn skip_whitespace(&mut self) {
+		while self.chars.get(self.index).is_some_and(|c| c.is_whitespace()) {
+			self.index += 1;
+		}
+	}
+
+	fn next(&mut self) -> Result<char, String> {
+		let c = self.chars.get(self.index).copied().ok_or("unexpected end of input")?;
+		self.index += 1;
+		Ok(c)
+	}
+
+	fn expect(&mut self, word: &str) -> Result<(), String> {
+		for expected in word.chars() {
+			if self.next()? != expected {
+				return Err(format!("expected {}", word));
+			}
+		}
+		Ok(())
+	}
+
+	fn value(&mut self) -> Result<Json, String> {
+		self.skip_whitespace();
+		match self.chars.get(self.index).copied().ok_or("unexpected end of input")? {
+			'n' => self.expect("null").map(|_| Json::Null),
+			't' => self.expect("true").map(|_| Json::Bool(true)),
+			'f' => self.expect("false").map(|_| Json::Bool(false)),
+			'"' => self.string().map(Json::String),
+			'[' => {
+				self.index += 1;
+				let mut values = Vec::new();
+				self.skip_whitespace();
+				if self.chars.get(self.index) == Some(&']') {
+					self.index += 1;
+					return Ok(Json::Array(values));
+				}
+				loop {
+					values.push(self.value()?);
+					self.skip_whitespace();
+					match self.next()? {
+						',' => (),
+						']' => return Ok(Json::Array(values)),
+						c => return Err(format!("expected , or ] but found {:?}", c))
+					}
+				}
+			},
+			'{' => {
+				self.index += 1;
+				let mut entries = Vec::new();
+				self.skip_whitespace();
+				if self.chars.get(self.index) == Some(&'}') {
+					self.index += 1;
+					return Ok(Json::Object(entries));
+				}
+				loop {
+					self.skip_whitespace();
+					if self.chars.get(self.index) != Some(&'"') {
+						return Err("expected a key".to_string());
+					}
+					let key = self.string()?;
+					self.skip_whitespace();
+					self.expect(":")?;
+					entries.push((key, self.value()?));
+					self.skip_whitespace();
+					match self.next()? {
+						',' => (),
+						'}' => return Ok(Json::Object(entries)),
+						c => return Err(format!("expected , or }} but found {:?}", c))
+					}
+				}
+			},
+			_ => self.number()
+		}
+	}
+
+	fn string(&mut self) -> Result<String, String> {
+		self.index += 1;
+		let mut string = String::new();
+		loop {
+			match self.next()? {
+				'"' => return Ok(string),
+				'\\' => match self.next()? {
+					'n' => string.push('\n'),
+					'r' => string.push('\r'),
+					't' => string.push('\t'),
+					'b' => string.push('\u{8}'),
+					'f' => string.push('\u{c}'),
+					'u' => {
+						let mut code = self.hex()?;
+						// characters outside the basic plane are written as surrogate pairs
+						if (0xd800..0xdc00).contains(&code) {
+							self.expect("\\u")?;
+							code = 0x10000 + ((code - 0xd800) << 10) + (self.hex()? - 0xdc00);
+						}
+						string.push(char::from_u32(code).ok_or("invalid \\u escape")?);
+					},
+					c => string.push(c)
+				},
+				c => string.push(c)
+			}
+		}
+	}
+
+	fn hex(&mut self) -> Result<u32, String> {
+		let mut code = 0;
+		for _ in 0..4 {
+			code = code * 16 + self.next()?.to_digit(16).ok_or("invalid \\u escape")?;
+		}
+		Ok(code)
+	}
+
+	fn number(&mut self) -> Result<Json, String> {
+		let start = self.index;
+		while self.chars.get(self.index).is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+			self.index += 1;
+		}
+		let text: String = self.chars[start..self.index].iter().collect();
+		text.parse().map(Json::Number).map_err(|_| format!("invalid value {:?}", text))
+	}
+}
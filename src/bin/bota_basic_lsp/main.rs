@@ -0,0 +1,360 @@
+//! A language server for BotaBasic, speaking JSON-RPC over stdin and stdout.
+
+mod json;
+
+use bota_basic::{word_spans, Kind, Level, LintConfig, Param, Program, Signature, Symbol, SymbolKind};
+use json::Json;
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+
+/// Reads one message, or nothing once stdin is closed
+fn read_message(input: &mut impl BufRead) -> Result<Option<Json>, String> {
+	let mut length = None;
+	loop {
+		let mut header = String::new();
+		if input.read_line(&mut header).map_err(|error| error.to_string())? == 0 {
+			return Ok(None);
+		}
+		let header = header.trim();
+		if header.is_empty() {
+			break;
+		}
+		if let Some((name, value)) = header.split_once(':') {
+			if name.eq_ignore_ascii_case("Content-Length") {
+				length = Some(value.trim().parse::<usize>().map_err(|_| format!("invalid Content-Length {:?}", value.trim()))?);
+			}
+		}
+	}
+	let length = length.ok_or("a message has no Content-Length")?;
+	let mut body = vec![0; length];
+	input.read_exact(&mut body).map_err(|error| error.to_string())?;
+	let body = String::from_utf8(body).map_err(|error| error.to_string())?;
+	Json::parse(&body).map(Some)
+}
+
+fn send(message: &Json) {
+	let body = message.to_string();
+	let mut stdout = std::io::stdout().lock();
+	let _ = write!(stdout, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+	let _ = stdout.flush();
+}
+
+fn notify(method: &str, params: Json) {
+	send(&Json::object([("jsonrpc", "2.0".into()), ("method", method.into()), ("params", params)]));
+}
+
+/// Converts a byte offset in a line to the UTF-16 column editors count in
+fn to_column(line: &str, offset: usize) -> usize {
+	line[..offset.min(line.len())].encode_utf16().count()
+}
+
+/// Converts a UTF-16 column to a byte offset in a line
+fn to_offset(line: &str, column: usize) -> usize {
+	let mut units = 0;
+	for (offset, c) in line.char_indices() {
+		if units >= column {
+			return offset;
+		}
+		units += c.len_utf16();
+	}
+	line.len()
+}
+
+fn range(text: &str, line: usize, span: std::ops::Range<usize>) -> Json {
+	let source = text.lines().nth(line).unwrap_or("");
+	let position = |offset| Json::object([("line", line.into()), ("character", to_column(source, offset).into())]);
+	Json::object([("start", position(span.start)), ("end", position(span.end))])
+}
+
+/// Where a problem is: the word for its parameter if it's about one, otherwise the whole line
+fn problem_range(text: &str, line: usize, param: Option<usize>) -> Json {
+	let source = text.lines().nth(line - 1).unwrap_or("");
+	let spans = word_spans(source);
+	let span = match param.and_then(|param| spans.get(param + 1)) {
+		Some(span) => span.clone(),
+		None => spans.first().map_or(0, |span| span.start)..source.len()
+	};
+	range(text, line - 1, span)
+}
+
+/// The path of a `file://` URI
+fn uri_path(uri: &str) -> Option<PathBuf> {
+	let path = uri.strip_prefix("file://")?;
+	let bytes = path.as_bytes();
+	let mut decoded = Vec::new();
+	let mut index = 0;
+	while index < bytes.len() {
+		match (bytes[index], path.get(index + 1..index + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok())) {
+			(b'%', Some(byte)) => {
+				decoded.push(byte);
+				index += 3;
+			},
+			(byte, _) => {
+				decoded.push(byte);
+				index += 1;
+			}
+		}
+	}
+	String::from_utf8(decoded).ok().map(PathBuf::from)
+}
+
+/// The lint config in the closest directory above a document, if there is one
+fn lint_config(uri: &str) -> LintConfig {
+	uri_path(uri)
+		.and_then(|path| path.ancestors().skip(1).map(|dir| dir.join("bota_basic.lint")).find(|config| config.is_file()))
+		.and_then(|config| std::fs::read_to_string(config).ok())
+		.and_then(|text| LintConfig::parse(&text).ok())
+		.unwrap_or_default()
+}
+
+fn diagnostics(uri: &str, text: &str) -> Json {
+	let mut diagnostics = Vec::new();
+	for error in Program::new(text.to_string()).check() {
+		diagnostics.push(Json::object([
+			("range", problem_range(text, error.line, error.param)),
+			("severity", Json::Number(1.0)),
+			("source", "bota_basic".into()),
+			("message", error.problem.to_string().into())
+		]));
+	}
+	for warning in Program::new(text.to_string()).lint(&lint_config(uri)) {
+		diagnostics.push(Json::object([
+			("range", problem_range(text, warning.line, warning.param)),
+			("severity", Json::Number(if warning.level == Level::Deny {1.0} else {2.0})),
+			("code", warning.lint.code().into()),
+			("source", "bota_basic".into()),
+			("message", warning.message.into())
+		]));
+	}
+	Json::object([("uri", uri.into()), ("diagnostics", Json::Array(diagnostics))])
+}
+
+fn kind_name(kind: Kind) -> &'static str {
+	match kind {
+		Kind::Any => "value",
+		Kind::Number => "number",
+		Kind::Integer => "integer",
+		Kind::Bool => "bool",
+		Kind::Char => "char",
+		Kind::Str => "str",
+		Kind::List => "list"
+	}
+}
+
+/// How a command is written, like `ADD [number location] [number] [number]`. Parameters which
+/// can be left out end with a `?`
+fn signature_text(name: &str, signature: &Signature) -> String {
+	let mut text = name.to_string();
+	let required = signature.params.len() - signature.optional;
+	for (index, param) in signature.params.iter().enumerate() {
+		let param = match param {
+			Param::Location(Kind::Any) => "location".to_string(),
+			Param::Location(kind) => format!("{} location", kind_name(*kind)),
+			Param::Operand(Kind::Any) => "variable".to_string(),
+			Param::Operand(kind) => format!("{} variable", kind_name(*kind)),
+			Param::Literal(kind) => kind_name(*kind).to_string(),
+			Param::Label => "label".to_string(),
+			Param::Word => "word".to_string()
+		};
+		text.push_str(&format!(" [{}{}]", param, if index >= required {"?"} else {""}));
+	}
+	text
+}
+
+struct Server {
+	documents: HashMap<String, String>
+}
+
+/// Where a request is asking about: the document's text, the line from 0 and the byte offset in it
+fn position<'a>(documents: &'a HashMap<String, String>, params: &Json) -> Option<(&'a str, &'a str, usize, usize)> {
+	let uri = params.get("textDocument").get("uri").as_str()?;
+	let (uri, text) = documents.get_key_value(uri)?;
+	let line = params.get("position").get("line").as_usize()?;
+	let source = text.lines().nth(line).unwrap_or("");
+	Some((uri, text, line, to_offset(source, params.get("position").get("character").as_usize()?)))
+}
+
+fn symbol_at(text: &str, line: usize, offset: usize) -> Option<(Vec<Symbol>, Symbol)> {
+	let symbols = Program::new(text.to_string()).symbols();
+	let symbol = symbols.iter().find(|symbol| symbol.line == line + 1 && symbol.span.start <= offset && offset <= symbol.span.end)?.clone();
+	Some((symbols, symbol))
+}
+
+fn location(uri: &str, text: &str, symbol: &Symbol) -> Json {
+	Json::object([("uri", uri.into()), ("range", range(text, symbol.line - 1, symbol.span.clone()))])
+}
+
+impl Server {
+	/// Answers a request, or gives back an error code and message
+	fn request(&mut self, method: &str, params: &Json) -> Result<Json, (i32, String)> {
+		match method {
+			"initialize" => Ok(Json::object([
+				("capabilities", Json::object([
+					("textDocumentSync", Json::Number(1.0)),
+					("definitionProvider", true.into()),
+					("referencesProvider", true.into()),
+					("hoverProvider", true.into()),
+					("completionProvider", Json::object([])),
+					("renameProvider", true.into())
+				])),
+				("serverInfo", Json::object([("name", "bota_basic_lsp".into()), ("version", env!("CARGO_PKG_VERSION").into())]))
+			])),
+			"shutdown" => Ok(Json::Null),
+			"textDocument/definition" => {
+				let Some((uri, text, line, offset)) = position(&self.documents, params) else {return Ok(Json::Null)};
+				let Some((symbols, symbol)) = symbol_at(text, line, offset) else {return Ok(Json::Null)};
+				Ok(Json::Array(symbols.iter()
+					.filter(|other| other.definition && other.kind == symbol.kind && other.name == symbol.name)
+					.map(|other| location(uri, text, other))
+					.collect()))
+			},
+			"textDocument/references" => {
+				let Some((uri, text, line, offset)) = position(&self.documents, params) else {return Ok(Json::Null)};
+				let Some((symbols, symbol)) = symbol_at(text, line, offset) else {return Ok(Json::Null)};
+				let declarations = params.get("context").get("includeDeclaration").as_bool().unwrap_or(true);
+				Ok(Json::Array(symbols.iter()
+					.filter(|other| other.kind == symbol.kind && other.name == symbol.name && (declarations || !other.definition))
+					.map(|other| location(uri, text, other))
+					.collect()))
+			},
+			"textDocument/hover" => {
+				let Some((_, text, line, offset)) = position(&self.documents, params) else {return Ok(Json::Null)};
+				let source = text.lines().nth(line).unwrap_or("");
+				let spans = word_spans(source);
+				let mut program = Program::new(text.to_string());
+				let contents = match spans.iter().position(|span| span.start <= offset && offset <= span.end) {
+					Some(0) => {
+						let name = source[spans[0].clone()].to_uppercase();
+						let Some(spec) = program.commands().get(&name) else {return Ok(Json::Null)};
+						signature_text(&name, &spec.signature)
+					},
+					Some(_) => match symbol_at(text, line, offset) {
+						Some((_, symbol)) if symbol.kind == SymbolKind::Variable => match program.type_at(line + 1, &symbol.name) {
+							Some(var_type) => format!("{}: {}", symbol.name, var_type),
+							None => symbol.name
+						},
+						Some((_, symbol)) => format!("LABEL {}", symbol.name),
+						None => return Ok(Json::Null)
+					},
+					None => return Ok(Json::Null)
+				};
+				Ok(Json::object([("contents", Json::object([("kind", "markdown".into()), ("value", format!("```\n{}\n```", contents).into())]))]))
+			},
+			"textDocument/completion" => {
+				let Some((_, text, line, offset)) = position(&self.documents, params) else {return Ok(Json::Null)};
+				let source = text.lines().nth(line).unwrap_or("");
+				// the word being typed, counting from 0
+				let spans = word_spans(source);
+				let word = spans.iter().filter(|span| span.end < offset).count();
+				let mut program = Program::new(text.to_string());
+				let mut items = Vec::new();
+				if word == 0 {
+					let mut names: Vec<&str> = program.commands().names().collect();
+					names.sort_unstable();
+					for name in names {
+						let signature = &program.commands().get(name).unwrap().signature;
+						items.push(Json::object([("label", name.into()), ("kind", Json::Number(3.0)), ("detail", signature_text(name, signature).into())]));
+					}
+					return Ok(Json::Array(items));
+				}
+
+				let command = source[spans[0].clone()].to_uppercase();
+				let param = program.commands().get(&command).and_then(|spec| spec.signature.params.get(word - 1).copied());
+				let wants = match (param, command.as_str(), word) {
+					(Some(Param::Label), _, _) | (_, "ON", 3) => SymbolKind::Label,
+					_ => SymbolKind::Variable
+				};
+				let mut names: Vec<String> = program.symbols().into_iter()
+					.filter(|symbol| symbol.definition && symbol.kind == wants)
+					.map(|symbol| symbol.name)
+					.collect();
+				names.sort_unstable();
+				names.dedup();
+				for name in names {
+					let (kind, detail) = match wants {
+						SymbolKind::Label => (18.0, "label".to_string()),
+						SymbolKind::Variable => (6.0, program.type_at(line + 1, &name).map_or_else(String::new, |var_type| var_type.to_string()))
+					};
+					items.push(Json::object([("label", name.into()), ("kind", Json::Number(kind)), ("detail", detail.into())]));
+				}
+				Ok(Json::Array(items))
+			},
+			"textDocument/rename" => {
+				let Some((uri, text, line, offset)) = position(&self.documents, params) else {return Ok(Json::Null)};
+				let Some((symbols, symbol)) = symbol_at(text, line, offset) else {return Ok(Json::Null)};
+				let new_name = params.get("newName").as_str().unwrap_or("");
+				if new_name.is_empty() || new_name.contains(|c: char| c.is_whitespace() || c == '"' || c == ',') {
+					return Err((INVALID_PARAMS, format!("{:?} can't be used as a name", new_name)));
+				}
+				let same = |other: &&Symbol| other.kind == symbol.kind && other.name == symbol.name;
+				if !symbols.iter().filter(same).any(|other| other.definition) {
+					return Err((INVALID_PARAMS, format!("{} isn't declared in this file", symbol.name)));
+				}
+				let edits = symbols.iter().filter(same)
+					.map(|other| Json::object([("range", range(text, other.line - 1, other.span.clone())), ("newText", new_name.into())]))
+					.collect();
+				Ok(Json::object([("changes", Json::object([(uri, Json::Array(edits))]))]))
+			},
+			_ => Err((METHOD_NOT_FOUND, format!("{} isn't supported", method)))
+		}
+	}
+
+	fn notification(&mut self, method: &str, params: &Json) {
+		let uri = params.get("textDocument").get("uri").as_str().unwrap_or("").to_string();
+		match method {
+			"textDocument/didOpen" => {
+				let text = params.get("textDocument").get("text").as_str().unwrap_or("").to_string();
+				notify("textDocument/publishDiagnostics", diagnostics(&uri, &text));
+				self.documents.insert(uri, text);
+			},
+			"textDocument/didChange" => {
+				// the whole document is sent each time
+				let Some(text) = params.get("contentChanges").last().get("text").as_str() else {return};
+				notify("textDocument/publishDiagnostics", diagnostics(&uri, text));
+				self.documents.insert(uri, text.to_string());
+			},
+			"textDocument/didClose" => {
+				self.documents.remove(&uri);
+				notify("textDocument/publishDiagnostics", Json::object([("uri", uri.into()), ("diagnostics", Json::Array(Vec::new()))]));
+			},
+			_ => ()
+		}
+	}
+}
+
+fn main() {
+	let mut server = Server {documents: HashMap::new()};
+	let mut stdin = std::io::stdin().lock();
+	let mut shut_down = false;
+	loop {
+		let message = match read_message(&mut stdin) {
+			Ok(Some(message)) => message,
+			Ok(None) => break,
+			Err(error) => {
+				eprintln!("{}", error);
+				continue;
+			}
+		};
+		let method = message.get("method").as_str().unwrap_or("");
+		if method == "exit" {
+			std::process::exit(if shut_down {0} else {1});
+		}
+		let params = message.get("params");
+		match message.get("id") {
+			Json::Null => server.notification(method, params),
+			id => {
+				let response = match server.request(method, params) {
+					Ok(result) => ("result", result),
+					Err((code, message)) => ("error", Json::object([("code", Json::Number(code as f64)), ("message", message.into())]))
+				};
+				shut_down |= method == "shutdown";
+				send(&Json::object([("jsonrpc", "2.0".into()), ("id", id.clone()), response]));
+			}
+		}
+	}
+	std::process::exit(if shut_down {0} else {1});
+}
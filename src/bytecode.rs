@@ -0,0 +1,487 @@
+//! Compiling programs to bytecode, and running it on a small stack machine.
+//!
+//! Each line compiles to a handful of instructions: one per parameter, pushing its value onto
+//! the stack, then one to run the command on them, then one per location to store its new value.
+//! Variables are numbered slots rather than names, and literals are parsed ahead of time, so a
+//! line can run without being split into words or having its names looked up. Jumps still go
+//! from line to line, so errors, routines and stepping behave as they do when interpreting.
+//!
+//! Saved bytecode starts with `BOTABBC` and a version number, then a checksum of everything after
+//! it and the hash of the source it was compiled from. Numbers are little-endian, as in snapshots.
+
+use crate::snapshot::{Reader, Writer};
+use crate::{CommandResponse, CommandSpec, Error, Kind, Param, Program, Registry, RuntimeError, Signature, UnparsedCommand, Variable, parse_scalar};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+const MAGIC: &[u8] = b"BOTABBC\0";
+const VERSION: u32 = 1;
+
+/// A 64-bit FNV-1a hash, which is quick and always the same from run to run
+pub(crate) fn hash(bytes: &[u8]) -> u64 {
+	bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum Op {
+	/// Pushes the variable in a slot
+	Load(usize, Param),
+	/// Pushes the variable in a slot, or a constant if the slot is empty
+	LoadOr(usize, usize, Param),
+	/// Parses a str constant as a literal when the line runs, for literals that can't be parsed ahead of time
+	Parse(usize, Param),
+	/// Pushes a constant
+	Const(usize),
+	/// Pushes a str constant, checking that there's a label by that name
+	Label(usize),
+	/// Runs a command on the given number of values from the top of the stack, then pushes the
+	/// new values of its locations, last first
+	Invoke(usize, usize),
+	/// Pops a value into a slot, unless the variable has been freed
+	Store(usize),
+	/// Fails because the line is missing some of a command's parameters
+	MissingParameters(usize)
+}
+
+/// A compiled program, which can be saved with [`Bytecode::to_bytes`] and run with [`Program::from_bytecode`]
+#[derive(Clone, Default)]
+pub struct Bytecode {
+	pub(crate) ops: Vec<Op>,
+	/// Where each line's instructions start, followed by the end of the last line
+	pub(crate) line_starts: Vec<usize>,
+	pub(crate) constants: Vec<Variable>,
+	/// The name of the variable in each slot
+	pub(crate) slots: Vec<String>,
+	/// The name and signature of each command that's run
+	pub(crate) commands: Vec<(String, Signature)>,
+	pub(crate) labels: HashMap<String, usize>,
+	pub(crate) try_blocks: HashMap<usize, usize>,
+	pub(crate) catch_blocks: HashMap<usize, usize>,
+	pub(crate) test_blocks: HashMap<usize, usize>,
+	pub(crate) block_names: HashMap<usize, String>,
+	source_hash: u64
+}
+
+impl Bytecode {
+	/// The hash that bytecode compiled from this source will have, for finding it in a cache
+	pub fn hash_source(source: &str) -> u64 {
+		hash(source.as_bytes())
+	}
+
+	/// The hash of the source this was compiled from
+	pub fn source_hash(&self) -> u64 {
+		self.source_hash
+	}
+
+	pub(crate) fn line_count(&self) -> usize {
+		self.line_starts.len() - 1
+	}
+
+	/// The line an instruction is on
+	pub(crate) fn line_of(&self, op: usize) -> usize {
+		self.line_starts.partition_point(|start| *start <= op) - 1
+	}
+
+	pub(crate) fn constant(&mut self, value: Variable) -> usize {
+		self.constants.push(value);
+		self.constants.len() - 1
+	}
+
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut body = Writer::default();
+		body.u64(self.source_hash);
+		body.usize(self.commands.len());
+		for (name, signature) in &self.commands {
+			body.str(name);
+			body.usize(signature.params.len());
+			for param in &signature.params {
+				write_param(&mut body, *param);
+			}
+			body.usize(signature.optional);
+		}
+		body.usize(self.slots.len());
+		for slot in &self.slots {
+			body.str(slot);
+		}
+		body.usize(self.constants.len());
+		for constant in &self.constants {
+			body.variable(constant);
+		}
+		body.usize(self.line_starts.len());
+		for start in &self.line_starts {
+			body.usize(*start);
+		}
+		body.usize(self.ops.len());
+		for op in &self.ops {
+			write_op(&mut body, *op);
+		}
+
+		// sorted, so that the same program always gives the same bytes
+		let mut labels: Vec<(&String, &usize)> = self.labels.iter().collect();
+		labels.sort();
+		body.usize(labels.len());
+		for (name, line) in labels {
+			body.str(name);
+			body.usize(*line);
+		}
+		for blocks in [&self.try_blocks, &self.catch_blocks, &self.test_blocks] {
+			let mut blocks: Vec<(&usize, &usize)> = blocks.iter().collect();
+			blocks.sort();
+			body.usize(blocks.len());
+			for (start, end) in blocks {
+				body.usize(*start);
+				body.usize(*end);
+			}
+		}
+		let mut names: Vec<(&usize, &String)> = self.block_names.iter().collect();
+		names.sort();
+		body.usize(names.len());
+		for (line, name) in names {
+			body.usize(*line);
+			body.str(name);
+		}
+
+		let mut writer = Writer::default();
+		writer.bytes.extend_from_slice(MAGIC);
+		writer.u32(VERSION);
+		writer.u64(hash(&body.bytes));
+		writer.bytes.extend_from_slice(&body.bytes);
+		writer.bytes
+	}
+
+	/// Reads bytecode saved with [`Bytecode::to_bytes`], checking that it's whole and makes sense
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+		let mut reader = Reader::new(bytes, Error::InvalidBytecode);
+		if reader.bytes(MAGIC.len()).ok() != Some(MAGIC) {
+			return Err(Error::InvalidBytecode("it isn't bytecode".to_string()));
+		}
+		let version = reader.u32()?;
+		if version != VERSION {
+			return Err(Error::InvalidBytecode(format!("version {} isn't supported", version)));
+		}
+		let checksum = reader.u64()?;
+		let body = reader.bytes(bytes.len() - MAGIC.len() - 12)?;
+		if hash(body) != checksum {
+			return Err(Error::InvalidBytecode("the checksum doesn't match".to_string()));
+		}
+
+		let mut reader = Reader::new(body, Error::InvalidBytecode);
+		let mut bytecode = Bytecode {source_hash: reader.u64()?, ..Bytecode::default()};
+		for _ in 0..reader.count()? {
+			let name = reader.str()?;
+			let params = (0..reader.count()?).map(|_| read_param(&mut reader)).collect::<Result<_, _>>()?;
+			bytecode.commands.push((name, Signature {params, optional: reader.usize()?}));
+		}
+		bytecode.slots = (0..reader.count()?).map(|_| reader.str()).collect::<Result<_, _>>()?;
+		bytecode.constants = (0..reader.count()?).map(|_| reader.variable()).collect::<Result<_, _>>()?;
+		bytecode.line_starts = (0..reader.count()?).map(|_| reader.usize()).collect::<Result<_, _>>()?;
+		bytecode.ops = (0..reader.count()?).map(|_| read_op(&mut reader)).collect::<Result<_, _>>()?;
+
+		for _ in 0..reader.count()? {
+			let name = reader.str()?;
+			bytecode.labels.insert(name, reader.usize()?);
+		}
+		for blocks in [&mut bytecode.try_blocks, &mut bytecode.catch_blocks, &mut bytecode.test_blocks] {
+			for _ in 0..reader.count()? {
+				let start = reader.usize()?;
+				blocks.insert(start, reader.usize()?);
+			}
+		}
+		for _ in 0..reader.count()? {
+			let line = reader.usize()?;
+			bytecode.block_names.insert(line, reader.str()?);
+		}
+		if !reader.is_empty() {
+			return Err(Error::InvalidBytecode("there's more after the end".to_string()));
+		}
+		bytecode.validate()?;
+		Ok(bytecode)
+	}
+
+	/// Checks that running the bytecode can't go outside of its tables or its stack
+	fn validate(&self) -> Result<(), Error> {
+		let invalid = |message: &str| Err(Error::InvalidBytecode(message.to_string()));
+		let starts_valid = self.line_starts.first() == Some(&0)
+			&& self.line_starts.last() == Some(&self.ops.len())
+			&& self.line_starts.windows(2).all(|pair| pair[0] <= pair[1]);
+		if !starts_valid {
+			return invalid("its lines don't line up with its instructions");
+		}
+		let lines = self.line_count();
+		let blocks_valid = self.labels.values().all(|line| *line < lines)
+			&& self.try_blocks.iter().all(|(start, end)| *start < lines && self.catch_blocks.contains_key(end))
+			&& self.catch_blocks.iter().chain(&self.test_blocks).all(|(start, end)| *end < lines && self.block_names.contains_key(start))
+			&& self.block_names.keys().all(|line| *line < lines);
+		if !blocks_valid {
+			return invalid("its blocks point outside of the program");
+		}
+
+		for line in 0..lines {
+			let mut depth = 0;
+			for op in &self.ops[self.line_starts[line]..self.line_starts[line + 1]] {
+				let (slot, constant, command) = match *op {
+					Op::Load(slot, _) => (Some(slot), None, None),
+					Op::LoadOr(slot, constant, _) => (Some(slot), Some(constant), None),
+					Op::Parse(constant, _) | Op::Const(constant) | Op::Label(constant) => (None, Some(constant), None),
+					Op::Invoke(command, _) | Op::MissingParameters(command) => (None, None, Some(command)),
+					Op::Store(slot) => (Some(slot), None, None)
+				};
+				if slot.is_some_and(|slot| slot >= self.slots.len())
+					|| constant.is_some_and(|constant| constant >= self.constants.len())
+					|| command.is_some_and(|command| command >= self.commands.len()) {
+					return invalid("an instruction points outside of its tables");
+				}
+				depth = match *op {
+					Op::Invoke(command, args) => {
+						let (name, signature) = &self.commands[command];
+						if args > depth || args < signature.required() || args > signature.params.len() {
+							return invalid("a command is given the wrong number of parameters");
+						}
+						let block = match name.to_uppercase().as_str() {
+							"TRY" => Some(&self.try_blocks),
+							"CATCH" => Some(&self.catch_blocks),
+							"TEST" => Some(&self.test_blocks),
+							_ => None
+						};
+						if block.is_some_and(|block| !block.contains_key(&line)) {
+							return invalid("a block isn't closed");
+						}
+						depth - args + signature.params[..args].iter().filter(|param| matches!(param, Param::Location(_))).count()
+					},
+					Op::Store(_) if depth == 0 => return invalid("the stack is empty"),
+					Op::Store(_) => depth - 1,
+					Op::MissingParameters(_) => depth,
+					_ => depth + 1
+				};
+			}
+		}
+		Ok(())
+	}
+}
+
+fn write_param(writer: &mut Writer, param: Param) {
+	let kind = |kind: Kind| Kind::ALL.iter().position(|k| *k == kind).unwrap() as u8;
+	match param {
+		Param::Location(k) => {writer.u8(0); writer.u8(kind(k));},
+		Param::Operand(k) => {writer.u8(1); writer.u8(kind(k));},
+		Param::Literal(k) => {writer.u8(2); writer.u8(kind(k));},
+		Param::Label => writer.u8(3),
+		Param::Word => writer.u8(4)
+	}
+}
+
+fn read_param(reader: &mut Reader) -> Result<Param, Error> {
+	let kind = |reader: &mut Reader| {
+		let kind = reader.u8()?;
+		Kind::ALL.get(kind as usize).copied().ok_or_else(|| reader.invalid("a parameter has an unknown kind"))
+	};
+	Ok(match reader.u8()? {
+		0 => Param::Location(kind(reader)?),
+		1 => Param::Operand(kind(reader)?),
+		2 => Param::Literal(kind(reader)?),
+		3 => Param::Label,
+		4 => Param::Word,
+		_ => return Err(reader.invalid("a parameter has an unknown type"))
+	})
+}
+
+fn write_op(writer: &mut Writer, op: Op) {
+	match op {
+		Op::Load(slot, param) => {writer.u8(0); writer.usize(slot); write_param(writer, param);},
+		Op::LoadOr(slot, constant, param) => {writer.u8(1); writer.usize(slot); writer.usize(constant); write_param(writer, param);},
+		Op::Parse(constant, param) => {writer.u8(2); writer.usize(constant); write_param(writer, param);},
+		Op::Const(constant) => {writer.u8(3); writer.usize(constant);},
+		Op::Label(constant) => {writer.u8(4); writer.usize(constant);},
+		Op::Invoke(command, args) => {writer.u8(5); writer.usize(command); writer.usize(args);},
+		Op::Store(slot) => {writer.u8(6); writer.usize(slot);},
+		Op::MissingParameters(command) => {writer.u8(7); writer.usize(command);}
+	}
+}
+
+fn read_op(reader: &mut Reader) -> Result<Op, Error> {
+	Ok(match reader.u8()? {
+		0 => Op::Load(reader.usize()?, read_param(reader)?),
+		1 => Op::LoadOr(reader.usize()?, reader.usize()?, read_param(reader)?),
+		2 => Op::Parse(reader.usize()?, read_param(reader)?),
+		3 => Op::Const(reader.usize()?),
+		4 => Op::Label(reader.usize()?),
+		5 => Op::Invoke(reader.usize()?, reader.usize()?),
+		6 => Op::Store(reader.usize()?),
+		7 => Op::MissingParameters(reader.usize()?),
+		_ => return Err(reader.invalid("an instruction is unknown"))
+	})
+}
+
+/// Bytecode with its commands found in a registry, ready to run
+pub(crate) struct Compiled {
+	pub bytecode: Bytecode,
+	commands: Vec<Rc<CommandSpec>>
+}
+
+impl Compiled {
+	/// Finds each command the bytecode runs, which must have the signature it was compiled with
+	pub fn link(bytecode: Bytecode, registry: &Registry) -> Result<Self, RuntimeError> {
+		let mut commands = Vec::new();
+		for (index, (name, signature)) in bytecode.commands.iter().enumerate() {
+			let message = match registry.spec(name) {
+				Some(spec) if spec.signature == *signature => {
+					commands.push(spec);
+					continue;
+				},
+				Some(_) => format!("it was compiled with a different {} command", name),
+				None => format!("there's no {} command", name)
+			};
+			let op = bytecode.ops.iter().position(|op| matches!(op, Op::Invoke(command, _) | Op::MissingParameters(command) if *command == index));
+			let line = op.map_or(0, |op| bytecode.line_of(op)) + 1;
+			return Err(RuntimeError {line, error: Error::InvalidBytecode(message)});
+		}
+		Ok(Compiled {bytecode, commands})
+	}
+}
+
+struct Compiler<'a> {
+	bytecode: Bytecode,
+	slots: HashMap<String, usize>,
+	registry: &'a Registry
+}
+
+impl Compiler<'_> {
+	fn slot(&mut self, name: &str) -> usize {
+		if let Some(slot) = self.slots.get(name) {
+			return *slot;
+		}
+		self.slots.insert(name.to_string(), self.bytecode.slots.len());
+		self.bytecode.slots.push(name.to_string());
+		self.bytecode.slots.len() - 1
+	}
+
+	fn command(&mut self, name: &str) -> usize {
+		match self.bytecode.commands.iter().position(|(command, _)| command == name) {
+			Some(index) => index,
+			None => {
+				let signature = self.registry.get(name).unwrap().signature.clone();
+				self.bytecode.commands.push((name.to_string(), signature));
+				self.bytecode.commands.len() - 1
+			}
+		}
+	}
+
+	/// Literals are parsed now unless they're lists, whose items might be variables.
+	/// A variable can have any name, so the literal's slot is still checked first
+	fn literal(&mut self, word: &str, param: Param) -> Op {
+		let literal = word.trim();
+		match parse_scalar(literal) {
+			Ok(value) if !literal.starts_with('[') => {
+				let slot = self.slot(literal);
+				Op::LoadOr(slot, self.bytecode.constant(value), param)
+			},
+			_ => Op::Parse(self.bytecode.constant(Variable::Str(word.to_string())), param)
+		}
+	}
+
+	fn line(&mut self, line: &str) {
+		let command = match UnparsedCommand::from_line(line.to_string(), self.registry) {
+			Ok(Some(command)) => command,
+			Ok(None) => return,
+			Err(Error::MissingParameters(name)) => {
+				let command = self.command(&name);
+				self.bytecode.ops.push(Op::MissingParameters(command));
+				return;
+			},
+			Err(_) => unreachable!()
+		};
+		let spec = self.registry.spec(&command.command_name).unwrap();
+		let mut locations = Vec::new();
+		for (param, word) in spec.signature.params.iter().zip(&command.parameters) {
+			let op = match *param {
+				Param::Location(_) => {
+					let slot = self.slot(word);
+					locations.push(slot);
+					Op::Load(slot, *param)
+				},
+				Param::Operand(_) => Op::Load(self.slot(word), *param),
+				Param::Literal(_) => self.literal(word, *param),
+				Param::Label => Op::Label(self.bytecode.constant(Variable::Str(word.clone()))),
+				Param::Word => Op::Const(self.bytecode.constant(Variable::Str(word.clone())))
+			};
+			self.bytecode.ops.push(op);
+		}
+		let args = spec.signature.params.len().min(command.parameters.len());
+		let command = self.command(&command.command_name);
+		self.bytecode.ops.push(Op::Invoke(command, args));
+		self.bytecode.ops.extend(locations.into_iter().map(Op::Store));
+	}
+}
+
+impl Program {
+	/// Compiles the lines of the program, which must already be loaded
+	pub(crate) fn compile_lines(&self) -> Bytecode {
+		let bytecode = Bytecode {
+			labels: self.labels.iter().map(|(name, label)| (name.clone(), label.0)).collect(),
+			try_blocks: self.try_blocks.clone(),
+			catch_blocks: self.catch_blocks.clone(),
+			test_blocks: self.test_blocks.clone(),
+			block_names: self.block_names.clone(),
+			source_hash: Bytecode::hash_source(&self.program),
+			..Bytecode::default()
+		};
+		let mut compiler = Compiler {bytecode, slots: HashMap::new(), registry: &self.commands};
+		for line in &self.lines {
+			compiler.bytecode.line_starts.push(compiler.bytecode.ops.len());
+			compiler.line(line);
+		}
+		compiler.bytecode.line_starts.push(compiler.bytecode.ops.len());
+		compiler.bytecode
+	}
+
+	/// Runs the compiled instructions for the current line
+	pub(crate) fn run_compiled_line(&mut self, compiled: &Compiled) -> Result<(), Error> {
+		self.check_limits()?;
+		let bytecode = &compiled.bytecode;
+		let ops = &bytecode.ops[bytecode.line_starts[self.current_line]..bytecode.line_starts[self.current_line + 1]];
+		let mut stack = Vec::new();
+		let mut response = CommandResponse::Nothing;
+		for op in ops {
+			match *op {
+				Op::Load(slot, param) => {
+					let value = self.vars.get_slot(slot).cloned()
+						.ok_or_else(|| Error::UnknownVariable(bytecode.slots[slot].clone()))?;
+					stack.push(param.check(value)?);
+				},
+				Op::LoadOr(slot, constant, param) => {
+					let value = self.vars.get_slot(slot).unwrap_or(&bytecode.constants[constant]).clone();
+					stack.push(param.check(value)?);
+				},
+				Op::Parse(constant, param) => {
+					let value = self.parse_literal(bytecode.constants[constant].to_str()?)?;
+					stack.push(param.check(value)?);
+				},
+				Op::Const(constant) => stack.push(bytecode.constants[constant].clone()),
+				Op::Label(constant) => {
+					let name = bytecode.constants[constant].to_str()?;
+					self.get_label(name.clone())?;
+					stack.push(Variable::Str(name));
+				},
+				Op::Invoke(command, args) => {
+					let spec = &compiled.commands[command];
+					let mut args = stack.split_off(stack.len() - args);
+					response = (spec.handler)(self, &mut args)?;
+					let locations = spec.signature.params.iter().zip(args)
+						.filter(|(param, _)| matches!(param, Param::Location(_)))
+						.map(|(_, value)| value);
+					let start = stack.len();
+					stack.extend(locations);
+					stack[start..].reverse();
+				},
+				Op::Store(slot) => {
+					let value = stack.pop().unwrap();
+					if self.vars.get_slot(slot).is_some() {
+						self.insert_slot(slot, value)?;
+					}
+				},
+				Op::MissingParameters(command) => return Err(Error::MissingParameters(bytecode.commands[command].0.clone()))
+			}
+		}
+		self.respond(response)
+	}
+}
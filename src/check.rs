@@ -0,0 +1,374 @@
+//! Checking a program's types before it runs.
+//!
+//! Each variable's type comes from its `DECL`. What's known about every variable is followed along
+//! each way the program can go from line to line, through jumps, routines and `TRY` blocks, and a
+//! problem is only reported if it happens however the program gets to the line.
+
+use crate::{parse_scalar, CommandSpec, Error, Kind, Param, Program, UnparsedCommand, VarType};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+use std::rc::Rc;
+
+/// Commands which jump to their label, and may carry on to the next line if they don't
+const CONDITIONAL_JUMPS: &[&str] = &["JEQ", "JNE", "JGT", "JLT", "JGE", "JLE", "JIF", "JIFNOT"];
+
+/// Variables that are declared together, with their types if they're known
+type Bindings = &'static [(&'static str, Option<VarType>)];
+
+/// The variables declared before the program starts
+pub(crate) const BUILT_IN_VARS: Bindings = &[
+	("PI", Some(VarType::Float)),
+	("E", Some(VarType::Float)),
+	("ARGS", Some(VarType::List)),
+	("ERR", Some(VarType::Str)),
+	("ERRLINE", Some(VarType::Natural))
+];
+
+/// The variables set for the routines that commands call
+pub(crate) const ROUTINE_VARS: &[(&str, Bindings)] = &[
+	("MAP", &[("ITEM", None), ("RESULT", None)]),
+	("FILTER", &[("ITEM", None), ("RESULT", Some(VarType::Boolean))]),
+	("REDUCE", &[("ACC", None), ("ITEM", None), ("RESULT", None)]),
+	("SORTBY", &[("A", None), ("B", None), ("RESULT", Some(VarType::Boolean))])
+];
+
+/// Something that would go wrong when a line runs
+#[derive(Clone, Debug)]
+pub enum Problem {
+	/// A variable is used before any `DECL` of it has run
+	Undeclared(String),
+	/// A variable is used after it's been freed
+	Freed(String),
+	/// A variable or literal is the wrong type for its parameter: its name, what the parameter needs, and its type
+	WrongType(String, &'static str, VarType),
+	/// Anything else that stops the line whatever's in the variables, like a label that doesn't exist
+	Error(Error)
+}
+
+impl Display for Problem {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Problem::Undeclared(name) => write!(f, "{} is used before it's declared", name),
+			Problem::Freed(name) => write!(f, "{} is used after it's freed", name),
+			Problem::WrongType(name, expected, found) => write!(f, "expected {}, but {} is {}", expected, name, a(*found)),
+			Problem::Error(error) => write!(f, "{}", error)
+		}
+	}
+}
+
+/// A problem found by [`Program::check`], along with where it is
+#[derive(Clone, Debug)]
+pub struct TypeError {
+	pub line: usize,
+	/// Which of the command's parameters the problem is with, counting from 0, if it's one in particular
+	pub param: Option<usize>,
+	pub problem: Problem
+}
+
+impl Display for TypeError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "error on line {}: {}", self.line, self.problem)
+	}
+}
+
+/// A type's name with "a" or "an" in front of it
+pub(crate) fn a(var_type: VarType) -> String {
+	let article = if var_type == VarType::Integer {"an"} else {"a"};
+	format!("{} {}", article, var_type)
+}
+
+/// What's known about a variable on the way into a line
+#[derive(Clone, PartialEq)]
+pub(crate) enum Status {
+	Undeclared,
+	Freed,
+	/// Declared, with its type if it's the same every way the line can be reached,
+	/// and whether it might have been given a value since
+	Declared(Option<VarType>, bool),
+	/// Declared on some ways to the line but not others
+	Maybe
+}
+
+impl Status {
+	fn join(&self, other: &Status) -> Status {
+		match (self, other) {
+			(a, b) if a == b => a.clone(),
+			(Status::Undeclared | Status::Freed, Status::Undeclared | Status::Freed) => Status::Freed,
+			(Status::Declared(a, a_set), Status::Declared(b, b_set)) => Status::Declared(if a == b {*a} else {None}, *a_set || *b_set),
+			_ => Status::Maybe
+		}
+	}
+}
+
+/// The status of every variable that isn't undeclared
+#[derive(Clone, PartialEq, Default)]
+pub(crate) struct State(HashMap<String, Status>);
+
+impl State {
+	pub(crate) fn get(&self, name: &str) -> &Status {
+		self.0.get(name).unwrap_or(&Status::Undeclared)
+	}
+
+	fn set(&mut self, name: &str, status: Status) {
+		self.0.insert(name.to_string(), status);
+	}
+
+	fn declared(mut self, vars: &[(&str, Option<VarType>)]) -> Self {
+		for (name, var_type) in vars {
+			self.set(name, Status::Declared(*var_type, true));
+		}
+		self
+	}
+
+	fn join(&self, other: &State) -> State {
+		let mut joined = State::default();
+		for name in self.0.keys().chain(other.0.keys()) {
+			joined.set(name, self.get(name).join(other.get(name)));
+		}
+		joined
+	}
+}
+
+/// A line that runs a command
+pub(crate) struct Line {
+	pub name: String,
+	pub words: Vec<String>,
+	pub spec: Rc<CommandSpec>
+}
+
+impl Line {
+	/// Each parameter the line is given, along with the word it's given
+	pub(crate) fn params(&self) -> impl Iterator<Item = (usize, Param, &str)> {
+		self.spec.signature.params.iter().zip(&self.words).enumerate().map(|(index, (param, word))| (index, *param, word.as_str()))
+	}
+}
+
+/// The lines of a program, and where each one can go next
+pub(crate) struct Flow {
+	pub lines: Vec<Result<Option<Line>, Error>>,
+	/// The lines each label is defined on
+	pub label_lines: HashMap<String, Vec<usize>>,
+	/// The lines after each one that calls a routine, which `RETURN` goes back to
+	return_lines: Vec<usize>,
+	/// The `CATCH` line for errors on each line
+	catch_lines: Vec<Option<usize>>,
+	/// The labels from every `ON ERROR GOTO`
+	error_labels: Vec<String>
+}
+
+impl Flow {
+	pub(crate) fn line(&self, line: usize) -> Option<&Line> {
+		self.lines[line].as_ref().ok().and_then(Option::as_ref)
+	}
+
+	/// The lines after where a label is defined, which jumping to it carries on from
+	fn targets(&self, label: &str) -> impl Iterator<Item = usize> + '_ {
+		self.label_lines.get(label).into_iter().flatten().map(|line| line + 1)
+	}
+
+	/// Where the program can go after running a line, and what it knows about its variables there
+	fn next(&self, program: &Program, line: usize, before: &State, after: State) -> Vec<(usize, State)> {
+		let mut next = Vec::new();
+		// an error goes to the innermost TRY block or the ON ERROR GOTO handler
+		if let Some(catch_line) = self.catch_lines[line] {
+			next.push((catch_line + 1, before.clone().declared(&[(&program.block_names[&catch_line], Some(VarType::Str))])));
+		}
+		for label in &self.error_labels {
+			next.extend(self.targets(label).map(|target| (target, before.clone())));
+		}
+
+		let Some(command) = self.line(line) else {
+			next.push((line + 1, after));
+			return next;
+		};
+		let label = command.words.first().map(String::as_str).unwrap_or_default();
+		match command.name.as_str() {
+			"JMP" => next.extend(self.targets(label).map(|target| (target, after.clone()))),
+			"JMPVAR" => next.extend(self.label_lines.values().flatten().map(|target| (target + 1, after.clone()))),
+			"RETURN" => next.extend(self.return_lines.iter().map(|target| (*target, after.clone()))),
+			"EXIT" => (),
+			"CATCH" => next.push((program.catch_blocks[&line] + 1, after)),
+			"TEST" => next.push((program.test_blocks[&line] + 1, after)),
+			name => {
+				if CONDITIONAL_JUMPS.contains(&name) || name == "CALL" {
+					next.extend(self.targets(label).map(|target| (target, after.clone())));
+				} else {
+					let vars = ROUTINE_VARS.iter().find(|(command, _)| *command == name).map_or(&[][..], |(_, vars)| vars);
+					for (_, param, word) in command.params() {
+						if let Param::Label = param {
+							next.extend(self.targets(word).map(|target| (target, after.clone().declared(vars))));
+						}
+					}
+				}
+				next.push((line + 1, after));
+			}
+		}
+		next
+	}
+}
+
+impl Program {
+	/// Checks the types of every command's parameters without running the program, and that
+	/// every variable it uses has been declared and not freed. Gives back every problem it finds
+	pub fn check(&mut self) -> Vec<TypeError> {
+		if let Err(error) = self.load_source() {
+			return vec![TypeError {line: error.line, param: None, problem: Problem::Error(error.error)}];
+		}
+		let flow = self.flow();
+		let states = self.follow(&flow);
+
+		let mut errors = Vec::new();
+		for (line, state) in states.iter().enumerate() {
+			let Some(state) = state else {continue};
+			match &flow.lines[line] {
+				Ok(Some(command)) => {
+					let problems = self.check_line(command, state).into_iter()
+						.map(|(param, problem)| TypeError {line: line + 1, param, problem});
+					errors.extend(problems);
+				},
+				Ok(None) => (),
+				Err(error) => errors.push(TypeError {line: line + 1, param: None, problem: Problem::Error(error.clone())})
+			}
+		}
+		errors
+	}
+
+	pub(crate) fn flow(&self) -> Flow {
+		let lines: Vec<Result<Option<Line>, Error>> = self.lines.iter().map(|line| {
+			Ok(UnparsedCommand::from_line(line.clone(), &self.commands)?.map(|command| Line {
+				spec: self.commands.spec(&command.command_name).unwrap(),
+				name: command.command_name,
+				words: command.parameters
+			}))
+		}).collect();
+
+		let mut flow = Flow {
+			label_lines: HashMap::new(),
+			return_lines: Vec::new(),
+			catch_lines: vec![None; lines.len()],
+			error_labels: Vec::new(),
+			lines
+		};
+		for line in 0..flow.lines.len() {
+			let Some(command) = flow.line(line) else {continue};
+			let calls = command.name == "CALL" || (!CONDITIONAL_JUMPS.contains(&command.name.as_str())
+				&& command.name != "JMP" && command.spec.signature.params.contains(&Param::Label));
+			match (command.name.as_str(), command.words.as_slice()) {
+				("LABEL", [name, ..]) => flow.label_lines.entry(name.clone()).or_default().push(line),
+				("ON", [_, _, label, ..]) if label != "0" => flow.error_labels.push(label.clone()),
+				_ if calls => flow.return_lines.push(line + 1),
+				_ => ()
+			}
+		}
+		// inner blocks start later, so they're put in last
+		let mut try_blocks: Vec<(usize, usize)> = self.try_blocks.iter().map(|(try_line, catch_line)| (*try_line, *catch_line)).collect();
+		try_blocks.sort();
+		for (try_line, catch_line) in try_blocks {
+			for line in try_line + 1..catch_line {
+				flow.catch_lines[line] = Some(catch_line);
+			}
+		}
+		flow
+	}
+
+	/// Works out what's known about the variables on the way into each line, or `None` if the line can't be reached
+	pub(crate) fn follow(&self, flow: &Flow) -> Vec<Option<State>> {
+		let start = State::default().declared(BUILT_IN_VARS);
+		let mut states: Vec<Option<State>> = vec![None; self.lines.len()];
+		let mut queue = VecDeque::new();
+		// tests start from their own line, with fresh variables
+		let mut entries: Vec<usize> = self.test_blocks.keys().map(|line| line + 1).collect();
+		entries.push(0);
+		for line in entries {
+			queue.push_back((line, start.clone()));
+		}
+
+		while let Some((line, state)) = queue.pop_front() {
+			if line >= states.len() {
+				continue;
+			}
+			let joined = match &states[line] {
+				Some(known) if known.join(&state) == *known => continue,
+				Some(known) => known.join(&state),
+				None => state
+			};
+			let after = self.run_on(flow.line(line), joined.clone());
+			queue.extend(flow.next(self, line, &joined, after));
+			states[line] = Some(joined);
+		}
+		states
+	}
+
+	/// What a line does to the variables
+	fn run_on(&self, command: Option<&Line>, mut state: State) -> State {
+		let Some(command) = command else {return state};
+		for (_, param, word) in command.params() {
+			if let (Param::Location(_), Status::Declared(var_type, _)) = (param, state.get(word)) {
+				state.set(word, Status::Declared(*var_type, true));
+			}
+		}
+		match (command.name.as_str(), command.words.as_slice()) {
+			("DECL", [name, var_type, ..]) => state.set(name, Status::Declared(VarType::from_name(var_type.clone()).ok(), false)),
+			("FREE", [name, ..]) => state.set(name, Status::Freed),
+			_ => ()
+		}
+		state
+	}
+
+	/// Finds the problems with one line, given what's known about the variables on the way into it
+	fn check_line(&self, command: &Line, state: &State) -> Vec<(Option<usize>, Problem)> {
+		let mut problems = Vec::new();
+		let uses_var = |word: &str, kind: Option<(Kind, bool)>| match state.get(word) {
+			Status::Undeclared if !self.declares(word) => Some(Problem::Error(Error::UnknownVariable(word.to_string()))),
+			Status::Undeclared => Some(Problem::Undeclared(word.to_string())),
+			Status::Freed => Some(Problem::Freed(word.to_string())),
+			Status::Declared(Some(var_type), _) => kind.filter(|(kind, _)| !kind.matches(&var_type.zero()))
+				.map(|(kind, location)| Problem::WrongType(word.to_string(), kind.expected(location), *var_type)),
+			_ => None
+		};
+
+		for (index, param, word) in command.params() {
+			let problem = match param {
+				Param::Location(kind) => uses_var(word, Some((kind, true))),
+				Param::Operand(kind) => uses_var(word, Some((kind, false))),
+				Param::Literal(kind) => {
+					let literal = word.trim();
+					match (state.get(literal), literal_type(literal)) {
+						(Status::Undeclared | Status::Freed, Some(var_type)) => Some(var_type)
+							.filter(|var_type| !kind.matches(&var_type.zero()))
+							.map(|var_type| Problem::WrongType(literal.to_string(), kind.expected(false), var_type)),
+						(Status::Undeclared, None) if !self.declares(literal) => Some(Problem::Error(Error::InvalidLiteral(literal.to_string()))),
+						_ => uses_var(literal, Some((kind, false)))
+					}
+				},
+				Param::Label => Some(Problem::Error(Error::UnknownLabel(word.to_string()))).filter(|_| !self.labels.contains_key(word)),
+				Param::Word => match (command.name.as_str(), index) {
+					("DECL", 1) => VarType::from_name(word.to_string()).err().map(Problem::Error),
+					("FREE", 0) => uses_var(word, None),
+					("ON", 2) if word != "0" => Some(Problem::Error(Error::UnknownLabel(word.to_string()))).filter(|_| !self.labels.contains_key(word)),
+					_ => None
+				}
+			};
+			problems.extend(problem.map(|problem| (Some(index), problem)));
+		}
+		problems
+	}
+
+	/// Whether any line could declare a variable with this name
+	fn declares(&self, name: &str) -> bool {
+		ROUTINE_VARS.iter().any(|(_, vars)| vars.iter().any(|(var, _)| *var == name))
+			|| self.block_names.values().any(|block| block == name)
+			|| self.lines.iter().any(|line| {
+				let mut words = line.split_ascii_whitespace();
+				words.next().is_some_and(|command| command.eq_ignore_ascii_case("DECL")) && words.next() == Some(name)
+			})
+	}
+}
+
+/// The type of a literal, if it is one
+pub(crate) fn literal_type(literal: &str) -> Option<VarType> {
+	if literal.starts_with('[') {
+		return Some(VarType::List);
+	}
+	parse_scalar(literal).ok().as_ref().map(VarType::of)
+}
@@ -0,0 +1,130 @@
+//! Listing a program's bytecode, for seeing what the compiler made of it.
+
+use crate::bytecode::{Bytecode, Op};
+use crate::{Param, Program, RuntimeError, Variable};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+/// Which lines read and write a variable
+#[derive(Default)]
+struct Uses {
+	reads: BTreeSet<usize>,
+	writes: BTreeSet<usize>
+}
+
+fn show_constant(value: &Variable) -> String {
+	match value {
+		Variable::Str(s) => format!("{:?}", s),
+		Variable::Char(c) => format!("{:?}", c),
+		value => value.to_string()
+	}
+}
+
+fn show_lines(lines: &BTreeSet<usize>) -> String {
+	if lines.is_empty() {
+		return "no lines".to_string();
+	}
+	lines.iter().map(|line| (line + 1).to_string()).collect::<Vec<_>>().join(", ")
+}
+
+impl Program {
+	/// Compiles the program if it hasn't been, and lists its bytecode: each line's instructions
+	/// with their addresses, where each label goes, the name in each variable slot, and which
+	/// lines read and write each variable
+	pub fn disassemble(&mut self) -> Result<String, RuntimeError> {
+		let vm = std::mem::replace(&mut self.vm, true);
+		let loaded = self.load();
+		self.vm = vm;
+		loaded?;
+		let compiled = self.compiled.clone().unwrap();
+		let bytecode = &compiled.bytecode;
+		let address = |line: usize| bytecode.line_starts[line];
+
+		let mut out = String::new();
+		let mut uses: BTreeMap<&str, Uses> = BTreeMap::new();
+		for line in 0..bytecode.line_count() {
+			let ops = address(line)..address(line + 1);
+			if ops.is_empty() {
+				continue;
+			}
+			match self.lines.get(line) {
+				Some(source) => writeln!(out, "line {}: {}", line + 1, source.trim()).unwrap(),
+				None => writeln!(out, "line {}:", line + 1).unwrap()
+			}
+			for index in ops.clone() {
+				writeln!(out, "  {:04}  {}", index, self.describe(bytecode, bytecode.ops[index])).unwrap();
+			}
+
+			let ops = &bytecode.ops[ops];
+			for op in ops {
+				match *op {
+					Op::Load(slot, Param::Location(_)) | Op::Store(slot) => {
+						uses.entry(&bytecode.slots[slot]).or_default().writes.insert(line);
+					},
+					Op::Load(slot, _) => {uses.entry(&bytecode.slots[slot]).or_default().reads.insert(line);},
+					_ => ()
+				}
+			}
+			// these commands name the variable they declare, free or set as a word
+			if let (Some(Op::Const(name)), Some(Op::Invoke(command, _))) = (ops.first(), ops.last()) {
+				if let ("DECL" | "FREE" | "CATCH", Variable::Str(name)) = (bytecode.commands[*command].0.as_str(), &bytecode.constants[*name]) {
+					uses.entry(name).or_default().writes.insert(line);
+				}
+			}
+		}
+		// literals might name variables, but they're only worth listing if there's one by that name
+		for (index, op) in bytecode.ops.iter().enumerate() {
+			let names: Vec<&str> = match *op {
+				Op::LoadOr(slot, _, _) => vec![&bytecode.slots[slot]],
+				Op::Parse(literal, _) => match &bytecode.constants[literal] {
+					Variable::Str(literal) => literal.trim().trim_start_matches('[').trim_end_matches(']').split(',').map(str::trim).collect(),
+					_ => Vec::new()
+				},
+				_ => Vec::new()
+			};
+			for name in names {
+				if let Some(uses) = uses.get_mut(name) {
+					uses.reads.insert(bytecode.line_of(index));
+				}
+			}
+		}
+
+		writeln!(out, "\nlabels:").unwrap();
+		let mut labels: Vec<(&String, usize)> = self.labels.iter().map(|(name, label)| (name, label.0)).collect();
+		labels.sort_by_key(|(_, line)| *line);
+		for (name, line) in labels {
+			writeln!(out, "  {}  line {}  @{:04}", name, line + 1, address(line)).unwrap();
+		}
+
+		writeln!(out, "\nslots:").unwrap();
+		for (slot, name) in bytecode.slots.iter().enumerate() {
+			writeln!(out, "  ${}  {}", slot, name).unwrap();
+		}
+
+		writeln!(out, "\nvariables:").unwrap();
+		for (name, uses) in uses {
+			writeln!(out, "  {}  read on {}  written on {}", name, show_lines(&uses.reads), show_lines(&uses.writes)).unwrap();
+		}
+		Ok(out)
+	}
+
+	fn describe(&self, bytecode: &Bytecode, op: Op) -> String {
+		let slot = |slot: usize| format!("${}:{}", slot, bytecode.slots[slot]);
+		let constant = |index: usize| format!("#{}:{}", index, show_constant(&bytecode.constants[index]));
+		match op {
+			Op::Load(index, _) => format!("LOAD     {}", slot(index)),
+			Op::LoadOr(index, value, _) => format!("LOADOR   {} {}", slot(index), constant(value)),
+			Op::Parse(value, _) => format!("PARSE    {}", constant(value)),
+			Op::Const(value) => format!("CONST    {}", constant(value)),
+			Op::Label(value) => {
+				let target = bytecode.constants[value].to_str().ok()
+					.and_then(|name| self.labels.get(&name))
+					.map_or_else(|| "no label".to_string(), |label| format!("line {} @{:04}", label.0 + 1, bytecode.line_starts[label.0]));
+				format!("LABEL    {} -> {}", constant(value), target)
+			},
+			Op::Invoke(command, args) => format!("INVOKE   {} {}", bytecode.commands[command].0, args),
+			Op::Store(index) => format!("STORE    {}", slot(index)),
+			Op::MissingParameters(command) => format!("MISSING  {}", bytecode.commands[command].0)
+		}
+	}
+}
@@ -0,0 +1,139 @@
+//! Laying out a program's source the same way every time.
+
+use crate::{split_words, Program};
+
+/// Something that indents the lines inside it
+#[derive(Clone, Copy, PartialEq)]
+enum Block {
+	/// The lines after a `LABEL`, up to the next one
+	Label,
+	Try,
+	Catch,
+	Test
+}
+
+enum Layout {
+	Blank,
+	/// A line that isn't a command, kept as it is
+	Comment(String),
+	Command(Vec<String>)
+}
+
+/// Closes the innermost open block of a kind, along with any label bodies inside it, giving back
+/// how deep it was. Nothing is closed if there isn't one
+fn close(blocks: &mut Vec<Block>, block: Block) -> Option<usize> {
+	let depth = blocks.iter().rposition(|open| *open == block)?;
+	blocks.truncate(depth);
+	Some(depth)
+}
+
+impl Program {
+	/// The program's source with its command names in upper case, its blocks and label bodies
+	/// indented, and the operands of neighbouring lines lined up. Lines that aren't commands are
+	/// comments, which are kept as they are
+	pub fn format(&self) -> String {
+		let mut lines: Vec<(usize, Layout)> = Vec::new();
+		let mut blocks = Vec::new();
+		// comments are indented like the line after them
+		let mut comments = Vec::new();
+		for line in self.program.lines() {
+			let mut words = split_words(line);
+			let Some(first) = words.first() else {
+				lines.push((0, Layout::Blank));
+				continue;
+			};
+			if self.commands.get(first).is_none() {
+				comments.push(lines.len());
+				lines.push((0, Layout::Comment(line.trim().to_string())));
+				continue;
+			}
+
+			words[0] = first.to_uppercase();
+			let depth = match words[0].as_str() {
+				"LABEL" => {
+					if blocks.last() == Some(&Block::Label) {
+						blocks.pop();
+					}
+					blocks.push(Block::Label);
+					blocks.len() - 1
+				},
+				"TRY" => {
+					blocks.push(Block::Try);
+					blocks.len() - 1
+				},
+				"CATCH" => match close(&mut blocks, Block::Try) {
+					Some(depth) => {
+						blocks.push(Block::Catch);
+						depth
+					},
+					None => blocks.len()
+				},
+				"ENDTRY" => close(&mut blocks, Block::Catch).unwrap_or(blocks.len()),
+				"TEST" => {
+					blocks.push(Block::Test);
+					blocks.len() - 1
+				},
+				"ENDTEST" => close(&mut blocks, Block::Test).unwrap_or(blocks.len()),
+				"ON" => {
+					// ERROR and GOTO are part of the command
+					for word in words.iter_mut().skip(1).take(2) {
+						*word = word.to_uppercase();
+					}
+					blocks.len()
+				},
+				_ => blocks.len()
+			};
+			for comment in comments.drain(..) {
+				lines[comment].0 = depth;
+			}
+			lines.push((depth, Layout::Command(words)));
+		}
+		for comment in comments {
+			lines[comment].0 = blocks.len();
+		}
+
+		let mut formatted = String::new();
+		let mut start = 0;
+		while start < lines.len() {
+			// commands next to each other at the same depth have their operands lined up
+			let (depth, layout) = &lines[start];
+			let mut end = start + 1;
+			if let Layout::Command(_) = layout {
+				while end < lines.len() && lines[end].0 == *depth && matches!(lines[end].1, Layout::Command(_)) {
+					end += 1;
+				}
+			}
+			let mut widths = Vec::new();
+			for (_, layout) in &lines[start..end] {
+				if let Layout::Command(words) = layout {
+					for (column, word) in words[..words.len() - 1].iter().enumerate() {
+						if column == widths.len() {
+							widths.push(0);
+						}
+						widths[column] = widths[column].max(word.chars().count());
+					}
+				}
+			}
+
+			for (depth, layout) in &lines[start..end] {
+				let indent = "\t".repeat(*depth);
+				match layout {
+					Layout::Blank => (),
+					Layout::Comment(comment) => formatted.push_str(&format!("{}{}", indent, comment)),
+					Layout::Command(words) => {
+						formatted.push_str(&indent);
+						for (column, word) in words.iter().enumerate() {
+							match widths.get(column).filter(|_| column < words.len() - 1) {
+								Some(width) => formatted.push_str(&format!("{:width$} ", word, width = width)),
+								None => formatted.push_str(word)
+							}
+						}
+					}
+				}
+				formatted.push('\n');
+			}
+			start = end;
+		}
+		formatted
+	}
+}
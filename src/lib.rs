@@ -0,0 +1,3109 @@
+mod bytecode;
+mod check;
+mod disasm;
+mod format;
+mod lint;
+mod optimize;
+mod snapshot;
+mod symbols;
+
+pub use bytecode::Bytecode;
+pub use check::{Problem, TypeError};
+pub use lint::{Level, Lint, LintConfig, Warning};
+pub use symbols::{Symbol, SymbolKind};
+use bytecode::Compiled;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::fmt::Display;
+
+#[derive(Clone, Debug)]
+pub enum Error {
+	MissingParameters(String),
+	UnknownVariable(String),
+	UnknownLabel(String),
+	UnknownType(String),
+	InvalidLiteral(String),
+	/// A value or location had a different type from the one a command needs
+	WrongType(&'static str),
+	Incomparable,
+	IndexOutOfRange(usize),
+	InvalidArgument(&'static str),
+	/// The block structure of the program, like a `TRY` without an `ENDTRY`, is broken
+	InvalidBlock(&'static str),
+	ReturnOutsideRoutine,
+	AssertionFailed(String),
+	Io(String),
+	/// An error from a command registered by the host
+	Host(String),
+	/// The program went over one of its limits. These can't be caught
+	LimitExceeded(Limit),
+	PermissionDenied(Capability),
+	/// An `INPUT` has to wait for a line. Stepping through the program waits for it, but otherwise it's an error
+	WaitingForInput,
+	InvalidSnapshot(String),
+	InvalidBytecode(String),
+	InvalidLintConfig(String)
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Error::MissingParameters(command) => write!(f, "{} is missing parameters", command),
+			Error::UnknownVariable(name) => write!(f, "there is no variable named {}", name),
+			Error::UnknownLabel(name) => write!(f, "there is no label named {}", name),
+			Error::UnknownType(name) => write!(f, "{} is not a type", name),
+			Error::InvalidLiteral(literal) => write!(f, "{} is not a valid literal", literal),
+			Error::WrongType(expected) => write!(f, "expected {}", expected),
+			Error::Incomparable => write!(f, "these values can't be ordered"),
+			Error::IndexOutOfRange(index) => write!(f, "index {} is out of range", index),
+			Error::InvalidArgument(message) => write!(f, "{}", message),
+			Error::InvalidBlock(message) => write!(f, "{}", message),
+			Error::ReturnOutsideRoutine => write!(f, "RETURN outside of a routine"),
+			Error::AssertionFailed(message) => write!(f, "assertion failed: {}", message),
+			Error::Io(message) => write!(f, "{}", message),
+			Error::Host(message) => write!(f, "{}", message),
+			Error::LimitExceeded(limit) => write!(f, "the program went over its {} limit", limit),
+			Error::PermissionDenied(capability) => write!(f, "the program isn't allowed to use {}", capability),
+			Error::WaitingForInput => write!(f, "there is no input yet"),
+			Error::InvalidSnapshot(message) => write!(f, "the snapshot is invalid: {}", message),
+			Error::InvalidBytecode(message) => write!(f, "the bytecode is invalid: {}", message),
+			Error::InvalidLintConfig(message) => write!(f, "the lint config is invalid: {}", message)
+		}
+	}
+}
+
+/// An error which stopped the program, along with the line it happened on
+#[derive(Clone, Debug)]
+pub struct RuntimeError {
+	pub line: usize,
+	pub error: Error
+}
+
+impl Display for RuntimeError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "error on line {}: {}", self.line, self.error)
+	}
+}
+
+/// Where each word on a line starts and ends
+pub fn word_spans(line: &str) -> Vec<std::ops::Range<usize>> {
+	let mut spans = Vec::new();
+	let mut start = None;
+	let mut in_quotes = false;
+	let mut escaped = false;
+	for (index, c) in line.char_indices() {
+		if in_quotes {
+			if escaped {
+				escaped = false;
+			} else if c == '\\' {
+				escaped = true;
+			} else if c == '"' {
+				in_quotes = false;
+			}
+		} else if c.is_whitespace() {
+			if let Some(start) = start.take() {
+				spans.push(start..index);
+			}
+		} else {
+			in_quotes = c == '"';
+			start.get_or_insert(index);
+		}
+	}
+	if let Some(start) = start {
+		spans.push(start..line.len());
+	}
+	spans
+}
+
+/// Splits a line on whitespace, except inside double quotes
+fn split_words(line: &str) -> Vec<String> {
+	word_spans(line).into_iter().map(|span| line[span].to_string()).collect()
+}
+
+/// Takes the quotes off of a string literal and replaces its escape sequences.
+/// The closing quote may be left off
+fn parse_string_literal(literal: &str) -> String {
+	let literal = literal.strip_prefix('"').unwrap_or(literal);
+	let mut string = String::new();
+	let mut chars = literal.chars();
+	while let Some(c) = chars.next() {
+		match c {
+			'\\' => match chars.next() {
+				Some('n') => string.push('\n'),
+				Some('t') => string.push('\t'),
+				Some(other) => string.push(other),
+				None => string.push('\\')
+			},
+			'"' if chars.as_str().is_empty() => (),
+			_ => string.push(c)
+		}
+	}
+	string
+}
+
+struct UnparsedCommand {
+	pub command_name : String,
+	pub parameters : Vec<String>
+}
+
+impl UnparsedCommand {
+	fn from_line(line: String, commands: &Registry) -> Result<Option<Self>, Error> {
+		let words = split_words(&line);
+		let first = match words.first() {
+			Some(first) => first,
+			None => return Ok(None)
+		};
+		if let Some(spec) = commands.get(first) {
+			let command_name = first.to_uppercase();
+			if words.len() <= spec.signature.required() {
+				return Err(Error::MissingParameters(command_name));
+			}
+			let parameters = words[1..].to_vec();
+			Ok(Some(UnparsedCommand {command_name, parameters}))
+		} else {
+			Ok(None)
+		}
+	}
+}
+
+/// What a command does with one of its parameters
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Param {
+	/// A variable which the command can change
+	Location(Kind),
+	/// A variable which the command reads
+	Operand(Kind),
+	/// A variable or a literal which the command reads
+	Literal(Kind),
+	/// The name of a label, passed on as a str
+	Label,
+	/// A word passed on as a str, like a type name
+	Word
+}
+
+/// The kinds of value a parameter will take
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kind {
+	Any,
+	/// A natural, int or float
+	Number,
+	/// A natural or int
+	Integer,
+	Bool,
+	Char,
+	Str,
+	List
+}
+
+impl Kind {
+	pub(crate) const ALL: [Kind; 7] = [Kind::Any, Kind::Number, Kind::Integer, Kind::Bool, Kind::Char, Kind::Str, Kind::List];
+
+	pub fn matches(&self, value: &Variable) -> bool {
+		match self {
+			Kind::Any => true,
+			Kind::Number => matches!(value, Variable::Natural(_) | Variable::Int(_) | Variable::Float(_)),
+			Kind::Integer => matches!(value, Variable::Natural(_) | Variable::Int(_)),
+			Kind::Bool => matches!(value, Variable::Bool(_)),
+			Kind::Char => matches!(value, Variable::Char(_)),
+			Kind::Str => matches!(value, Variable::Str(_)),
+			Kind::List => matches!(value, Variable::List(_))
+		}
+	}
+
+	fn check(&self, value: Variable, location: bool) -> Result<Variable, Error> {
+		if self.matches(&value) {
+			return Ok(value);
+		}
+		Err(Error::WrongType(self.expected(location)))
+	}
+
+	/// What a parameter of this kind needs, for error messages
+	pub(crate) fn expected(&self, location: bool) -> &'static str {
+		match (self, location) {
+			(Kind::Any, _) => unreachable!(),
+			(Kind::Number, false) => "a number",
+			(Kind::Number, true) => "a number location",
+			(Kind::Integer, false) => "a natural or int",
+			(Kind::Integer, true) => "a natural or int location",
+			(Kind::Bool, false) => "a bool",
+			(Kind::Bool, true) => "a bool location",
+			(Kind::Char, false) => "a char",
+			(Kind::Char, true) => "a char location",
+			(Kind::Str, false) => "a str",
+			(Kind::Str, true) => "a str location",
+			(Kind::List, false) => "a list",
+			(Kind::List, true) => "a list location"
+		}
+	}
+}
+
+impl Param {
+	/// Checks that a parameter's value is the kind it needs to be
+	fn check(&self, value: Variable) -> Result<Variable, Error> {
+		match self {
+			Param::Location(kind) => kind.check(value, true),
+			Param::Operand(kind) | Param::Literal(kind) => kind.check(value, false),
+			Param::Label | Param::Word => Ok(value)
+		}
+	}
+}
+
+/// The parameters a command takes
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Signature {
+	pub params: Vec<Param>,
+	/// How many of the parameters at the end can be left out
+	pub optional: usize
+}
+
+impl Signature {
+	pub fn new(params: Vec<Param>) -> Self {
+		Signature {params, optional: 0}
+	}
+
+	/// Adds a parameter which can be left out
+	pub fn with_optional(mut self, param: Param) -> Self {
+		self.params.push(param);
+		self.optional += 1;
+		self
+	}
+
+	pub fn required(&self) -> usize {
+		self.params.len() - self.optional
+	}
+}
+
+impl From<Vec<Param>> for Signature {
+	fn from(params: Vec<Param>) -> Self {
+		Signature::new(params)
+	}
+}
+
+/// Runs a command, given the values of its parameters in order.
+/// Changes to the values of `Location` parameters are saved to their variables afterwards
+pub type Handler = Rc<dyn Fn(&mut Program, &mut [Variable]) -> Result<CommandResponse, Error>>;
+
+pub struct CommandSpec {
+	pub signature: Signature,
+	pub handler: Handler,
+	/// Whether this is one of [`Registry::builtins`], rather than a command added by the host
+	pub(crate) builtin: bool
+}
+
+/// The commands a program can use, by name
+#[derive(Clone, Default)]
+pub struct Registry {
+	commands: HashMap<String, Rc<CommandSpec>>
+}
+
+impl Registry {
+	/// A registry with no commands in it
+	pub fn new() -> Self {
+		Registry::default()
+	}
+
+	/// Adds a command, replacing any command with the same name
+	pub fn register<F>(&mut self, name: &str, signature: impl Into<Signature>, handler: F)
+	where F: Fn(&mut Program, &mut [Variable]) -> Result<CommandResponse, Error> + 'static {
+		self.commands.insert(name.to_uppercase(), Rc::new(CommandSpec {signature: signature.into(), handler: Rc::new(handler), builtin: false}));
+	}
+
+	pub fn get(&self, name: &str) -> Option<&CommandSpec> {
+		self.commands.get(&name.to_uppercase()).map(|spec| spec.as_ref())
+	}
+
+	pub fn names(&self) -> impl Iterator<Item = &str> {
+		self.commands.keys().map(|name| name.as_str())
+	}
+
+	fn spec(&self, name: &str) -> Option<Rc<CommandSpec>> {
+		self.commands.get(&name.to_uppercase()).cloned()
+	}
+}
+
+#[derive(Clone)]
+pub enum Variable {
+	Natural(u32),
+	Int(i32),
+	Float(f32),
+	Char(char),
+	Bool(bool),
+	Str(String),
+	List(Vec<Variable>)
+}
+
+impl Variable {
+	pub fn to_float(&self) -> Result<f32, Error> {
+		match self {
+			Variable::Natural(n) => Ok(*n as f32),
+			Variable::Int(i) => Ok(*i as f32),
+			Variable::Float(f) => Ok(*f),
+			_ => Err(Error::WrongType("a number"))
+		}
+	}
+
+	/// Roughly how many bytes the value takes up
+	fn size(&self) -> usize {
+		std::mem::size_of::<Variable>() + match self {
+			Variable::Str(s) => s.len(),
+			Variable::List(l) => l.iter().map(Variable::size).sum(),
+			_ => 0
+		}
+	}
+
+	/// The length of the longest str or list in the value
+	fn longest(&self) -> usize {
+		match self {
+			Variable::Str(s) => s.chars().count(),
+			Variable::List(l) => l.iter().map(Variable::longest).fold(l.len(), usize::max),
+			_ => 0
+		}
+	}
+
+	/// Rounds a number to the nearest natural
+	pub fn to_nat(&self) -> Result<u32, Error> {
+		Ok(self.to_float()?.round().abs() as u32)
+	}
+
+	pub fn to_integer(&self) -> Result<i64, Error> {
+		match self {
+			Variable::Natural(n) => Ok(*n as i64),
+			Variable::Int(i) => Ok(*i as i64),
+			_ => Err(Error::WrongType("a natural or int"))
+		}
+	}
+
+	pub fn to_bool(&self) -> Result<bool, Error> {
+		match self {
+			Variable::Bool(b) => Ok(*b),
+			_ => Err(Error::WrongType("a bool"))
+		}
+	}
+
+	pub fn to_str(&self) -> Result<String, Error> {
+		match self {
+			Variable::Str(s) => Ok(s.clone()),
+			_ => Err(Error::WrongType("a str"))
+		}
+	}
+
+	pub fn to_list(&self) -> Result<Vec<Variable>, Error> {
+		match self {
+			Variable::List(l) => Ok(l.clone()),
+			_ => Err(Error::WrongType("a list"))
+		}
+	}
+}
+
+impl Variable {
+	fn kind_rank(&self) -> u8 {
+		match self {
+			Variable::Bool(_) => 0,
+			Variable::Natural(_) | Variable::Int(_) | Variable::Float(_) => 1,
+			Variable::Char(_) => 2,
+			Variable::Str(_) => 3,
+			Variable::List(_) => 4
+		}
+	}
+
+	fn as_f64(&self) -> f64 {
+		match self {
+			Variable::Natural(n) => *n as f64,
+			Variable::Int(i) => *i as f64,
+			Variable::Float(f) => *f as f64,
+			_ => unreachable!()
+		}
+	}
+
+	/// Compares two values, promoting numbers to a common type. Strings and lists compare
+	/// lexicographically. Values of different kinds can't be compared, and neither can NaN
+	pub fn compare(&self, other: &Variable) -> Option<std::cmp::Ordering> {
+		match (self, other) {
+			(Variable::Bool(a), Variable::Bool(b)) => Some(a.cmp(b)),
+			(Variable::Char(a), Variable::Char(b)) => Some(a.cmp(b)),
+			(Variable::Str(a), Variable::Str(b)) => Some(a.cmp(b)),
+			(Variable::Natural(a), Variable::Natural(b)) => Some(a.cmp(b)),
+			(Variable::Int(a), Variable::Int(b)) => Some(a.cmp(b)),
+			(Variable::List(a), Variable::List(b)) => {
+				for (a, b) in a.iter().zip(b.iter()) {
+					let ordering = a.compare(b)?;
+					if ordering != std::cmp::Ordering::Equal {
+						return Some(ordering);
+					}
+				}
+				Some(a.len().cmp(&b.len()))
+			},
+			_ if self.kind_rank() == 1 && other.kind_rank() == 1 => self.as_f64().partial_cmp(&other.as_f64()),
+			_ => None
+		}
+	}
+
+	/// The ordering used for sorting. Numbers compare by value regardless of their type,
+	/// and different kinds of values sort as `bool < numbers < char < str < list`
+	pub fn sort_cmp(&self, other: &Variable) -> std::cmp::Ordering {
+		match (self, other) {
+			(Variable::Bool(a), Variable::Bool(b)) => a.cmp(b),
+			(Variable::Char(a), Variable::Char(b)) => a.cmp(b),
+			(Variable::Str(a), Variable::Str(b)) => a.cmp(b),
+			(Variable::List(a), Variable::List(b)) => {
+				for (a, b) in a.iter().zip(b.iter()) {
+					let ordering = a.sort_cmp(b);
+					if ordering != std::cmp::Ordering::Equal {
+						return ordering;
+					}
+				}
+				a.len().cmp(&b.len())
+			},
+			_ if self.kind_rank() == 1 && other.kind_rank() == 1 => self.as_f64().total_cmp(&other.as_f64()),
+			_ => self.kind_rank().cmp(&other.kind_rank())
+		}
+	}
+}
+
+impl PartialEq for Variable {
+	fn eq(&self, other: &Self) -> bool {
+		self.compare(other) == Some(std::cmp::Ordering::Equal)
+	}
+}
+
+impl PartialOrd for Variable {
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		self.compare(other)
+	}
+}
+
+impl std::fmt::Debug for Variable {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self)
+	}
+}
+
+impl Display for Variable {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Variable::Natural(n) => write!(f, "{}", n),
+			Variable::Int(i) => write!(f, "{}", i),
+			Variable::Float(float) => write!(f, "{}", float),
+			Variable::Char(c) => write!(f, "{}", c),
+			Variable::Bool(b) => write!(f, "{}", b),
+			Variable::Str(s) => write!(f, "{}", s),
+			Variable::List(l) => write!(f, "{:?}", l)
+		}
+	}
+}
+
+/// Whether a literal is a number with a decimal point, like `1.5` or `-0.25`
+fn is_float_literal(literal: &str) -> bool {
+	let digits = literal.strip_prefix(['-', '+']).unwrap_or(literal);
+	digits.starts_with(|c: char| c.is_ascii_digit()) && digits.contains('.') && !digits.starts_with("0x")
+}
+
+/// Parses a whole number, allowing a sign, `_` separators, and `0x`, `0b` or `0o` prefixes
+fn parse_integer(literal: &str) -> Result<i64, Error> {
+	let (negative, digits) = match literal.strip_prefix('-') {
+		Some(digits) => (true, digits),
+		None => (false, literal.strip_prefix('+').unwrap_or(literal))
+	};
+	let digits = digits.replace('_', "").to_lowercase();
+	let value = if let Some(hex) = digits.strip_prefix("0x") {
+		i64::from_str_radix(hex, 16)
+	} else if let Some(bin) = digits.strip_prefix("0b") {
+		i64::from_str_radix(bin, 2)
+	} else if let Some(oct) = digits.strip_prefix("0o") {
+		i64::from_str_radix(oct, 8)
+	} else {
+		digits.parse()
+	}.map_err(|_| Error::InvalidLiteral(literal.to_string()))?;
+	Ok(if negative {-value} else {value})
+}
+
+/// Parses a literal that isn't a list
+fn parse_scalar(literal: &str) -> Result<Variable, Error> {
+	if is_float_literal(literal) {
+		Ok(Variable::Float(literal.parse().map_err(|_| Error::InvalidLiteral(literal.to_string()))?))
+	} else if literal.starts_with('-') || literal.starts_with('+') {
+		Ok(Variable::Int(i32::try_from(parse_integer(literal)?).map_err(|_| Error::InvalidLiteral(literal.to_string()))?))
+	} else if literal.starts_with('\"') {
+		Ok(Variable::Str(parse_string_literal(literal)))
+	} else if literal == "TRUE" {
+		Ok(Variable::Bool(true))
+	} else if literal == "FALSE" {
+		Ok(Variable::Bool(false))
+	} else if literal.starts_with('\'') {
+		Ok(Variable::Char(literal.chars().nth(1).ok_or_else(|| Error::InvalidLiteral(literal.to_string()))?))
+	} else {
+		Ok(Variable::Natural(u32::try_from(parse_integer(literal)?).map_err(|_| Error::InvalidLiteral(literal.to_string()))?))
+	}
+}
+
+fn radix_from_name(name: String) -> Result<u32, Error> {
+	match name.to_uppercase().as_str() {
+		"HEX" => Ok(16),
+		"BIN" => Ok(2),
+		"OCT" => Ok(8),
+		_ => Err(Error::InvalidArgument("the radix must be HEX, BIN or OCT"))
+	}
+}
+
+fn format_radix(value: i64, radix: u32) -> String {
+	let magnitude = value.unsigned_abs();
+	let digits = match radix {
+		16 => format!("{:x}", magnitude),
+		2 => format!("{:b}", magnitude),
+		8 => format!("{:o}", magnitude),
+		_ => unreachable!()
+	};
+	if value < 0 {format!("-{}", digits)} else {digits}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VarType {
+	Natural,
+	Integer,
+	Float,
+	Character,
+	Boolean,
+	Str,
+	List
+}
+
+impl VarType {
+	pub fn from_name(name: String) -> Result<Self, Error> {
+		match name.to_lowercase().as_str() {
+			"natural" => Ok(VarType::Natural),
+			"int" => Ok(VarType::Integer),
+			"float" => Ok(VarType::Float),
+			"char" => Ok(VarType::Character),
+			"bool" => Ok(VarType::Boolean),
+			"str" => Ok(VarType::Str),
+			"list" => Ok(VarType::List),
+			_ => Err(Error::UnknownType(name))
+		}
+	}
+
+	/// The type of a value
+	pub fn of(value: &Variable) -> Self {
+		match value {
+			Variable::Natural(_) => VarType::Natural,
+			Variable::Int(_) => VarType::Integer,
+			Variable::Float(_) => VarType::Float,
+			Variable::Char(_) => VarType::Character,
+			Variable::Bool(_) => VarType::Boolean,
+			Variable::Str(_) => VarType::Str,
+			Variable::List(_) => VarType::List
+		}
+	}
+
+	/// The value a variable of this type starts with
+	fn zero(&self) -> Variable {
+		match self {
+			VarType::Boolean => Variable::Bool(false),
+			VarType::Character => Variable::Char('\0'),
+			VarType::Float => Variable::Float(0.0),
+			VarType::Integer => Variable::Int(0),
+			VarType::List => Variable::List(vec![]),
+			VarType::Natural => Variable::Natural(0),
+			VarType::Str => Variable::Str(String::new())
+		}
+	}
+}
+
+impl Display for VarType {
+	/// Writes the name `DECL` knows the type by
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			VarType::Natural => "natural",
+			VarType::Integer => "int",
+			VarType::Float => "float",
+			VarType::Character => "char",
+			VarType::Boolean => "bool",
+			VarType::Str => "str",
+			VarType::List => "list"
+		})
+	}
+}
+
+#[derive(Clone)]
+pub struct Label(usize);
+
+#[derive(Clone)]
+enum Number {
+	Natural(u32),
+	Integer(i32),
+	Float(f32)
+}
+
+impl Number {
+	pub fn from_var(var: Variable) -> Result<Self, Error> {
+		match var {
+			Variable::Natural(n) => Ok(Number::Natural(n)),
+			Variable::Int(i) => Ok(Number::Integer(i)),
+			Variable::Float(f) => Ok(Number::Float(f)),
+			_ => Err(Error::WrongType("a number"))
+		}
+	}
+
+	pub fn to_float(&self) -> f32 {
+		match self {
+			Number::Natural(n) => *n as f32,
+			Number::Integer(i) => *i as f32,
+			Number::Float(f) => *f
+		}
+	}
+
+	/// The value as a whole number, unless it's a float
+	pub fn to_integer(&self) -> Option<i64> {
+		match self {
+			Number::Natural(n) => Some(*n as i64),
+			Number::Integer(i) => Some(*i as i64),
+			Number::Float(_) => None
+		}
+	}
+}
+
+#[derive(Clone)]
+struct Rng {
+	state: u64
+}
+
+impl Rng {
+	pub fn from_seed(seed: u64) -> Self {
+		Rng {state: seed}
+	}
+
+	/// Seeds from the clock and the hasher's random keys, for runs without a `--seed`
+	pub fn from_entropy() -> Self {
+		use std::hash::{BuildHasher, Hasher};
+		let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+		let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+		hasher.write_u128(nanos);
+		Rng {state: hasher.finish()}
+	}
+
+	// splitmix64
+	pub fn next_u64(&mut self) -> u64 {
+		self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+		let mut z = self.state;
+		z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+		z ^ (z >> 31)
+	}
+
+	/// A float in `[0, 1)`
+	pub fn next_float(&mut self) -> f64 {
+		(self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+	}
+
+	/// An integer in `[0, bound)`
+	pub fn below(&mut self, bound: u64) -> u64 {
+		((self.next_u64() as u128 * bound as u128) >> 64) as u64
+	}
+}
+
+#[derive(Clone)]
+enum Clock {
+	System(std::time::Instant),
+	/// Starts at the given Unix time in milliseconds and only moves forward when the program sleeps
+	Fake {
+		unix_millis: u64,
+		elapsed_millis: u64
+	}
+}
+
+impl Clock {
+	/// Seconds since the Unix epoch
+	pub fn now(&self) -> u64 {
+		match self {
+			Clock::System(_) => std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+			Clock::Fake {unix_millis, elapsed_millis} => (unix_millis + elapsed_millis) / 1000
+		}
+	}
+
+	/// Milliseconds since the program started
+	pub fn millis(&self) -> u64 {
+		match self {
+			Clock::System(start) => start.elapsed().as_millis() as u64,
+			Clock::Fake {elapsed_millis, ..} => *elapsed_millis
+		}
+	}
+
+	pub fn sleep(&mut self, millis: u64) {
+		match self {
+			Clock::System(_) => std::thread::sleep(std::time::Duration::from_millis(millis)),
+			Clock::Fake {elapsed_millis, ..} => *elapsed_millis += millis
+		}
+	}
+
+	fn restart(&mut self) {
+		match self {
+			Clock::System(start) => *start = std::time::Instant::now(),
+			Clock::Fake {elapsed_millis, ..} => *elapsed_millis = 0
+		}
+	}
+}
+
+/// Where `PRINT` writes to and where `INPUT` reads from
+pub trait Io {
+	fn write(&mut self, text: &str) -> Result<(), Error>;
+
+	/// Reads a line, including its newline. Gives an empty string once the input has run out,
+	/// or `None` if there isn't a line yet and the program should wait for one
+	fn read_line(&mut self) -> Result<Option<String>, Error>;
+}
+
+/// The process's stdout and stdin
+pub struct StdIo;
+
+impl Io for StdIo {
+	fn write(&mut self, text: &str) -> Result<(), Error> {
+		use std::io::Write;
+		std::io::stdout().write_all(text.as_bytes()).map_err(|e| Error::Io(e.to_string()))
+	}
+
+	fn read_line(&mut self) -> Result<Option<String>, Error> {
+		use std::io::Write;
+		// so that prompts show up before the program waits
+		std::io::stdout().flush().map_err(|e| Error::Io(e.to_string()))?;
+		let mut line = String::new();
+		std::io::stdin().read_line(&mut line).map_err(|e| Error::Io(e.to_string()))?;
+		Ok(Some(line))
+	}
+}
+
+/// Keeps the output in memory and reads from a string.
+/// Clones share the same buffers, so a host can keep one to look at the output afterwards
+#[derive(Clone, Default)]
+pub struct MemoryIo {
+	output: Rc<RefCell<String>>,
+	input: Rc<RefCell<VecDeque<String>>>,
+	/// Whether running out of input means waiting for more, rather than the end of the input
+	waits: bool
+}
+
+impl MemoryIo {
+	pub fn new(input: &str) -> Self {
+		let io = MemoryIo::default();
+		io.push_input(input);
+		io
+	}
+
+	/// Starts with no input, and makes the program wait whenever it runs out.
+	/// More can be given with [`MemoryIo::push_input`]
+	pub fn waiting() -> Self {
+		MemoryIo {waits: true, ..MemoryIo::default()}
+	}
+
+	pub fn push_input(&self, input: &str) {
+		self.input.borrow_mut().extend(input.split_inclusive('\n').map(|line| line.to_string()));
+	}
+
+	/// Everything written so far
+	pub fn output(&self) -> String {
+		self.output.borrow().clone()
+	}
+}
+
+impl Io for MemoryIo {
+	fn write(&mut self, text: &str) -> Result<(), Error> {
+		self.output.borrow_mut().push_str(text);
+		Ok(())
+	}
+
+	fn read_line(&mut self) -> Result<Option<String>, Error> {
+		match self.input.borrow_mut().pop_front() {
+			Some(line) => Ok(Some(line)),
+			None if self.waits => Ok(None),
+			None => Ok(Some(String::new()))
+		}
+	}
+}
+
+/// Answers each `INPUT` with the next of a list of lines, and keeps the output in memory.
+/// Unlike [`MemoryIo`], asking for more lines than were given is an error
+#[derive(Clone, Default)]
+pub struct ScriptedIo {
+	memory: MemoryIo,
+	remaining: Rc<RefCell<VecDeque<String>>>
+}
+
+impl ScriptedIo {
+	pub fn new(lines: Vec<String>) -> Self {
+		ScriptedIo {
+			memory: MemoryIo::default(),
+			remaining: Rc::new(RefCell::new(lines.into()))
+		}
+	}
+
+	/// Everything written so far
+	pub fn output(&self) -> String {
+		self.memory.output()
+	}
+
+	/// How many of the lines haven't been read yet
+	pub fn remaining(&self) -> usize {
+		self.remaining.borrow().len()
+	}
+}
+
+impl Io for ScriptedIo {
+	fn write(&mut self, text: &str) -> Result<(), Error> {
+		self.memory.write(text)
+	}
+
+	fn read_line(&mut self) -> Result<Option<String>, Error> {
+		match self.remaining.borrow_mut().pop_front() {
+			Some(line) => Ok(Some(line + "\n")),
+			None => Err(Error::Io("the scripted input has run out".to_string()))
+		}
+	}
+}
+
+// Converts days since the Unix epoch to a (year, month, day) in the proleptic Gregorian calendar
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+	let z = days + 719_468;
+	let era = z.div_euclid(146_097);
+	let day_of_era = z.rem_euclid(146_097);
+	let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let shifted_month = (5 * day_of_year + 2) / 153;
+	let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+	let month = if shifted_month < 10 {shifted_month + 3} else {shifted_month - 9};
+	let year = year_of_era + era * 400 + if month <= 2 {1} else {0};
+	(year, month as u32, day as u32)
+}
+
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+	let year = if month <= 2 {year - 1} else {year};
+	let era = year.div_euclid(400);
+	let year_of_era = year.rem_euclid(400);
+	let shifted_month = if month > 2 {month as i64 - 3} else {month as i64 + 9};
+	let day_of_year = (153 * shifted_month + 2) / 5 + day as i64 - 1;
+	let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+	era * 146_097 + day_of_era - 719_468
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+	match month {
+		2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+		2 => 28,
+		4 | 6 | 9 | 11 => 30,
+		_ => 31
+	}
+}
+
+/// Formats a Unix timestamp in UTC. Supports `%Y`, `%m`, `%d`, `%H`, `%M`, `%S` and `%%`
+fn format_date(timestamp: i64, format: &str) -> Result<String, Error> {
+	let (year, month, day) = civil_from_days(timestamp.div_euclid(86_400));
+	let seconds = timestamp.rem_euclid(86_400);
+	let mut output = String::new();
+	let mut chars = format.chars();
+	while let Some(c) = chars.next() {
+		if c != '%' {
+			output.push(c);
+			continue;
+		}
+		match chars.next() {
+			Some('Y') => output.push_str(&format!("{:04}", year)),
+			Some('m') => output.push_str(&format!("{:02}", month)),
+			Some('d') => output.push_str(&format!("{:02}", day)),
+			Some('H') => output.push_str(&format!("{:02}", seconds / 3600)),
+			Some('M') => output.push_str(&format!("{:02}", seconds / 60 % 60)),
+			Some('S') => output.push_str(&format!("{:02}", seconds % 60)),
+			Some('%') => output.push('%'),
+			_ => return Err(Error::InvalidArgument("unknown date format specifier"))
+		}
+	}
+	Ok(output)
+}
+
+/// Parses a UTC date written in the same format [`format_date`] uses
+fn parse_date(text: &str, format: &str) -> Result<i64, Error> {
+	let invalid = || Error::InvalidArgument("the date doesn't match its format");
+	let (mut year, mut month, mut day, mut hour, mut minute, mut second) = (1970, 1, 1, 0, 0, 0);
+	let mut text = text.trim().chars().peekable();
+	let mut format = format.chars();
+	while let Some(c) = format.next() {
+		if c != '%' {
+			if text.next() != Some(c) {
+				return Err(invalid());
+			}
+			continue;
+		}
+		let spec = format.next();
+		if spec == Some('%') {
+			if text.next() != Some('%') {
+				return Err(invalid());
+			}
+			continue;
+		}
+		let width = if spec == Some('Y') {4} else {2};
+		let mut digits = String::new();
+		while digits.len() < width && text.peek().is_some_and(|c| c.is_ascii_digit()) {
+			digits.push(text.next().unwrap());
+		}
+		let value: i64 = digits.parse().map_err(|_| invalid())?;
+		match spec {
+			Some('Y') => year = value,
+			Some('m') => month = value as u32,
+			Some('d') => day = value as u32,
+			Some('H') => hour = value,
+			Some('M') => minute = value,
+			Some('S') => second = value,
+			_ => return Err(Error::InvalidArgument("unknown date format specifier"))
+		}
+	}
+	if text.next().is_some() || !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+		return Err(invalid());
+	}
+	if hour >= 24 || minute >= 60 || second >= 60 {
+		return Err(invalid());
+	}
+	Ok(days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second)
+}
+
+enum Command<'a> {
+	Add(&'a mut Variable, Variable, Variable),
+	Sub(&'a mut Variable, Number, Number),
+	Mul(&'a mut Variable, Number, Number),
+	Div(&'a mut Variable, Number, Number),
+	Mod(&'a mut Variable, Number, Number),
+	Round(&'a mut Variable, f32),
+	Floor(&'a mut Variable, f32),
+	Ceil(&'a mut Variable, f32),
+	Sqrt(&'a mut Variable, Number),
+	Pow(&'a mut Variable, Number, Number),
+	Abs(&'a mut Variable, Number),
+	Min(&'a mut Variable, Number, Number),
+	Max(&'a mut Variable, Number, Number),
+	Sin(&'a mut Variable, Number),
+	Cos(&'a mut Variable, Number),
+	Tan(&'a mut Variable, Number),
+	Atan2(&'a mut Variable, Number, Number),
+	Log(&'a mut Variable, Number),
+	Exp(&'a mut Variable, Number),
+	Clamp(&'a mut Variable, Number, Number, Number),
+	Sign(&'a mut Variable, Number),
+	And(&'a mut Variable, bool, bool),
+	Or(&'a mut Variable, bool, bool),
+	Xor(&'a mut Variable, bool, bool),
+	Not(&'a mut Variable, bool),
+	Band(&'a mut Variable, i64, i64),
+	Bor(&'a mut Variable, i64, i64),
+	Bxor(&'a mut Variable, i64, i64),
+	Bnot(&'a mut Variable, i64),
+	Shl(&'a mut Variable, i64, u32),
+	Shr(&'a mut Variable, i64, u32),
+	Decl(String, VarType),
+	Set(&'a mut Variable, Variable),
+	Free(String),
+	Label(String),
+	Jmp(Label),
+	Jeq(Label, Variable, Variable),
+	Jgt(Label, Variable, Variable),
+	Jlt(Label, Variable, Variable),
+	Jne(Label, Variable, Variable),
+	Jge(Label, Variable, Variable),
+	Jle(Label, Variable, Variable),
+	Jif(Label, bool),
+	Jifnot(Label, bool),
+	Eq(&'a mut Variable, Variable, Variable),
+	Ne(&'a mut Variable, Variable, Variable),
+	Lt(&'a mut Variable, Variable, Variable),
+	Gt(&'a mut Variable, Variable, Variable),
+	Le(&'a mut Variable, Variable, Variable),
+	Ge(&'a mut Variable, Variable, Variable),
+	Print(String, &'a mut dyn Io),
+	Input(&'a mut Variable, &'a mut dyn Io),
+	Convert(&'a mut Variable, Variable),
+	ConvertRadix(&'a mut Variable, Variable, u32),
+	Slice(&'a mut Variable, Vec<Variable>, u32, u32),
+	Index(&'a mut Variable, Vec<Variable>, u32),
+	Len(&'a mut Variable, Vec<Variable>),
+	Insert(&'a mut Vec<Variable>, u32, Variable),
+	Sort(&'a mut Variable, Vec<Variable>, bool),
+	Call(Label),
+	Return,
+	Try,
+	Catch,
+	EndTry,
+	OnError(Option<Label>),
+	Test,
+	EndTest,
+	Assert(bool, String),
+	AssertEq(Variable, Variable),
+	GetEnv(&'a mut Variable, String),
+	Random(&'a mut Variable, &'a mut Rng, Number, Number),
+	Shuffle(&'a mut Variable, &'a mut Rng, Vec<Variable>),
+	Choose(&'a mut Variable, &'a mut Rng, Vec<Variable>),
+	Seed(&'a mut Rng, u64),
+	Now(&'a mut Variable, &'a Clock),
+	Clock(&'a mut Variable, &'a Clock),
+	Sleep(&'a mut Clock, u64),
+	FormatDate(&'a mut Variable, i64, String),
+	ParseDate(&'a mut Variable, String, String),
+	Exit(i32)
+}
+
+/// What the program should do after a command runs
+pub enum CommandResponse {
+	Declare(String, VarType),
+	Label(String),
+	Free(String),
+	Jump(Label),
+	Call(Label),
+	Return,
+	Try,
+	Catch,
+	OnError(Option<Label>),
+	Test,
+	EndTest,
+	Exit(i32),
+	Nothing
+}
+
+impl<'a> Command<'a> {
+
+	pub fn run(&mut self) -> Result<CommandResponse, Error> {
+		match self {
+			Command::Add(ref mut l, o1, o2) => Self::add(l, o1.clone(), o2.clone())?,
+			Command::Sub(ref mut l, o1, o2) => Self::sub(l, o1.clone(), o2.clone())?,
+			Command::Mul(ref mut l, o1, o2) => Self::mul(l, o1.clone(), o2.clone())?,
+			Command::Div(ref mut l, o1, o2) => Self::div(l, o1.clone(), o2.clone())?,
+			Command::Mod(ref mut l, o1, o2) => Self::modulo(l, o1.clone(), o2.clone())?,
+			Command::Round(ref mut l, o1) => Self::round(l, *o1)?,
+			Command::Floor(ref mut l, o1) => Self::floor(l, *o1)?,
+			Command::Ceil(ref mut l, o1) => Self::ceil(l, *o1)?,
+			Command::Sqrt(ref mut l, o1) => Self::store_float(l, o1.to_float().sqrt())?,
+			Command::Pow(ref mut l, o1, o2) => Self::store_float(l, o1.to_float().powf(o2.to_float()))?,
+			Command::Abs(ref mut l, o1) => match o1.to_integer() {
+				Some(o1) => Self::store_whole(l, o1.abs())?,
+				None => Self::store_float(l, o1.to_float().abs())?
+			},
+			Command::Min(ref mut l, o1, o2) => match (o1.to_integer(), o2.to_integer()) {
+				(Some(o1), Some(o2)) => Self::store_whole(l, o1.min(o2))?,
+				_ => Self::store_float(l, o1.to_float().min(o2.to_float()))?
+			},
+			Command::Max(ref mut l, o1, o2) => match (o1.to_integer(), o2.to_integer()) {
+				(Some(o1), Some(o2)) => Self::store_whole(l, o1.max(o2))?,
+				_ => Self::store_float(l, o1.to_float().max(o2.to_float()))?
+			},
+			Command::Sin(ref mut l, o1) => Self::store_float(l, o1.to_float().sin())?,
+			Command::Cos(ref mut l, o1) => Self::store_float(l, o1.to_float().cos())?,
+			Command::Tan(ref mut l, o1) => Self::store_float(l, o1.to_float().tan())?,
+			Command::Atan2(ref mut l, o1, o2) => Self::store_float(l, o1.to_float().atan2(o2.to_float()))?,
+			Command::Log(ref mut l, o1) => Self::store_float(l, o1.to_float().ln())?,
+			Command::Exp(ref mut l, o1) => Self::store_float(l, o1.to_float().exp())?,
+			Command::Clamp(ref mut l, o1, o2, o3) => Self::clamp(l, o1.clone(), o2.clone(), o3.clone())?,
+			Command::Sign(ref mut l, o1) => Self::sign(l, o1.clone())?,
+			Command::And(ref mut l, o1, o2) => Self::and(l, *o1, *o2)?,
+			Command::Or(ref mut l, o1, o2) => Self::or(l, *o1, *o2)?,
+			Command::Xor(ref mut l, o1, o2) => Self::xor(l, *o1, *o2)?,
+			Command::Not(ref mut l, o1) => Self::not(l, *o1)?,
+			Command::Band(ref mut l, o1, o2) => Self::store_integer(l, *o1 & *o2)?,
+			Command::Bor(ref mut l, o1, o2) => Self::store_integer(l, *o1 | *o2)?,
+			Command::Bxor(ref mut l, o1, o2) => Self::store_integer(l, *o1 ^ *o2)?,
+			Command::Bnot(ref mut l, o1) => Self::store_integer(l, !*o1)?,
+			Command::Shl(ref mut l, o1, o2) => Self::store_integer(l, o1.checked_shl(*o2).unwrap_or(0))?,
+			Command::Shr(ref mut l, o1, o2) => Self::store_integer(l, *o1 >> (*o2).min(63))?,
+			Command::Decl(name, var_type) => return Ok(Self::decl((**name).to_string(), *var_type)),
+			Command::Set(ref mut l, literal) => Self::set(l, literal.clone())?,
+			Command::Free(var_name) => return Ok(Self::free((**var_name).to_string())),
+			Command::Label(name) => return Ok(Self::label((**name).to_string())),
+			Command::Jmp(label) => return Ok(Self::jmp(label.clone())),
+			Command::Jeq(label, o1, o2) => return Ok(Self::jeq(label.clone(), o1.clone(), o2.clone())),
+			Command::Jgt(label, o1, o2) => return Self::jgt(label.clone(), o1.clone(), o2.clone()),
+			Command::Jlt(label, o1, o2) => return Self::jlt(label.clone(), o1.clone(), o2.clone()),
+			Command::Jne(label, o1, o2) => return Ok(Self::jne(label.clone(), o1.clone(), o2.clone())),
+			Command::Jge(label, o1, o2) => return Self::jge(label.clone(), o1.clone(), o2.clone()),
+			Command::Jle(label, o1, o2) => return Self::jle(label.clone(), o1.clone(), o2.clone()),
+			Command::Jif(label, condition) => return Ok(Self::jif(label.clone(), *condition)),
+			Command::Jifnot(label, condition) => return Ok(Self::jif(label.clone(), !*condition)),
+			Command::Eq(ref mut l, o1, o2) => Self::store_bool(l, Self::ordering(o1, o2)?.is_eq())?,
+			Command::Ne(ref mut l, o1, o2) => Self::store_bool(l, Self::ordering(o1, o2)?.is_ne())?,
+			Command::Lt(ref mut l, o1, o2) => Self::store_bool(l, Self::ordering(o1, o2)?.is_lt())?,
+			Command::Gt(ref mut l, o1, o2) => Self::store_bool(l, Self::ordering(o1, o2)?.is_gt())?,
+			Command::Le(ref mut l, o1, o2) => Self::store_bool(l, Self::ordering(o1, o2)?.is_le())?,
+			Command::Ge(ref mut l, o1, o2) => Self::store_bool(l, Self::ordering(o1, o2)?.is_ge())?,
+			Command::Print(text, io) => io.write(text)?,
+			Command::Input(ref mut location, io) => Self::input(location, *io)?,
+			Command::Convert(ref mut location, variable) => Self::convert(location, variable)?,
+			Command::ConvertRadix(ref mut location, variable, radix) => Self::convert_radix(location, variable, *radix)?,
+			Command::Slice(ref mut location, list, start, end) => Self::slice(location, list.to_vec(), *start, *end)?,
+			Command::Index(ref mut location, list, index) => Self::index(location, list.to_vec(), *index)?,
+			Command::Len(ref mut location, list) => Self::len(location, list.to_vec())?,
+			Command::Insert(ref mut list, index, item) => Self::insert(list, *index, item.clone())?,
+			Command::Sort(ref mut location, list, descending) => Self::sort(location, list.to_vec(), *descending)?,
+			Command::Call(label) => return Ok(CommandResponse::Call(label.clone())),
+			Command::Return => return Ok(CommandResponse::Return),
+			Command::Try => return Ok(CommandResponse::Try),
+			Command::Catch => return Ok(CommandResponse::Catch),
+			Command::EndTry => (),
+			Command::OnError(label) => return Ok(CommandResponse::OnError(label.clone())),
+			Command::Test => return Ok(CommandResponse::Test),
+			Command::EndTest => return Ok(CommandResponse::EndTest),
+			Command::Assert(condition, message) => Self::assert(*condition, (**message).to_string())?,
+			Command::AssertEq(o1, o2) => Self::assert_eq(o1, o2)?,
+			Command::GetEnv(ref mut location, name) => Self::getenv(location, (**name).to_string())?,
+			Command::Random(ref mut location, ref mut rng, min, max) => Self::random(location, rng, min.clone(), max.clone())?,
+			Command::Shuffle(ref mut location, ref mut rng, list) => Self::shuffle(location, rng, list.to_vec())?,
+			Command::Choose(ref mut location, ref mut rng, list) => Self::choose(location, rng, list.to_vec())?,
+			Command::Seed(ref mut rng, seed) => **rng = Rng::from_seed(*seed),
+			Command::Now(ref mut location, clock) => Self::store_time(location, clock.now())?,
+			Command::Clock(ref mut location, clock) => Self::store_time(location, clock.millis())?,
+			Command::Sleep(ref mut clock, millis) => clock.sleep(*millis),
+			Command::FormatDate(ref mut location, timestamp, format) => Self::format_date(location, *timestamp, format)?,
+			Command::ParseDate(ref mut location, text, format) => Self::parse_date(location, text, format)?,
+			Command::Exit(code) => return Ok(Self::exit(*code))
+		};
+		Ok(CommandResponse::Nothing)
+	}
+
+	fn add(location: &mut Variable, op1: Variable, op2: Variable) -> Result<(), Error> {
+		if let Variable::List(ref mut location) = location {
+			if let Variable::List(ref op1) = op1 {
+				if let Variable::List(ref op2) = op2 {
+					location.clear();
+					location.append(&mut op1.clone());
+					location.append(&mut op2.clone());
+				} else {
+					location.clear();
+					location.append(&mut op1.clone());
+					location.push(op2.clone());
+				}
+			} else {
+				location.clear();
+				location.push(op1.clone());
+				location.push(op2.clone());
+			}
+		} else if let Variable::Str(ref mut string) = location {
+			string.clear();
+			string.push_str(&op1.to_string());
+			string.push_str(&op2.to_string());
+		} else if let Variable::Natural(ref mut num) = location {
+			*num = (op1.to_float()? + op2.to_float()?).round().abs() as u32;
+		} else if let Variable::Int(ref mut num) = location {
+			*num = (op1.to_float()? + op2.to_float()?).round() as i32;
+		} else if let Variable::Float(ref mut num) = location {
+			*num = op1.to_float()? + op2.to_float()?;
+		} else {
+			return Err(Error::WrongType("a list, str or number location"));
+		}
+		Ok(())
+	}
+
+	fn sub(location: &mut Variable, op1: Number, op2: Number) -> Result<(), Error> {
+		if let Variable::Natural(ref mut n) = location {
+			*n = (op1.to_float() - op2.to_float()).round().abs() as u32;
+		} else if let Variable::Int(ref mut n) = location {
+			*n = (op1.to_float() - op2.to_float()).round() as i32;
+		} else if let Variable::Float(ref mut n) = location {
+			*n = op1.to_float() - op2.to_float();
+		} else {
+			return Err(Error::WrongType("a number location"));
+		}
+		Ok(())
+	}
+
+	fn mul(location: &mut Variable, op1: Number, op2: Number) -> Result<(), Error> {
+		if let Variable::Natural(ref mut n) = location {
+			*n = (op1.to_float() * op2.to_float()).round().abs() as u32;
+		} else if let Variable::Int(ref mut n) = location {
+			*n = (op1.to_float() * op2.to_float()).round() as i32;
+		} else if let Variable::Float(ref mut n) = location {
+			*n = op1.to_float() * op2.to_float();
+		} else {
+			return Err(Error::WrongType("a number location"));
+		}
+		Ok(())
+	}
+
+	fn div(location: &mut Variable, op1: Number, op2: Number) -> Result<(), Error> {
+		if let Variable::Natural(ref mut n) = location {
+			*n = (op1.to_float() / op2.to_float()).round().abs() as u32;
+		} else if let Variable::Int(ref mut n) = location {
+			*n = (op1.to_float() / op2.to_float()).round() as i32;
+		} else if let Variable::Float(ref mut n) = location {
+			*n = op1.to_float() / op2.to_float();
+		} else {
+			return Err(Error::WrongType("a number location"));
+		}
+		Ok(())
+	}
+
+	fn modulo(location: &mut Variable, op1: Number, op2: Number) -> Result<(), Error> {
+		if let Variable::Natural(ref mut n) = location {
+			*n = (op1.to_float() % op2.to_float()).round().abs() as u32;
+		} else if let Variable::Int(ref mut n) = location {
+			*n = (op1.to_float() % op2.to_float()).round() as i32;
+		} else if let Variable::Float(ref mut n) = location {
+			*n = op1.to_float() % op2.to_float();
+		} else {
+			return Err(Error::WrongType("a number location"));
+		}
+		Ok(())
+	}
+
+	fn round(location: &mut Variable, op1: f32) -> Result<(), Error> {
+		if let Variable::Natural(ref mut n) = location {
+			*n = op1.round().abs() as u32;
+		} else if let Variable::Int(ref mut n) = location {
+			*n = op1.round() as i32;
+		} else if let Variable::Float(ref mut n) = location {
+			*n = op1.round();
+		} else {
+			return Err(Error::WrongType("a number location"));
+		}
+		Ok(())
+	}
+
+	fn floor(location: &mut Variable, op1:f32) -> Result<(), Error> {
+		if let Variable::Natural(ref mut n) = location {
+			*n = op1.floor().abs() as u32;
+		} else if let Variable::Int(ref mut n) = location {
+			*n = op1.floor() as i32;
+		} else if let Variable::Float(ref mut n) = location {
+			*n = op1.floor();
+		} else {
+			return Err(Error::WrongType("a number location"));
+		}
+		Ok(())
+	}
+
+	fn ceil(location: &mut Variable, op1: f32) -> Result<(), Error> {
+		if let Variable::Natural(ref mut n) = location {
+			*n = op1.ceil() as u32;
+		} else if let Variable::Int(ref mut n) = location {
+			*n = op1.ceil() as i32;
+		} else if let Variable::Float(ref mut n) = location {
+			*n = op1.ceil();
+		} else {
+			return Err(Error::WrongType("a number location"));
+		}
+		Ok(())
+	}
+
+	fn store_float(location: &mut Variable, value: f32) -> Result<(), Error> {
+		match location {
+			Variable::Natural(ref mut n) => *n = value.round().abs() as u32,
+			Variable::Int(ref mut i) => *i = value.round() as i32,
+			Variable::Float(ref mut f) => *f = value,
+			_ => return Err(Error::WrongType("a number location"))
+		}
+		Ok(())
+	}
+
+	/// Stores a result worked out from whole numbers, without going through a float
+	fn store_whole(location: &mut Variable, value: i64) -> Result<(), Error> {
+		match location {
+			Variable::Natural(ref mut n) => *n = u32::try_from(value.unsigned_abs()).map_err(|_| Error::InvalidArgument("the result is too large for a natural"))?,
+			Variable::Int(ref mut i) => *i = i32::try_from(value).map_err(|_| Error::InvalidArgument("the result is too large for an int"))?,
+			Variable::Float(ref mut f) => *f = value as f32,
+			_ => return Err(Error::WrongType("a number location"))
+		}
+		Ok(())
+	}
+
+	fn clamp(location: &mut Variable, value: Number, min: Number, max: Number) -> Result<(), Error> {
+		if let (Some(value), Some(min), Some(max)) = (value.to_integer(), min.to_integer(), max.to_integer()) {
+			if min > max {
+				return Err(Error::InvalidArgument("the minimum is larger than the maximum"));
+			}
+			return Self::store_whole(location, value.clamp(min, max));
+		}
+		let (min, max) = (min.to_float(), max.to_float());
+		if min.partial_cmp(&max).is_none_or(|ordering| ordering.is_gt()) {
+			return Err(Error::InvalidArgument("the minimum is larger than the maximum"));
+		}
+		Self::store_float(location, value.to_float().clamp(min, max))
+	}
+
+	fn sign(location: &mut Variable, value: Number) -> Result<(), Error> {
+		if let Some(value) = value.to_integer() {
+			return Self::store_whole(location, value.signum());
+		}
+		let value = value.to_float();
+		let sign = if value > 0.0 {
+			1.0
+		} else if value < 0.0 {
+			-1.0
+		} else {
+			0.0
+		};
+		Self::store_float(location, sign)
+	}
+
+	fn and(location: &mut Variable, op1: bool, op2: bool) -> Result<(), Error> {
+		if let Variable::Bool(ref mut b) = location {
+			*b = op1 && op2;
+		} else {
+			return Err(Error::WrongType("a bool location"));
+		}
+		Ok(())
+	}
+
+	fn or(location: &mut Variable, op1: bool, op2: bool) -> Result<(), Error> {
+		if let Variable::Bool(ref mut b) = location {
+			*b = op1 || op2;
+		} else {
+			return Err(Error::WrongType("a bool location"));
+		}
+		Ok(())
+	}
+
+	fn xor(location: &mut Variable, op1: bool, op2: bool) -> Result<(), Error> {
+		if let Variable::Bool(ref mut b) = location {
+			*b = op1 != op2;
+		} else {
+			return Err(Error::WrongType("a bool location"));
+		}
+		Ok(())
+	}
+
+	fn not(location: &mut Variable, op1: bool) -> Result<(), Error> {
+		if let Variable::Bool(ref mut b) = location {
+			*b = !op1;
+		} else {
+			return Err(Error::WrongType("a bool location"));
+		}
+		Ok(())
+	}
+
+	/// Stores a whole number, truncating it to the width of the location
+	fn store_integer(location: &mut Variable, value: i64) -> Result<(), Error> {
+		match location {
+			Variable::Natural(ref mut n) => *n = value as u32,
+			Variable::Int(ref mut i) => *i = value as i32,
+			_ => return Err(Error::WrongType("a natural or int location"))
+		}
+		Ok(())
+	}
+
+	fn decl(var_name: String, var_type: VarType) -> CommandResponse {
+		CommandResponse::Declare(var_name, var_type)
+	}
+
+	fn set(location: &mut Variable, literal: Variable) -> Result<(), Error> {
+		if let Variable::Bool(ref mut b) = location {
+			if let Variable::Bool(nb) = literal {
+				*b = nb;
+			} else {
+				return Err(Error::WrongType("a value of the same type as the location"));
+			}
+		} else if let Variable::Char(ref mut c) = location {
+			if let Variable::Char(nc) = literal {
+				*c = nc;
+			} else {
+				return Err(Error::WrongType("a value of the same type as the location"));
+			}
+		} else if let Variable::Float(ref mut f) = location {
+			// whole number literals are naturals or ints, so they're allowed here too
+			match literal {
+				Variable::Float(nf) => *f = nf,
+				Variable::Int(ni) => *f = ni as f32,
+				Variable::Natural(nn) => *f = nn as f32,
+				_ => return Err(Error::WrongType("a value of the same type as the location"))
+			}
+		} else if let Variable::Int(ref mut i) = location {
+			if let Variable::Int(ni) = literal {
+				*i = ni;
+			} else if let Variable::Natural(nn) = literal {
+				*i = nn as i32;
+			} else {
+				return Err(Error::WrongType("a value of the same type as the location"));
+			}
+		} else if let Variable::List(ref mut l) = location {
+			if let Variable::List(nl) = literal {
+				*l = nl.clone();
+			} else {
+				return Err(Error::WrongType("a value of the same type as the location"));
+			}
+		} else if let Variable::Natural(ref mut n) = location {
+			if let Variable::Natural(nn) = literal {
+				*n = nn;
+			} else {
+				return Err(Error::WrongType("a value of the same type as the location"));
+			}
+		} else if let Variable::Str(ref mut s) = location {
+			if let Variable::Str(ns) = literal {
+				*s = ns.clone();
+			} else {
+				return Err(Error::WrongType("a value of the same type as the location"));
+			}
+		}
+		Ok(())
+	}
+
+	fn free(location: String) -> CommandResponse {
+		CommandResponse::Free(location.clone())
+	}
+
+	fn label(name: String) -> CommandResponse {
+		CommandResponse::Label(name.clone())
+	}
+
+	fn jmp(label: Label) -> CommandResponse {
+		CommandResponse::Jump(label)
+	}
+
+	fn jeq(label: Label, o1: Variable, o2: Variable) -> CommandResponse {
+		if o1 == o2 {
+			CommandResponse::Jump(label)
+		} else {
+			CommandResponse::Nothing
+		}
+	}
+
+	/// Orders two values which must be comparable
+	fn ordering(o1: &Variable, o2: &Variable) -> Result<std::cmp::Ordering, Error> {
+		o1.compare(o2).ok_or(Error::Incomparable)
+	}
+
+	fn store_bool(location: &mut Variable, value: bool) -> Result<(), Error> {
+		if let Variable::Bool(ref mut b) = location {
+			*b = value;
+		} else {
+			return Err(Error::WrongType("a bool location"));
+		}
+		Ok(())
+	}
+
+	fn jgt(label: Label, o1: Variable, o2: Variable) -> Result<CommandResponse, Error> {
+		if Self::ordering(&o1, &o2)?.is_gt() {
+			Ok(CommandResponse::Jump(label))
+		} else {
+			Ok(CommandResponse::Nothing)
+		}
+	}
+
+	fn jlt(label: Label, o1: Variable, o2: Variable) -> Result<CommandResponse, Error> {
+		if Self::ordering(&o1, &o2)?.is_lt() {
+			Ok(CommandResponse::Jump(label))
+		} else {
+			Ok(CommandResponse::Nothing)
+		}
+	}
+
+	fn jne(label: Label, o1: Variable, o2: Variable) -> CommandResponse {
+		if o1 != o2 {
+			CommandResponse::Jump(label)
+		} else {
+			CommandResponse::Nothing
+		}
+	}
+
+	fn jge(label: Label, o1: Variable, o2: Variable) -> Result<CommandResponse, Error> {
+		if Self::ordering(&o1, &o2)?.is_ge() {
+			Ok(CommandResponse::Jump(label))
+		} else {
+			Ok(CommandResponse::Nothing)
+		}
+	}
+
+	fn jle(label: Label, o1: Variable, o2: Variable) -> Result<CommandResponse, Error> {
+		if Self::ordering(&o1, &o2)?.is_le() {
+			Ok(CommandResponse::Jump(label))
+		} else {
+			Ok(CommandResponse::Nothing)
+		}
+	}
+
+	fn jif(label: Label, condition: bool) -> CommandResponse {
+		if condition {
+			CommandResponse::Jump(label)
+		} else {
+			CommandResponse::Nothing
+		}
+	}
+
+	fn assert(condition: bool, message: String) -> Result<(), Error> {
+		if condition {
+			Ok(())
+		} else {
+			Err(Error::AssertionFailed(message))
+		}
+	}
+
+	fn assert_eq(o1: &Variable, o2: &Variable) -> Result<(), Error> {
+		if o1 == o2 {
+			Ok(())
+		} else {
+			Err(Error::AssertionFailed(format!("{:?} is not equal to {:?}", o1, o2)))
+		}
+	}
+
+	fn input(location: &mut Variable, io: &mut dyn Io) -> Result<(), Error> {
+		if let Variable::Str(ref mut s) = location {
+			*s = io.read_line()?.ok_or(Error::WaitingForInput)?;
+		} else {return Err(Error::WrongType("a str location"));}
+		Ok(())
+	}
+
+	// TODO convert to match
+	fn convert(location: &mut Variable, variable: &Variable) -> Result<(), Error> {
+		if let Variable::Bool(ref mut b) = location {
+			if let Variable::Bool(b2) = variable {
+				*b = *b2;
+			} else if let Variable::Char(c) = variable {
+				*b = !(*c == 'f' || *c == 'F');
+			} else if let Variable::Float(f) = variable {
+				*b = *f != 0.0;
+			} else if let Variable::Int(i) = variable {
+				*b = *i != 0;
+			} else if let Variable::List(l) = variable {
+				*b = !l.is_empty();
+			} else if let Variable::Natural(n) = variable {
+				*b = *n != 0;
+			} else if let Variable::Str(s) = variable {
+				*b = !s.is_empty();
+			}
+		} else if let Variable::Char(ref mut c) = location {
+			if let Variable::Bool(b) = variable {
+				if *b {
+					*c = 't';
+				} else {
+					*c = 'f';
+				}
+			} else if let Variable::Char(oc) = variable {
+				*c = *oc;
+			} else {
+				return Err(Error::WrongType("a value that converts to the location's type"));
+			}
+		} else if let Variable::Float(ref mut f) = location {
+			if let Variable::Bool(b) = variable {
+				if *b {
+					*f = 1.0;
+				} else {
+					*f = 0.0;
+				}
+			} else if let Variable::Float(of2) = variable {
+				*f = *of2;
+			} else if let Variable::Int(i) = variable {
+				*f = *i as f32;
+			} else if let Variable::Natural(n) = variable {
+				*f = *n as f32;
+			} else if let Variable::Str(s) = variable {
+				*f = s.trim().parse().map_err(|_| Error::InvalidLiteral(s.clone()))?;
+			} else {
+				return Err(Error::WrongType("a value that converts to the location's type"));
+			}
+		} else if let Variable::Int(ref mut i) = location {
+			match variable {
+				Variable::Bool(b) => *i = if *b {1} else {0},
+				Variable::Float(f) => *i = f.round() as i32,
+				Variable::Int(i2) => *i = *i2,
+				Variable::Natural(n) => *i = *n as i32,
+				Variable::Str(s) => *i = s.trim().parse().map_err(|_| Error::InvalidLiteral(s.clone()))?,
+				_ => return Err(Error::WrongType("a value that converts to the location's type"))
+			}
+		} else if let Variable::List(ref mut l) = location {
+			match variable {
+				Variable::List(l2) => *l = l2.clone(),
+				Variable::Str(s) => {
+					l.clear();
+					for character in s.chars() {
+						l.push(Variable::Char(character))
+					}
+				},
+				_ => *l = vec![variable.clone()]
+			}
+		} else if let Variable::Natural(ref mut n) = location {
+			match variable {
+				Variable::Float(f) => *n = f.round().abs() as u32,
+				Variable::Int(i) => *n = i.unsigned_abs(),
+				Variable::Natural(n2) => *n = *n2,
+				Variable::Bool(b) => *n = if *b {1} else {0},
+				Variable::Str(s) => *n = s.trim().parse().map_err(|_| Error::InvalidLiteral(s.clone()))?,
+				_ => return Err(Error::WrongType("a value that converts to the location's type"))
+			}
+		} else if let Variable::Str(ref mut s) = location {
+			*s = format!("{}", variable);
+		}
+		Ok(())
+	}
+
+	fn convert_radix(location: &mut Variable, variable: &Variable, radix: u32) -> Result<(), Error> {
+		match (location, variable) {
+			(Variable::Str(ref mut s), Variable::Natural(n)) => *s = format_radix(*n as i64, radix),
+			(Variable::Str(ref mut s), Variable::Int(i)) => *s = format_radix(*i as i64, radix),
+			(Variable::Natural(ref mut n), Variable::Str(s)) => *n = u32::from_str_radix(&s.trim().replace('_', ""), radix).map_err(|_| Error::InvalidLiteral(s.clone()))?,
+			(Variable::Int(ref mut i), Variable::Str(s)) => *i = i32::from_str_radix(&s.trim().replace('_', ""), radix).map_err(|_| Error::InvalidLiteral(s.clone()))?,
+			_ => return Err(Error::WrongType("a str and a natural or int"))
+		}
+		Ok(())
+	}
+
+	fn slice(location: &mut Variable, list: Vec<Variable>, start: u32, end: u32) -> Result<(), Error> {
+		if start > end {
+			return Err(Error::InvalidArgument("the start of the slice is after its end"));
+		}
+		if end as usize > list.len() {
+			return Err(Error::IndexOutOfRange(end as usize));
+		}
+		if let Variable::List(ref mut l) = location {
+			*l = list[start as usize..end as usize].to_vec();
+		} else {return Err(Error::WrongType("a list location"));}
+		Ok(())
+	}
+
+	fn index(location: &mut Variable, list: Vec<Variable>, index: u32) -> Result<(), Error> {
+		let value = list.get(index as usize).ok_or(Error::IndexOutOfRange(index as usize))?.clone();
+		Self::set(location, value)
+	}
+
+	fn len(location: &mut Variable, list: Vec<Variable>) -> Result<(), Error> {
+		match location {
+			Variable::Float(ref mut f) => *f = list.len() as f32,
+			Variable::Int(ref mut i) => *i = list.len() as i32,
+			Variable::Natural(ref mut n) => *n = list.len() as u32,
+			_ => return Err(Error::WrongType("a number location"))
+		}
+		Ok(())
+	}
+
+	fn insert(location: &mut Vec<Variable>, index: u32, item: Variable) -> Result<(), Error> {
+		if index as usize > location.len() {
+			return Err(Error::IndexOutOfRange(index as usize));
+		}
+		location.insert(index as usize, item);
+		Ok(())
+	}
+
+	fn sort(location: &mut Variable, mut list: Vec<Variable>, descending: bool) -> Result<(), Error> {
+		list.sort_by(|a, b| a.sort_cmp(b));
+		if descending {
+			list.reverse();
+		}
+		if let Variable::List(ref mut l) = location {
+			*l = list;
+		} else {return Err(Error::WrongType("a list location"));}
+		Ok(())
+	}
+
+	fn getenv(location: &mut Variable, name: String) -> Result<(), Error> {
+		if let Variable::Str(ref mut s) = location {
+			*s = std::env::var(name).unwrap_or_default();
+		} else {
+			return Err(Error::WrongType("a str location"));
+		}
+		Ok(())
+	}
+
+	fn exit(code: i32) -> CommandResponse {
+		CommandResponse::Exit(code)
+	}
+
+	fn store_time(location: &mut Variable, time: u64) -> Result<(), Error> {
+		match location {
+			Variable::Natural(ref mut n) => *n = u32::try_from(time).map_err(|_| Error::InvalidArgument("the time is too large for a natural"))?,
+			Variable::Int(ref mut i) => *i = i32::try_from(time).map_err(|_| Error::InvalidArgument("the time is too large for an int"))?,
+			Variable::Float(ref mut f) => *f = time as f32,
+			_ => return Err(Error::WrongType("a number location"))
+		}
+		Ok(())
+	}
+
+	fn format_date(location: &mut Variable, timestamp: i64, format: &str) -> Result<(), Error> {
+		if let Variable::Str(ref mut s) = location {
+			*s = format_date(timestamp, format)?;
+		} else {return Err(Error::WrongType("a str location"));}
+		Ok(())
+	}
+
+	fn parse_date(location: &mut Variable, text: &str, format: &str) -> Result<(), Error> {
+		let timestamp = parse_date(text, format)?;
+		match location {
+			Variable::Natural(ref mut n) => *n = u32::try_from(timestamp).map_err(|_| Error::InvalidArgument("the date doesn't fit in a natural"))?,
+			Variable::Int(ref mut i) => *i = i32::try_from(timestamp).map_err(|_| Error::InvalidArgument("the date doesn't fit in an int"))?,
+			_ => return Err(Error::WrongType("a natural or int location"))
+		}
+		Ok(())
+	}
+
+	fn random(location: &mut Variable, rng: &mut Rng, min: Number, max: Number) -> Result<(), Error> {
+		let empty = Error::InvalidArgument("there are no values between the minimum and maximum");
+		if min.to_float().partial_cmp(&max.to_float()).is_none_or(|ordering| ordering.is_gt()) {
+			return Err(empty);
+		}
+		// whole number bounds are used as they are, so large ones stay exact
+		let mut between = |low: i64, high: i64| {
+			let min = min.to_integer().unwrap_or_else(|| min.to_float().ceil() as i64).max(low);
+			let max = max.to_integer().unwrap_or_else(|| max.to_float().floor() as i64).min(high);
+			if min > max {
+				return Err(Error::InvalidArgument("there are no values between the minimum and maximum"));
+			}
+			Ok(min + rng.below((max - min + 1) as u64) as i64)
+		};
+		match location {
+			Variable::Natural(ref mut n) => *n = u32::try_from(between(0, u32::MAX as i64)?).map_err(|_| empty)?,
+			Variable::Int(ref mut i) => *i = i32::try_from(between(i32::MIN as i64, i32::MAX as i64)?).map_err(|_| empty)?,
+			Variable::Float(ref mut f) => {
+				let (min, max) = (min.to_float() as f64, max.to_float() as f64);
+				*f = (min + rng.next_float() * (max - min)) as f32;
+			},
+			_ => return Err(Error::WrongType("a number location"))
+		}
+		Ok(())
+	}
+
+	fn shuffle(location: &mut Variable, rng: &mut Rng, mut list: Vec<Variable>) -> Result<(), Error> {
+		for i in (1..list.len()).rev() {
+			let j = rng.below(i as u64 + 1) as usize;
+			list.swap(i, j);
+		}
+		if let Variable::List(ref mut l) = location {
+			*l = list;
+		} else {return Err(Error::WrongType("a list location"));}
+		Ok(())
+	}
+
+	fn choose(location: &mut Variable, rng: &mut Rng, list: Vec<Variable>) -> Result<(), Error> {
+		if list.is_empty() {
+			return Err(Error::InvalidArgument("can't choose from an empty list"));
+		}
+		let value = list[rng.below(list.len() as u64) as usize].clone();
+		Self::set(location, value)
+	}
+}
+
+/// A stable merge sort which, unlike `sort_by`, is fine with comparators that aren't a total order
+fn merge_sort(list: Vec<Variable>, less: &mut dyn FnMut(&Variable, &Variable) -> Result<bool, Error>) -> Result<Vec<Variable>, Error> {
+	if list.len() <= 1 {
+		return Ok(list);
+	}
+	let mut left = list;
+	let right = left.split_off(left.len() / 2);
+	let left = merge_sort(left, less)?;
+	let right = merge_sort(right, less)?;
+	let mut merged = Vec::with_capacity(left.len() + right.len());
+	let mut left = left.into_iter().peekable();
+	let mut right = right.into_iter().peekable();
+	while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+		if less(r, l)? {
+			merged.push(right.next().unwrap());
+		} else {
+			merged.push(left.next().unwrap());
+		}
+	}
+	merged.extend(left);
+	merged.extend(right);
+	Ok(merged)
+}
+
+/// Bounds on what a program can use, for running scripts that can't be trusted.
+/// Each one is unlimited if it's `None`
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+	/// How many lines can be run
+	pub instructions: Option<u64>,
+	/// How long the program can run for, in real time even if the clock is fake
+	pub time: Option<std::time::Duration>,
+	/// Roughly how many bytes all of the variables together can take up
+	pub memory: Option<usize>,
+	/// How many items a list, or characters a str, can have
+	pub length: Option<usize>,
+	/// How many routines can be called inside of each other
+	pub call_depth: Option<usize>
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Limit {
+	Instructions,
+	Time,
+	Memory,
+	Length,
+	CallDepth
+}
+
+impl Display for Limit {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Limit::Instructions => write!(f, "instruction"),
+			Limit::Time => write!(f, "time"),
+			Limit::Memory => write!(f, "memory"),
+			Limit::Length => write!(f, "length"),
+			Limit::CallDepth => write!(f, "call depth")
+		}
+	}
+}
+
+/// Where a program is after running some of it
+#[derive(Clone, Debug)]
+pub enum Status {
+	Running,
+	/// An `INPUT` is waiting for a line from the program's [`Io`]. It runs again on the next step
+	WaitingForInput,
+	Finished(i32),
+	Errored(RuntimeError)
+}
+
+impl From<Result<i32, RuntimeError>> for Status {
+	fn from(outcome: Result<i32, RuntimeError>) -> Self {
+		match outcome {
+			Ok(code) => Status::Finished(code),
+			Err(error) => Status::Errored(error)
+		}
+	}
+}
+
+/// Something outside of the program which it might use
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Capability {
+	Stdin,
+	Stdout,
+	File(PathBuf),
+	Env,
+	/// The real clock. A fake clock can always be used
+	Clock,
+	Process,
+	Network
+}
+
+impl Display for Capability {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Capability::Stdin => write!(f, "stdin"),
+			Capability::Stdout => write!(f, "stdout"),
+			Capability::File(path) => write!(f, "the file {}", path.display()),
+			Capability::Env => write!(f, "the environment"),
+			Capability::Clock => write!(f, "the clock"),
+			Capability::Process => write!(f, "other processes"),
+			Capability::Network => write!(f, "the network")
+		}
+	}
+}
+
+/// What a program is allowed to use. Commands registered by the host should call
+/// [`Permissions::check`] before touching anything outside of the program
+#[derive(Clone, Debug, Default)]
+pub struct Permissions {
+	pub stdin: bool,
+	pub stdout: bool,
+	/// Files in these directories, or these files themselves, can be used
+	pub files: Vec<PathBuf>,
+	/// Every file can be used
+	pub all_files: bool,
+	pub env: bool,
+	pub clock: bool,
+	pub process: bool,
+	pub network: bool
+}
+
+impl Permissions {
+	/// Allows everything
+	pub fn all() -> Self {
+		Permissions {
+			stdin: true,
+			stdout: true,
+			files: Vec::new(),
+			all_files: true,
+			env: true,
+			clock: true,
+			process: true,
+			network: true
+		}
+	}
+
+	/// Allows nothing
+	pub fn none() -> Self {
+		Permissions::default()
+	}
+
+	pub fn allows(&self, capability: &Capability) -> bool {
+		match capability {
+			Capability::Stdin => self.stdin,
+			Capability::Stdout => self.stdout,
+			Capability::File(path) => self.all_files || self.files.iter().any(|allowed| within(path, allowed)),
+			Capability::Env => self.env,
+			Capability::Clock => self.clock,
+			Capability::Process => self.process,
+			Capability::Network => self.network
+		}
+	}
+
+	pub fn check(&self, capability: Capability) -> Result<(), Error> {
+		if self.allows(&capability) {
+			Ok(())
+		} else {
+			Err(Error::PermissionDenied(capability))
+		}
+	}
+}
+
+/// Whether `path` is `allowed` or inside of it, once `.`, `..` and links are resolved. A file that doesn't
+/// exist yet is resolved through the directory it would be in, and paths that can't be resolved never are
+fn within(path: &Path, allowed: &Path) -> bool {
+	let resolve = |path: &Path| std::fs::canonicalize(path).ok().or_else(|| {
+		let name = path.file_name()?;
+		let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+		std::fs::canonicalize(parent).ok().map(|parent| parent.join(name))
+	});
+	match (resolve(path), resolve(allowed)) {
+		(Some(path), Some(allowed)) => path.starts_with(allowed),
+		_ => false
+	}
+}
+
+/// A running `TRY` block
+#[derive(Clone)]
+struct TryHandler {
+	try_line: usize,
+	catch_line: usize,
+	call_depth: usize
+}
+
+/// The program's variables. Each name gets a numbered slot the first time it's seen,
+/// which compiled code uses to find the variable without looking up its name
+#[derive(Default)]
+struct Vars {
+	slots: HashMap<String, usize>,
+	names: Vec<String>,
+	values: Vec<Option<Variable>>
+}
+
+impl Vars {
+	/// Variables with a slot for each name, in order
+	fn with_slots(names: &[String]) -> Self {
+		let mut vars = Vars::default();
+		for name in names {
+			vars.slot(name);
+		}
+		vars
+	}
+
+	/// Finds the slot for a name, giving it a new one if it doesn't have one yet
+	fn slot(&mut self, name: &str) -> usize {
+		if let Some(slot) = self.slots.get(name) {
+			return *slot;
+		}
+		self.slots.insert(name.to_string(), self.names.len());
+		self.names.push(name.to_string());
+		self.values.push(None);
+		self.names.len() - 1
+	}
+
+	fn get(&self, name: &str) -> Option<&Variable> {
+		self.slots.get(name).and_then(|slot| self.get_slot(*slot))
+	}
+
+	fn get_slot(&self, slot: usize) -> Option<&Variable> {
+		self.values[slot].as_ref()
+	}
+
+	fn contains_key(&self, name: &str) -> bool {
+		self.get(name).is_some()
+	}
+
+	/// Puts a value in a slot, or empties it, giving back what was there
+	fn replace(&mut self, slot: usize, value: Option<Variable>) -> Option<Variable> {
+		std::mem::replace(&mut self.values[slot], value)
+	}
+
+	fn insert(&mut self, name: &str, value: Variable) -> Option<Variable> {
+		let slot = self.slot(name);
+		self.replace(slot, Some(value))
+	}
+
+	/// Removes every variable, but keeps their slots
+	fn clear(&mut self) {
+		self.values.iter_mut().for_each(|value| *value = None);
+	}
+
+	fn iter(&self) -> impl Iterator<Item = (&String, &Variable)> {
+		self.names.iter().zip(&self.values).filter_map(|(name, value)| value.as_ref().map(|value| (name, value)))
+	}
+}
+
+pub struct Program {
+	program: String,
+	lines: Vec<String>,
+	commands: Registry,
+	call_stack: Vec<usize>,
+	vars: Vars,
+	labels: HashMap<String, Label>,
+	current_line: usize,
+	/// The `CATCH` line of each `TRY` line
+	try_blocks: HashMap<usize, usize>,
+	/// The `ENDTRY` line of each `CATCH` line
+	catch_blocks: HashMap<usize, usize>,
+	/// The `ENDTEST` line of each `TEST` line
+	test_blocks: HashMap<usize, usize>,
+	/// The name given on each `CATCH` and `TEST` line
+	block_names: HashMap<usize, String>,
+	/// Whether the program is compiled to bytecode before it runs, rather than interpreted line by line
+	vm: bool,
+	/// Whether the bytecode is optimized after it's compiled
+	optimize: bool,
+	/// The bytecode the program was made from, if it wasn't made from source
+	bytecode: Option<Bytecode>,
+	compiled: Option<Rc<Compiled>>,
+	/// Whether a single test is being run, rather than the whole program
+	testing: bool,
+	try_handlers: Vec<TryHandler>,
+	/// The label from `ON ERROR GOTO`, and the call depth it was set at
+	on_error: Option<(Label, usize)>,
+	/// Where the error being handled happened, which may be inside a routine that's been left
+	fault_line: Option<usize>,
+	args: Vec<String>,
+	exit_code: Option<i32>,
+	seed: Option<u64>,
+	rng: Rng,
+	clock: Clock,
+	io: Box<dyn Io>,
+	limits: Limits,
+	permissions: Permissions,
+	/// Whether the program has been started, so that it can be stepped through
+	started: bool,
+	/// How the program ended, once it has
+	outcome: Option<Result<i32, RuntimeError>>,
+	/// How many lines have run since the program started
+	instructions: u64,
+	/// How long the program has spent running, not counting time between steps
+	time_used: std::time::Duration,
+	/// When the line that's running started
+	step_start: std::time::Instant,
+	/// Roughly how many bytes the variables take up, only counted when there's a memory limit
+	memory: usize
+}
+
+impl Program {
+
+	pub fn new(program: String) -> Self {
+		Program {
+			program,
+			lines: Vec::new(),
+			commands: Registry::builtins(),
+			call_stack: Vec::new(),
+			vars: Vars::default(),
+			labels: HashMap::new(),
+			current_line: 0,
+			try_blocks: HashMap::new(),
+			catch_blocks: HashMap::new(),
+			test_blocks: HashMap::new(),
+			block_names: HashMap::new(),
+			vm: false,
+			optimize: false,
+			bytecode: None,
+			compiled: None,
+			testing: false,
+			try_handlers: Vec::new(),
+			on_error: None,
+			fault_line: None,
+			args: Vec::new(),
+			exit_code: None,
+			seed: None,
+			rng: Rng::from_seed(0),
+			clock: Clock::System(std::time::Instant::now()),
+			io: Box::new(StdIo),
+			limits: Limits::default(),
+			permissions: Permissions::all(),
+			started: false,
+			outcome: None,
+			instructions: 0,
+			time_used: std::time::Duration::ZERO,
+			step_start: std::time::Instant::now(),
+			memory: 0
+		}
+	}
+
+	pub fn with_args(mut self, args: Vec<String>) -> Self {
+		self.args = args;
+		self
+	}
+
+	pub fn with_seed(mut self, seed: u64) -> Self {
+		self.seed = Some(seed);
+		self
+	}
+
+	/// Uses a clock that starts at `unix_millis` and only advances when the program sleeps
+	pub fn with_fake_clock(mut self, unix_millis: u64) -> Self {
+		self.clock = Clock::Fake {unix_millis, elapsed_millis: 0};
+		self
+	}
+
+	pub fn with_limits(mut self, limits: Limits) -> Self {
+		self.limits = limits;
+		self
+	}
+
+	/// Decides what the program can use. Everything is allowed unless this is called
+	pub fn with_permissions(mut self, permissions: Permissions) -> Self {
+		self.permissions = permissions;
+		self
+	}
+
+	pub fn permissions(&self) -> &Permissions {
+		&self.permissions
+	}
+
+	/// Checks that the program can use the clock, if it's the real one
+	fn check_clock(&self) -> Result<(), Error> {
+		match self.clock {
+			Clock::System(_) => self.permissions.check(Capability::Clock),
+			Clock::Fake {..} => Ok(())
+		}
+	}
+
+	/// Adds a command for the program to use, or replaces a built-in one
+	pub fn with_command<F>(mut self, name: &str, signature: impl Into<Signature>, handler: F) -> Self
+	where F: Fn(&mut Program, &mut [Variable]) -> Result<CommandResponse, Error> + 'static {
+		self.commands.register(name, signature, handler);
+		self
+	}
+
+	pub fn commands(&self) -> &Registry {
+		&self.commands
+	}
+
+	/// Sends `PRINT` and `INPUT` somewhere other than stdout and stdin
+	pub fn with_io(mut self, io: impl Io + 'static) -> Self {
+		self.io = Box::new(io);
+		self
+	}
+
+	/// Compiles the program to bytecode when it starts, and runs that instead of interpreting each line
+	pub fn with_vm(mut self) -> Self {
+		self.vm = true;
+		self
+	}
+
+	/// Optimizes the program's bytecode once it's compiled, which means running it on the VM
+	pub fn with_optimizer(mut self) -> Self {
+		self.vm = true;
+		self.optimize = true;
+		self
+	}
+
+	/// A program that runs bytecode from [`Program::compile`] on the VM, without its source
+	pub fn from_bytecode(bytecode: Bytecode) -> Self {
+		let mut program = Program::new(String::new());
+		program.bytecode = Some(bytecode);
+		program
+	}
+
+	fn parse_literal(&self, literal: String) -> Result<Variable, Error> {
+		let literal = literal.trim().to_string();
+		if let Some(var) = self.vars.get(&literal) {
+			Ok(var.clone())
+		} else if let Some(inner) = literal.strip_prefix('[') {
+			let inner = inner.strip_suffix(']').ok_or_else(|| Error::InvalidLiteral(literal.clone()))?;
+			if inner.trim().is_empty() {
+				return Ok(Variable::List(Vec::new()));
+			}
+			let items : Vec<String> = inner.split(',').map(|s| s.to_string()).collect();
+			Ok(Variable::List(items.iter().map(|i| self.parse_literal(i.to_string())).collect::<Result<_, _>>()?))
+		} else {
+			parse_scalar(&literal)
+		}
+	}
+
+	fn get_var(&self, name: String) -> Result<Variable, Error> {
+		self.vars.get(&name).cloned().ok_or(Error::UnknownVariable(name))
+	}
+
+	fn get_label(&self, name: String) -> Result<Label, Error> {
+		self.labels.get(&name).cloned().ok_or(Error::UnknownLabel(name))
+	}
+
+	/// Runs the routine at `label` until it returns, with the given variables set for it to use.
+	/// Returns the value the routine left in `RESULT`
+	fn call_routine(&mut self, label: Label, bindings: Vec<(&str, Variable)>) -> Result<Variable, Error> {
+		let mut saved = Vec::new();
+		for (name, value) in bindings {
+			saved.push((name, self.insert_var(name.to_string(), value)?));
+		}
+
+		let depth = self.call_stack.len();
+		let caller = self.current_line;
+		let mut outcome = Ok(());
+		self.enter_routine(caller)?;
+		self.current_line = label.0 + 1;
+		while self.call_stack.len() > depth && self.exit_code.is_none() {
+			if self.current_line >= self.line_count() {
+				outcome = Err(Error::InvalidBlock("the routine ran past the end of the program"));
+				break;
+			}
+			if let Err(error) = self.run_current_line() {
+				// the command that called the routine can't be stopped halfway through
+				let error = match error {
+					Error::WaitingForInput => Error::Io("routines called by commands can't wait for input".to_string()),
+					error => error
+				};
+				if let Err(error) = self.handle_error(error, depth + 1) {
+					outcome = Err(error);
+					break;
+				}
+			}
+			self.current_line += 1;
+		}
+		self.unwind(depth);
+		self.current_line = caller;
+
+		let result = self.get_var("RESULT".to_string());
+		for (name, value) in saved {
+			match value {
+				Some(value) => {self.insert_var(name.to_string(), value)?;},
+				None => {self.remove_var(name);}
+			}
+		}
+		outcome?;
+		result
+	}
+
+	fn map_list(&mut self, list: Vec<Variable>, label: Label) -> Result<Vec<Variable>, Error> {
+		let mut mapped = Vec::with_capacity(list.len());
+		for item in list {
+			mapped.push(self.call_routine(label.clone(), vec![("ITEM", item.clone()), ("RESULT", item)])?);
+		}
+		Ok(mapped)
+	}
+
+	fn filter_list(&mut self, list: Vec<Variable>, label: Label) -> Result<Vec<Variable>, Error> {
+		let mut filtered = Vec::new();
+		for item in list {
+			if self.call_routine(label.clone(), vec![("ITEM", item.clone()), ("RESULT", Variable::Bool(false))])? == Variable::Bool(true) {
+				filtered.push(item);
+			}
+		}
+		Ok(filtered)
+	}
+
+	fn reduce_list(&mut self, initial: Variable, list: Vec<Variable>, label: Label) -> Result<Variable, Error> {
+		let mut acc = initial;
+		for item in list {
+			acc = self.call_routine(label.clone(), vec![("ACC", acc.clone()), ("ITEM", item), ("RESULT", acc)])?;
+		}
+		Ok(acc)
+	}
+
+	fn sort_list_by(&mut self, list: Vec<Variable>, label: Label) -> Result<Vec<Variable>, Error> {
+		merge_sort(list, &mut |a, b| {
+			Ok(self.call_routine(label.clone(), vec![("A", a.clone()), ("B", b.clone()), ("RESULT", Variable::Bool(false))])? == Variable::Bool(true))
+		})
+	}
+
+	/// Drops the call frames, and the `TRY` blocks inside them, above `call_depth`
+	fn unwind(&mut self, call_depth: usize) {
+		self.call_stack.truncate(call_depth);
+		self.try_handlers.retain(|handler| handler.call_depth <= call_depth);
+	}
+
+	/// Drops the `TRY` blocks in the current routine that a jump has left, so their `CATCH` doesn't
+	/// get errors from outside of them
+	fn leave_try_blocks(&mut self) {
+		let call_depth = self.call_stack.len();
+		while let Some(handler) = self.try_handlers.last() {
+			if handler.call_depth < call_depth || (handler.try_line..handler.catch_line).contains(&self.current_line) {
+				break;
+			}
+			self.try_handlers.pop();
+		}
+	}
+
+	/// Sends an error to the innermost `TRY` block or `ON ERROR GOTO` that was set up at least
+	/// `call_depth` frames deep, unwinding the call frames above it. If there isn't one, the error is
+	/// given back so that it can go to the caller
+	fn handle_error(&mut self, error: Error, call_depth: usize) -> Result<(), Error> {
+		let line = *self.fault_line.get_or_insert(self.current_line);
+		if let Error::LimitExceeded(_) | Error::WaitingForInput = error {
+			return Err(error);
+		}
+		let handler = self.try_handlers.last().filter(|handler| handler.call_depth >= call_depth).cloned();
+		let on_error = self.on_error.clone().filter(|(_, depth)| *depth >= call_depth);
+
+		match (handler, on_error) {
+			(Some(handler), on_error) if on_error.as_ref().is_none_or(|(_, depth)| handler.call_depth >= *depth) => {
+				self.try_handlers.pop();
+				self.unwind(handler.call_depth);
+				let variable = self.block_names[&handler.catch_line].clone();
+				self.insert_var(variable, Variable::Str(error.to_string()))?;
+				self.current_line = handler.catch_line;
+			},
+			(_, Some((label, depth))) => {
+				// the handler is turned off while it runs, so an error inside it can't loop forever
+				self.on_error = None;
+				self.unwind(depth);
+				self.current_line = label.0;
+			},
+			_ => return Err(error)
+		}
+		self.insert_var("ERR".to_string(), Variable::Str(error.to_string()))?;
+		self.insert_var("ERRLINE".to_string(), Variable::Natural(line as u32 + 1))?;
+		self.fault_line = None;
+		Ok(())
+	}
+
+	/// Stores a variable, keeping track of its size if the program has limits on it
+	fn insert_var(&mut self, name: String, value: Variable) -> Result<Option<Variable>, Error> {
+		let slot = self.vars.slot(&name);
+		self.insert_slot(slot, value)
+	}
+
+	fn insert_slot(&mut self, slot: usize, value: Variable) -> Result<Option<Variable>, Error> {
+		if let Some(length) = self.limits.length {
+			if value.longest() > length {
+				return Err(Error::LimitExceeded(Limit::Length));
+			}
+		}
+		let tracked = self.limits.memory.is_some();
+		let name_size = self.vars.names[slot].len();
+		if tracked {
+			self.memory += name_size + value.size();
+		}
+		let old = self.vars.replace(slot, Some(value));
+		if let (true, Some(old)) = (tracked, &old) {
+			self.memory -= name_size + old.size();
+		}
+		if self.limits.memory.is_some_and(|memory| self.memory > memory) {
+			return Err(Error::LimitExceeded(Limit::Memory));
+		}
+		Ok(old)
+	}
+
+	fn remove_var(&mut self, name: &str) -> Option<Variable> {
+		let slot = *self.vars.slots.get(name)?;
+		let old = self.vars.replace(slot, None);
+		if let (true, Some(old)) = (self.limits.memory.is_some(), &old) {
+			self.memory -= name.len() + old.size();
+		}
+		old
+	}
+
+	fn enter_routine(&mut self, return_line: usize) -> Result<(), Error> {
+		if self.limits.call_depth.is_some_and(|depth| self.call_stack.len() >= depth) {
+			return Err(Error::LimitExceeded(Limit::CallDepth));
+		}
+		self.call_stack.push(return_line);
+		Ok(())
+	}
+
+	/// How much longer the program can run for, if it has a time limit
+	fn time_left(&self) -> Option<std::time::Duration> {
+		self.limits.time.map(|time| time.saturating_sub(self.time_used + self.step_start.elapsed()))
+	}
+
+	/// Counts a line as run, and checks that the program is still within its instruction and time limits
+	fn check_limits(&mut self) -> Result<(), Error> {
+		self.instructions += 1;
+		if self.limits.instructions.is_some_and(|instructions| self.instructions > instructions) {
+			return Err(Error::LimitExceeded(Limit::Instructions));
+		}
+		if self.time_left().is_some_and(|left| left.is_zero()) {
+			return Err(Error::LimitExceeded(Limit::Time));
+		}
+		Ok(())
+	}
+
+	/// Finds the values of a command's parameters, checking that they're the right kinds
+	fn resolve(&self, signature: &Signature, parameters: &[String]) -> Result<Vec<Variable>, Error> {
+		signature.params.iter().zip(parameters).map(|(param, word)| param.check(match param {
+			Param::Location(_) | Param::Operand(_) => self.get_var(word.clone())?,
+			Param::Literal(_) => self.parse_literal(word.clone())?,
+			Param::Label => self.get_label(word.clone()).map(|_| Variable::Str(word.clone()))?,
+			Param::Word => Variable::Str(word.clone())
+		})).collect()
+	}
+
+	fn run_line(&mut self, line: String) -> Result<(), Error> {
+		self.check_limits()?;
+		let command = UnparsedCommand::from_line(line, &self.commands)?;
+		if let Some(command) = command {
+			let spec = self.commands.commands[&command.command_name].clone();
+			let mut args = self.resolve(&spec.signature, &command.parameters)?;
+			let response = (spec.handler)(self, &mut args)?;
+			for ((param, name), value) in spec.signature.params.iter().zip(&command.parameters).zip(args) {
+				if let Param::Location(_) = param {
+					if self.vars.contains_key(name) {
+						self.insert_var(name.clone(), value)?;
+					}
+				}
+			}
+			self.respond(response)?;
+		}
+		Ok(())
+	}
+
+	/// Does what a command asked for once it's run
+	fn respond(&mut self, response: CommandResponse) -> Result<(), Error> {
+		match response {
+			CommandResponse::Declare(s, t) => {self.insert_var(s, t.zero())?;},
+			CommandResponse::Free(s) => {self.remove_var(&s).ok_or(Error::UnknownVariable(s))?;},
+			CommandResponse::Jump(label) => {
+				self.current_line = label.0;
+				self.leave_try_blocks();
+			},
+			CommandResponse::Call(label) => {
+				self.enter_routine(self.current_line)?;
+				self.current_line = label.0;
+			},
+			CommandResponse::Return => {
+				self.current_line = self.call_stack.pop().ok_or(Error::ReturnOutsideRoutine)?;
+				self.unwind(self.call_stack.len());
+			},
+			CommandResponse::Label(name) => {self.labels.insert(name, Label(self.current_line));},
+			CommandResponse::Try => {
+				// a command from the host could ask for a block on a line that doesn't start one
+				let catch_line = *self.try_blocks.get(&self.current_line).ok_or(Error::InvalidBlock("this line doesn't start a TRY block"))?;
+				self.try_handlers.push(TryHandler {try_line: self.current_line, catch_line, call_depth: self.call_stack.len()});
+			},
+			CommandResponse::Catch => {
+				// reaching the CATCH means nothing went wrong, so the handler is skipped
+				let end_line = *self.catch_blocks.get(&self.current_line).ok_or(Error::InvalidBlock("this line doesn't start a CATCH block"))?;
+				self.try_handlers.pop();
+				self.current_line = end_line;
+			},
+			CommandResponse::Test => {
+				// tests only run from the test runner, so they are skipped otherwise
+				self.current_line = *self.test_blocks.get(&self.current_line).ok_or(Error::InvalidBlock("this line doesn't start a TEST block"))?;
+			},
+			CommandResponse::EndTest => {
+				if self.testing {
+					self.exit_code = Some(0);
+				}
+			},
+			CommandResponse::OnError(label) => {self.on_error = label.map(|label| (label, self.call_stack.len()));},
+			CommandResponse::Exit(code) => {self.exit_code = Some(code);},
+			CommandResponse::Nothing => ()
+		}
+		Ok(())
+	}
+
+	/// Finds every label up front, so that lines can jump or call forward
+	fn scan_labels(&self) -> HashMap<String, Label> {
+		let mut labels = HashMap::new();
+		for (line_num, line) in self.lines.iter().enumerate() {
+			let mut words = line.split_ascii_whitespace();
+			if let (Some(command), Some(name)) = (words.next(), words.next()) {
+				if command.to_uppercase() == "LABEL" {
+					labels.insert(name.to_string(), Label(line_num));
+				}
+			}
+		}
+		labels
+	}
+
+	/// Matches up each `TRY` with its `CATCH` and `ENDTRY`, and each `TEST` with its `ENDTEST`
+	fn scan_blocks(&mut self) -> Result<(), RuntimeError> {
+		let mut open_blocks: Vec<(usize, Option<usize>)> = Vec::new();
+		let mut open_test: Option<usize> = None;
+		self.try_blocks = HashMap::new();
+		self.catch_blocks = HashMap::new();
+		self.test_blocks = HashMap::new();
+		self.block_names = HashMap::new();
+		for (line_num, line) in self.lines.iter().enumerate() {
+			let words: Vec<&str> = line.split_ascii_whitespace().collect();
+			let command = words.first().map(|command| command.to_uppercase());
+			let error = |message| RuntimeError {line: line_num + 1, error: Error::InvalidBlock(message)};
+			match command.as_deref() {
+				Some("TRY") => open_blocks.push((line_num, None)),
+				Some("CATCH") => match open_blocks.last_mut() {
+					Some((_, catch_line @ None)) if words.len() > 1 => {
+						*catch_line = Some(line_num);
+						self.block_names.insert(line_num, words[1].to_string());
+					},
+					Some((_, None)) => return Err(RuntimeError {line: line_num + 1, error: Error::MissingParameters("CATCH".to_string())}),
+					_ => return Err(error("CATCH without a TRY"))
+				},
+				Some("ENDTRY") => match open_blocks.pop() {
+					Some((try_line, Some(catch_line))) => {
+						self.try_blocks.insert(try_line, catch_line);
+						self.catch_blocks.insert(catch_line, line_num);
+					},
+					Some((_, None)) => return Err(error("ENDTRY without a CATCH")),
+					None => return Err(error("ENDTRY without a TRY"))
+				},
+				Some("TEST") if open_test.is_some() => return Err(error("TEST inside another TEST")),
+				Some("TEST") if words.len() < 2 => return Err(RuntimeError {line: line_num + 1, error: Error::MissingParameters("TEST".to_string())}),
+				Some("TEST") => {
+					open_test = Some(line_num);
+					self.block_names.insert(line_num, words[1].to_string());
+				},
+				Some("ENDTEST") => match open_test.take() {
+					Some(test_line) => {self.test_blocks.insert(test_line, line_num);},
+					None => return Err(error("ENDTEST without a TEST"))
+				},
+				_ => ()
+			}
+		}
+		if let Some((try_line, _)) = open_blocks.pop() {
+			return Err(RuntimeError {line: try_line + 1, error: Error::InvalidBlock("TRY without an ENDTRY")});
+		}
+		match open_test {
+			Some(test_line) => Err(RuntimeError {line: test_line + 1, error: Error::InvalidBlock("TEST without an ENDTEST")}),
+			None => Ok(())
+		}
+	}
+
+	/// Splits the program into lines and finds its labels and blocks
+	fn load_source(&mut self) -> Result<(), RuntimeError> {
+		self.lines = self.program.lines().map(|line| line.to_string()).collect();
+		self.labels = self.scan_labels();
+		self.scan_blocks()
+	}
+
+	/// Gets the program ready to run, compiling it first if it runs on the VM
+	fn load(&mut self) -> Result<(), RuntimeError> {
+		self.compiled = None;
+		self.vars = Vars::default();
+		let bytecode = match &self.bytecode {
+			Some(bytecode) => bytecode.clone(),
+			None if self.vm => self.compile()?,
+			None => return self.load_source()
+		};
+		let compiled = Compiled::link(bytecode, &self.commands)?;
+		let bytecode = &compiled.bytecode;
+		self.labels = bytecode.labels.iter().map(|(name, line)| (name.clone(), Label(*line))).collect();
+		self.try_blocks = bytecode.try_blocks.clone();
+		self.catch_blocks = bytecode.catch_blocks.clone();
+		self.test_blocks = bytecode.test_blocks.clone();
+		self.block_names = bytecode.block_names.clone();
+		self.vars = Vars::with_slots(&bytecode.slots);
+		self.compiled = Some(Rc::new(compiled));
+		Ok(())
+	}
+
+	/// Compiles the program's source to bytecode, optimizing it if [`Program::with_optimizer`] was used
+	pub fn compile(&mut self) -> Result<Bytecode, RuntimeError> {
+		self.load_source()?;
+		let mut bytecode = self.compile_lines();
+		if self.optimize {
+			bytecode.optimize(&self.commands);
+		}
+		Ok(bytecode)
+	}
+
+	fn line_count(&self) -> usize {
+		match &self.compiled {
+			Some(compiled) => compiled.bytecode.line_count(),
+			None => self.lines.len()
+		}
+	}
+
+	fn run_current_line(&mut self) -> Result<(), Error> {
+		match self.compiled.clone() {
+			Some(compiled) => self.run_compiled_line(&compiled),
+			None => self.run_line(self.lines[self.current_line].clone())
+		}
+	}
+
+	/// Clears everything from the last run, and declares the built-in variables
+	fn reset(&mut self) {
+		self.current_line = 0;
+		self.call_stack = Vec::new();
+		self.try_handlers = Vec::new();
+		self.on_error = None;
+		self.fault_line = None;
+		self.vars.clear();
+		self.exit_code = None;
+		self.clock.restart();
+		self.rng = match self.seed {
+			Some(seed) => Rng::from_seed(seed),
+			None => Rng::from_entropy()
+		};
+		self.vars.insert("PI", Variable::Float(std::f32::consts::PI));
+		self.vars.insert("E", Variable::Float(std::f32::consts::E));
+		self.vars.insert("ARGS", Variable::List(self.args.iter().map(|a| Variable::Str(a.clone())).collect()));
+		self.vars.insert("ERR", Variable::Str(String::new()));
+		self.vars.insert("ERRLINE", Variable::Natural(0));
+		self.outcome = None;
+		self.reset_usage();
+	}
+
+	/// Starts counting towards the limits again
+	fn reset_usage(&mut self) {
+		self.instructions = 0;
+		self.time_used = std::time::Duration::ZERO;
+		self.memory = if self.limits.memory.is_some() {
+			self.vars.iter().map(|(name, value)| name.len() + value.size()).sum()
+		} else {0};
+	}
+
+	/// Runs from the current line until the program ends or exits
+	fn run(&mut self) -> Result<i32, RuntimeError> {
+		loop {
+			match self.run_step() {
+				Status::Running => (),
+				Status::WaitingForInput => {
+					let line = self.current_line + 1;
+					return Err(RuntimeError {line, error: Error::WaitingForInput});
+				},
+				Status::Finished(code) => return Ok(code),
+				Status::Errored(error) => return Err(error)
+			}
+		}
+	}
+
+	/// Runs the current line, giving back where the program is afterwards.
+	/// Only the time spent in here counts towards the time limit
+	fn run_step(&mut self) -> Status {
+		self.step_start = std::time::Instant::now();
+		let status = self.run_timed_step();
+		self.time_used += self.step_start.elapsed();
+		status
+	}
+
+	fn run_timed_step(&mut self) -> Status {
+		if let Some(outcome) = &self.outcome {
+			return Status::from(outcome.clone());
+		}
+		if self.current_line >= self.line_count() || self.exit_code.is_some() {
+			return self.finish(Ok(self.exit_code.unwrap_or(0)));
+		}
+		if let Err(error) = self.run_current_line() {
+			if let Error::WaitingForInput = error {
+				// the line runs again once there's input, so it only counts once
+				self.instructions -= 1;
+				return Status::WaitingForInput;
+			}
+			if let Err(error) = self.handle_error(error, 0) {
+				let line = self.fault_line.unwrap_or(self.current_line) + 1;
+				return self.finish(Err(RuntimeError {line, error}));
+			}
+		}
+		self.current_line += 1;
+		if self.current_line >= self.line_count() || self.exit_code.is_some() {
+			return self.finish(Ok(self.exit_code.unwrap_or(0)));
+		}
+		Status::Running
+	}
+
+	fn finish(&mut self, outcome: Result<i32, RuntimeError>) -> Status {
+		self.outcome = Some(outcome.clone());
+		Status::from(outcome)
+	}
+
+	/// Gets the program ready to run from the top, for stepping through it with [`Program::step`]
+	pub fn start(&mut self) -> Result<(), RuntimeError> {
+		self.load()?;
+		self.testing = false;
+		self.reset();
+		self.started = true;
+		Ok(())
+	}
+
+	/// Runs one line of the program, starting it first if it hasn't been.
+	/// Once the program has finished or errored, this keeps giving back how it ended
+	pub fn step(&mut self) -> Status {
+		if !self.started {
+			if let Err(error) = self.start() {
+				return self.finish(Err(error));
+			}
+		}
+		self.run_step()
+	}
+
+	/// Runs up to `instructions` lines, stopping early if the program finishes, errors or has to wait for input
+	pub fn run_for(&mut self, instructions: u64) -> Status {
+		let mut status = Status::Running;
+		for _ in 0..instructions {
+			status = self.step();
+			if !matches!(status, Status::Running) {
+				break;
+			}
+		}
+		status
+	}
+
+	pub fn run_program(&mut self) -> Result<i32, RuntimeError> {
+		self.start()?;
+		self.run()
+	}
+
+	/// Runs the rest of the program, from wherever it was stopped or restored
+	pub fn resume(&mut self) -> Result<i32, RuntimeError> {
+		if !self.started {
+			self.start()?;
+		}
+		self.run()
+	}
+
+	/// Runs each `TEST` block on its own, with none of the variables from the other tests.
+	/// A test passes if it reaches its `ENDTEST` without an error or a non-zero `EXIT`
+	pub fn run_tests(&mut self) -> Result<Vec<TestOutcome>, RuntimeError> {
+		self.load()?;
+		self.testing = true;
+		let mut tests: Vec<(usize, String)> = self.test_blocks.keys().map(|line| (*line, self.block_names[line].clone())).collect();
+		tests.sort();
+
+		let mut outcomes = Vec::new();
+		for (line, name) in tests {
+			self.reset();
+			self.current_line = line + 1;
+			outcomes.push(TestOutcome {name, line: line + 1, result: self.run()});
+		}
+		self.testing = false;
+		Ok(outcomes)
+	}
+}
+
+/// The result of one `TEST` block. The test passed if it exited with a code of zero
+pub struct TestOutcome {
+	pub name: String,
+	pub line: usize,
+	pub result: Result<i32, RuntimeError>
+}
+
+impl TestOutcome {
+	pub fn passed(&self) -> bool {
+		matches!(self.result, Ok(0))
+	}
+}
+
+impl Registry {
+	/// A registry with every built-in command
+	pub fn builtins() -> Self {
+		use Param::{Location, Operand, Literal, Word};
+		let mut commands = Registry::new();
+
+		commands.register("ADD", vec![Location(Kind::Any), Operand(Kind::Any), Operand(Kind::Any)], |_, args| {
+			let [location, op1, op2] = args else {unreachable!()};
+			Command::Add(location, op1.clone(), op2.clone()).run()
+		});
+		commands.register("SUB", vec![Location(Kind::Any), Operand(Kind::Number), Operand(Kind::Number)], |_, args| {
+			let [location, op1, op2] = args else {unreachable!()};
+			Command::Sub(location, Number::from_var(op1.clone())?, Number::from_var(op2.clone())?).run()
+		});
+		commands.register("MUL", vec![Location(Kind::Any), Operand(Kind::Number), Operand(Kind::Number)], |_, args| {
+			let [location, op1, op2] = args else {unreachable!()};
+			Command::Mul(location, Number::from_var(op1.clone())?, Number::from_var(op2.clone())?).run()
+		});
+		commands.register("DIV", vec![Location(Kind::Any), Operand(Kind::Number), Operand(Kind::Number)], |_, args| {
+			let [location, op1, op2] = args else {unreachable!()};
+			Command::Div(location, Number::from_var(op1.clone())?, Number::from_var(op2.clone())?).run()
+		});
+		commands.register("MOD", vec![Location(Kind::Any), Operand(Kind::Number), Operand(Kind::Number)], |_, args| {
+			let [location, op1, op2] = args else {unreachable!()};
+			Command::Mod(location, Number::from_var(op1.clone())?, Number::from_var(op2.clone())?).run()
+		});
+
+		commands.register("ROUND", vec![Location(Kind::Any), Operand(Kind::Number)], |_, args| {
+			let [location, op1] = args else {unreachable!()};
+			Command::Round(location, op1.to_float()?).run()
+		});
+		commands.register("FLOOR", vec![Location(Kind::Any), Operand(Kind::Number)], |_, args| {
+			let [location, op1] = args else {unreachable!()};
+			Command::Floor(location, op1.to_float()?).run()
+		});
+		commands.register("CEIL", vec![Location(Kind::Any), Operand(Kind::Number)], |_, args| {
+			let [location, op1] = args else {unreachable!()};
+			Command::Ceil(location, op1.to_float()?).run()
+		});
+
+		commands.register("SQRT", vec![Location(Kind::Any), Operand(Kind::Number)], |_, args| {
+			let [location, op1] = args else {unreachable!()};
+			Command::Sqrt(location, Number::from_var(op1.clone())?).run()
+		});
+		commands.register("POW", vec![Location(Kind::Any), Operand(Kind::Number), Operand(Kind::Number)], |_, args| {
+			let [location, base, exponent] = args else {unreachable!()};
+			Command::Pow(location, Number::from_var(base.clone())?, Number::from_var(exponent.clone())?).run()
+		});
+		commands.register("ABS", vec![Location(Kind::Any), Operand(Kind::Number)], |_, args| {
+			let [location, op1] = args else {unreachable!()};
+			Command::Abs(location, Number::from_var(op1.clone())?).run()
+		});
+		commands.register("MIN", vec![Location(Kind::Any), Operand(Kind::Number), Operand(Kind::Number)], |_, args| {
+			let [location, op1, op2] = args else {unreachable!()};
+			Command::Min(location, Number::from_var(op1.clone())?, Number::from_var(op2.clone())?).run()
+		});
+		commands.register("MAX", vec![Location(Kind::Any), Operand(Kind::Number), Operand(Kind::Number)], |_, args| {
+			let [location, op1, op2] = args else {unreachable!()};
+			Command::Max(location, Number::from_var(op1.clone())?, Number::from_var(op2.clone())?).run()
+		});
+		commands.register("SIN", vec![Location(Kind::Any), Operand(Kind::Number)], |_, args| {
+			let [location, op1] = args else {unreachable!()};
+			Command::Sin(location, Number::from_var(op1.clone())?).run()
+		});
+		commands.register("COS", vec![Location(Kind::Any), Operand(Kind::Number)], |_, args| {
+			let [location, op1] = args else {unreachable!()};
+			Command::Cos(location, Number::from_var(op1.clone())?).run()
+		});
+		commands.register("TAN", vec![Location(Kind::Any), Operand(Kind::Number)], |_, args| {
+			let [location, op1] = args else {unreachable!()};
+			Command::Tan(location, Number::from_var(op1.clone())?).run()
+		});
+		commands.register("ATAN2", vec![Location(Kind::Any), Operand(Kind::Number), Operand(Kind::Number)], |_, args| {
+			let [location, y, x] = args else {unreachable!()};
+			Command::Atan2(location, Number::from_var(y.clone())?, Number::from_var(x.clone())?).run()
+		});
+		commands.register("LOG", vec![Location(Kind::Any), Operand(Kind::Number)], |_, args| {
+			let [location, op1] = args else {unreachable!()};
+			Command::Log(location, Number::from_var(op1.clone())?).run()
+		});
+		commands.register("EXP", vec![Location(Kind::Any), Operand(Kind::Number)], |_, args| {
+			let [location, op1] = args else {unreachable!()};
+			Command::Exp(location, Number::from_var(op1.clone())?).run()
+		});
+		commands.register("CLAMP", vec![Location(Kind::Any), Operand(Kind::Number), Operand(Kind::Number), Operand(Kind::Number)], |_, args| {
+			let [location, op1, min, max] = args else {unreachable!()};
+			Command::Clamp(location, Number::from_var(op1.clone())?, Number::from_var(min.clone())?, Number::from_var(max.clone())?).run()
+		});
+		commands.register("SIGN", vec![Location(Kind::Any), Operand(Kind::Number)], |_, args| {
+			let [location, op1] = args else {unreachable!()};
+			Command::Sign(location, Number::from_var(op1.clone())?).run()
+		});
+
+		commands.register("AND", vec![Location(Kind::Any), Operand(Kind::Bool), Operand(Kind::Bool)], |_, args| {
+			let [location, op1, op2] = args else {unreachable!()};
+			Command::And(location, op1.to_bool()?, op2.to_bool()?).run()
+		});
+		commands.register("OR", vec![Location(Kind::Any), Operand(Kind::Bool), Operand(Kind::Bool)], |_, args| {
+			let [location, op1, op2] = args else {unreachable!()};
+			Command::Or(location, op1.to_bool()?, op2.to_bool()?).run()
+		});
+		commands.register("XOR", vec![Location(Kind::Any), Operand(Kind::Bool), Operand(Kind::Bool)], |_, args| {
+			let [location, op1, op2] = args else {unreachable!()};
+			Command::Xor(location, op1.to_bool()?, op2.to_bool()?).run()
+		});
+		commands.register("NOT", vec![Location(Kind::Any), Operand(Kind::Bool)], |_, args| {
+			let [location, op1] = args else {unreachable!()};
+			Command::Not(location, op1.to_bool()?).run()
+		});
+
+		commands.register("BAND", vec![Location(Kind::Any), Operand(Kind::Integer), Operand(Kind::Integer)], |_, args| {
+			let [location, op1, op2] = args else {unreachable!()};
+			Command::Band(location, op1.to_integer()?, op2.to_integer()?).run()
+		});
+		commands.register("BOR", vec![Location(Kind::Any), Operand(Kind::Integer), Operand(Kind::Integer)], |_, args| {
+			let [location, op1, op2] = args else {unreachable!()};
+			Command::Bor(location, op1.to_integer()?, op2.to_integer()?).run()
+		});
+		commands.register("BXOR", vec![Location(Kind::Any), Operand(Kind::Integer), Operand(Kind::Integer)], |_, args| {
+			let [location, op1, op2] = args else {unreachable!()};
+			Command::Bxor(location, op1.to_integer()?, op2.to_integer()?).run()
+		});
+		commands.register("BNOT", vec![Location(Kind::Any), Operand(Kind::Integer)], |_, args| {
+			let [location, op1] = args else {unreachable!()};
+			Command::Bnot(location, op1.to_integer()?).run()
+		});
+		commands.register("SHL", vec![Location(Kind::Any), Operand(Kind::Integer), Operand(Kind::Number)], |_, args| {
+			let [location, op1, amount] = args else {unreachable!()};
+			Command::Shl(location, op1.to_integer()?, amount.to_nat()?).run()
+		});
+		commands.register("SHR", vec![Location(Kind::Any), Operand(Kind::Integer), Operand(Kind::Number)], |_, args| {
+			let [location, op1, amount] = args else {unreachable!()};
+			Command::Shr(location, op1.to_integer()?, amount.to_nat()?).run()
+		});
+
+		commands.register("DECL", vec![Word, Word], |_, args| {
+			let [name, var_type] = args else {unreachable!()};
+			Command::Decl(name.to_str()?, VarType::from_name(var_type.to_str()?)?).run()
+		});
+		commands.register("SET", vec![Location(Kind::Any), Literal(Kind::Any)], |_, args| {
+			let [location, literal] = args else {unreachable!()};
+			Command::Set(location, literal.clone()).run()
+		});
+		commands.register("FREE", vec![Word], |_, args| Command::Free(args[0].to_str()?).run());
+
+		commands.register("LABEL", vec![Word], |_, args| Command::Label(args[0].to_str()?).run());
+		commands.register("JMP", vec![Param::Label], |program, args| Command::Jmp(program.get_label(args[0].to_str()?)?).run());
+		commands.register("JEQ", vec![Param::Label, Operand(Kind::Any), Operand(Kind::Any)], |program, args| {
+			Command::Jeq(program.get_label(args[0].to_str()?)?, args[1].clone(), args[2].clone()).run()
+		});
+		commands.register("JNE", vec![Param::Label, Operand(Kind::Any), Operand(Kind::Any)], |program, args| {
+			Command::Jne(program.get_label(args[0].to_str()?)?, args[1].clone(), args[2].clone()).run()
+		});
+		commands.register("JGT", vec![Param::Label, Operand(Kind::Any), Operand(Kind::Any)], |program, args| {
+			Command::Jgt(program.get_label(args[0].to_str()?)?, args[1].clone(), args[2].clone()).run()
+		});
+		commands.register("JLT", vec![Param::Label, Operand(Kind::Any), Operand(Kind::Any)], |program, args| {
+			Command::Jlt(program.get_label(args[0].to_str()?)?, args[1].clone(), args[2].clone()).run()
+		});
+		commands.register("JGE", vec![Param::Label, Operand(Kind::Any), Operand(Kind::Any)], |program, args| {
+			Command::Jge(program.get_label(args[0].to_str()?)?, args[1].clone(), args[2].clone()).run()
+		});
+		commands.register("JLE", vec![Param::Label, Operand(Kind::Any), Operand(Kind::Any)], |program, args| {
+			Command::Jle(program.get_label(args[0].to_str()?)?, args[1].clone(), args[2].clone()).run()
+		});
+		commands.register("JIF", vec![Param::Label, Operand(Kind::Bool)], |program, args| {
+			Command::Jif(program.get_label(args[0].to_str()?)?, args[1].to_bool()?).run()
+		});
+		commands.register("JIFNOT", vec![Param::Label, Operand(Kind::Bool)], |program, args| {
+			Command::Jifnot(program.get_label(args[0].to_str()?)?, args[1].to_bool()?).run()
+		});
+		commands.register("JMPVAR", vec![Operand(Kind::Str)], |program, args| Command::Jmp(program.get_label(args[0].to_str()?)?).run());
+
+		commands.register("EQ", vec![Location(Kind::Any), Operand(Kind::Any), Operand(Kind::Any)], |_, args| {
+			let [location, op1, op2] = args else {unreachable!()};
+			Command::Eq(location, op1.clone(), op2.clone()).run()
+		});
+		commands.register("NE", vec![Location(Kind::Any), Operand(Kind::Any), Operand(Kind::Any)], |_, args| {
+			let [location, op1, op2] = args else {unreachable!()};
+			Command::Ne(location, op1.clone(), op2.clone()).run()
+		});
+		commands.register("LT", vec![Location(Kind::Any), Operand(Kind::Any), Operand(Kind::Any)], |_, args| {
+			let [location, op1, op2] = args else {unreachable!()};
+			Command::Lt(location, op1.clone(), op2.clone()).run()
+		});
+		commands.register("GT", vec![Location(Kind::Any), Operand(Kind::Any), Operand(Kind::Any)], |_, args| {
+			let [location, op1, op2] = args else {unreachable!()};
+			Command::Gt(location, op1.clone(), op2.clone()).run()
+		});
+		commands.register("LE", vec![Location(Kind::Any), Operand(Kind::Any), Operand(Kind::Any)], |_, args| {
+			let [location, op1, op2] = args else {unreachable!()};
+			Command::Le(location, op1.clone(), op2.clone()).run()
+		});
+		commands.register("GE", vec![Location(Kind::Any), Operand(Kind::Any), Operand(Kind::Any)], |_, args| {
+			let [location, op1, op2] = args else {unreachable!()};
+			Command::Ge(location, op1.clone(), op2.clone()).run()
+		});
+
+		commands.register("PRINT", vec![Operand(Kind::Str)], |program, args| {
+			program.permissions.check(Capability::Stdout)?;
+			Command::Print(args[0].to_str()?, program.io.as_mut()).run()
+		});
+		commands.register("INPUT", vec![Location(Kind::Str)], |program, args| {
+			program.permissions.check(Capability::Stdin)?;
+			Command::Input(&mut args[0], program.io.as_mut()).run()
+		});
+		commands.register("CONVERT", Signature::new(vec![Location(Kind::Any), Operand(Kind::Any)]).with_optional(Word), |_, args| {
+			match args {
+				[location, var] => Command::Convert(location, var.clone()).run(),
+				[location, var, radix] => Command::ConvertRadix(location, var.clone(), radix_from_name(radix.to_str()?)?).run(),
+				_ => unreachable!()
+			}
+		});
+
+		commands.register("SLICE", vec![Location(Kind::Any), Operand(Kind::List), Operand(Kind::Number), Operand(Kind::Number)], |_, args| {
+			let [location, list, start, end] = args else {unreachable!()};
+			Command::Slice(location, list.to_list()?, start.to_nat()?, end.to_nat()?).run()
+		});
+		commands.register("INDEX", vec![Location(Kind::Any), Operand(Kind::List), Operand(Kind::Number)], |_, args| {
+			let [location, list, index] = args else {unreachable!()};
+			Command::Index(location, list.to_list()?, index.to_nat()?).run()
+		});
+		commands.register("LEN", vec![Location(Kind::Any), Operand(Kind::List)], |_, args| {
+			let [location, list] = args else {unreachable!()};
+			Command::Len(location, list.to_list()?).run()
+		});
+		commands.register("INSERT", vec![Location(Kind::List), Operand(Kind::Number), Operand(Kind::Any)], |_, args| {
+			let [list, index, item] = args else {unreachable!()};
+			let index = index.to_nat()?;
+			let Variable::List(list) = list else {unreachable!()};
+			Command::Insert(list, index, item.clone()).run()
+		});
+		commands.register("SORT", Signature::new(vec![Location(Kind::Any), Operand(Kind::List)]).with_optional(Word), |_, args| {
+			let descending = match args.get(2).map(|order| order.to_string().to_uppercase()) {
+				None => false,
+				Some(order) if order == "ASC" => false,
+				Some(order) if order == "DESC" => true,
+				_ => return Err(Error::InvalidArgument("the order must be ASC or DESC"))
+			};
+			let list = args[1].to_list()?;
+			Command::Sort(&mut args[0], list, descending).run()
+		});
+		commands.register("SORTBY", vec![Location(Kind::Any), Operand(Kind::List), Param::Label], |program, args| {
+			let [location, list, label] = args else {unreachable!()};
+			let sorted = program.sort_list_by(list.to_list()?, program.get_label(label.to_str()?)?)?;
+			Command::Set(location, Variable::List(sorted)).run()
+		});
+		commands.register("MAP", vec![Location(Kind::Any), Operand(Kind::List), Param::Label], |program, args| {
+			let [location, list, label] = args else {unreachable!()};
+			let mapped = program.map_list(list.to_list()?, program.get_label(label.to_str()?)?)?;
+			Command::Set(location, Variable::List(mapped)).run()
+		});
+		commands.register("FILTER", vec![Location(Kind::Any), Operand(Kind::List), Param::Label], |program, args| {
+			let [location, list, label] = args else {unreachable!()};
+			let filtered = program.filter_list(list.to_list()?, program.get_label(label.to_str()?)?)?;
+			Command::Set(location, Variable::List(filtered)).run()
+		});
+		commands.register("REDUCE", vec![Location(Kind::Any), Operand(Kind::List), Param::Label], |program, args| {
+			let [location, list, label] = args else {unreachable!()};
+			let reduced = program.reduce_list(location.clone(), list.to_list()?, program.get_label(label.to_str()?)?)?;
+			Command::Set(location, reduced).run()
+		});
+
+		commands.register("CALL", vec![Param::Label], |program, args| Command::Call(program.get_label(args[0].to_str()?)?).run());
+		commands.register("RETURN", vec![], |_, _| Command::Return.run());
+
+		commands.register("TRY", vec![], |_, _| Command::Try.run());
+		commands.register("CATCH", vec![Word], |_, _| Command::Catch.run());
+		commands.register("ENDTRY", vec![], |_, _| Command::EndTry.run());
+		commands.register("ON", vec![Word, Word, Word], |program, args| {
+			let [on, goto, label] = args else {unreachable!()};
+			if on.to_str()?.to_uppercase() != "ERROR" || goto.to_str()?.to_uppercase() != "GOTO" {
+				return Err(Error::InvalidArgument("expected ON ERROR GOTO [label]"));
+			}
+			let label = label.to_str()?;
+			let label = if label == "0" {
+				None
+			} else {
+				Some(program.get_label(label)?)
+			};
+			Command::OnError(label).run()
+		});
+
+		commands.register("TEST", vec![Word], |_, _| Command::Test.run());
+		commands.register("ENDTEST", vec![], |_, _| Command::EndTest.run());
+		commands.register("ASSERT", vec![Operand(Kind::Bool), Literal(Kind::Str)], |_, args| {
+			Command::Assert(args[0].to_bool()?, args[1].to_str()?).run()
+		});
+		commands.register("ASSERTEQ", vec![Operand(Kind::Any), Operand(Kind::Any)], |_, args| {
+			Command::AssertEq(args[0].clone(), args[1].clone()).run()
+		});
+
+		commands.register("GETENV", vec![Location(Kind::Any), Literal(Kind::Str)], |program, args| {
+			program.permissions.check(Capability::Env)?;
+			let [location, name] = args else {unreachable!()};
+			Command::GetEnv(location, name.to_str()?).run()
+		});
+		commands.register("EXIT", vec![Literal(Kind::Integer)], |_, args| {
+			let code = i32::try_from(args[0].to_integer()?).map_err(|_| Error::InvalidArgument("the exit code is too large for an int"))?;
+			Command::Exit(code).run()
+		});
+
+		commands.register("RANDOM", vec![Location(Kind::Any), Operand(Kind::Number), Operand(Kind::Number)], |program, args| {
+			let [location, min, max] = args else {unreachable!()};
+			Command::Random(location, &mut program.rng, Number::from_var(min.clone())?, Number::from_var(max.clone())?).run()
+		});
+		commands.register("SHUFFLE", vec![Location(Kind::Any), Operand(Kind::List)], |program, args| {
+			let [location, list] = args else {unreachable!()};
+			Command::Shuffle(location, &mut program.rng, list.to_list()?).run()
+		});
+		commands.register("CHOOSE", vec![Location(Kind::Any), Operand(Kind::List)], |program, args| {
+			let [location, list] = args else {unreachable!()};
+			Command::Choose(location, &mut program.rng, list.to_list()?).run()
+		});
+		commands.register("SEED", vec![Literal(Kind::Integer)], |program, args| {
+			let seed = u64::try_from(args[0].to_integer()?).map_err(|_| Error::InvalidArgument("the seed can't be negative"))?;
+			Command::Seed(&mut program.rng, seed).run()
+		});
+
+		commands.register("NOW", vec![Location(Kind::Any)], |program, args| {
+			program.check_clock()?;
+			Command::Now(&mut args[0], &program.clock).run()
+		});
+		commands.register("CLOCK", vec![Location(Kind::Any)], |program, args| {
+			program.check_clock()?;
+			Command::Clock(&mut args[0], &program.clock).run()
+		});
+		commands.register("SLEEP", vec![Literal(Kind::Integer)], |program, args| {
+			program.check_clock()?;
+			let millis = u64::try_from(args[0].to_integer()?).map_err(|_| Error::InvalidArgument("the time to sleep can't be negative"))?;
+			// a real sleep can't take the program over its time limit, so it stops when the time runs out
+			if let (Clock::System(_), Some(left)) = (&program.clock, program.time_left()) {
+				if std::time::Duration::from_millis(millis) > left {
+					std::thread::sleep(left);
+					return Err(Error::LimitExceeded(Limit::Time));
+				}
+			}
+			Command::Sleep(&mut program.clock, millis).run()
+		});
+		commands.register("FORMATDATE", vec![Location(Kind::Any), Operand(Kind::Integer), Literal(Kind::Str)], |_, args| {
+			let [location, timestamp, format] = args else {unreachable!()};
+			Command::FormatDate(location, timestamp.to_integer()?, format.to_str()?).run()
+		});
+		commands.register("PARSEDATE", vec![Location(Kind::Any), Operand(Kind::Str), Literal(Kind::Str)], |_, args| {
+			let [location, text, format] = args else {unreachable!()};
+			Command::ParseDate(location, text.to_str()?, format.to_str()?).run()
+		});
+
+		// the optimizer only works ahead of time for these, and not for commands the host has put in their place
+		for spec in commands.commands.values_mut() {
+			Rc::get_mut(spec).unwrap().builtin = true;
+		}
+		commands
+	}
+}
@@ -0,0 +1,304 @@
+//! Warnings about things that aren't errors, but probably aren't what was meant.
+//!
+//! Each kind of warning has a code and a name, and either can be used to turn it off for the next
+//! line with a `lint: allow [lint]` comment, for the whole file with `lint: allow-file [lint]`, or
+//! to change how seriously it's taken in a [`LintConfig`].
+
+use crate::check::{a, literal_type, Flow, Line, State, Status};
+use crate::{Error, Param, Program, VarType};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+/// Commands which compare two of their parameters, and the first of them
+const COMPARISONS: &[(&str, usize)] = &[
+	("EQ", 1), ("NE", 1), ("LT", 1), ("GT", 1), ("LE", 1), ("GE", 1),
+	("JEQ", 1), ("JNE", 1), ("JGT", 1), ("JLT", 1), ("JGE", 1), ("JLE", 1), ("ASSERTEQ", 0)
+];
+
+/// Comparisons that only check whether their values are equal
+const EQUALITY: &[&str] = &["JEQ", "JNE", "ASSERTEQ"];
+
+/// Lines that mark where a label or block is. Jumps carry on after them, so they aren't run themselves
+const MARKERS: &[&str] = &["LABEL", "CATCH", "ENDTRY", "TEST", "ENDTEST"];
+
+/// A kind of warning
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Lint {
+	/// A variable is declared, but its value is never read
+	UnusedVariable,
+	/// Nothing jumps to or calls a label
+	UnusedLabel,
+	/// A line can never run, like one straight after a `JMP`
+	UnreachableCode,
+	/// A variable is read before anything has given it a value
+	ReadBeforeSet,
+	/// A variable is declared again while it's still declared, which resets it
+	ShadowedDeclaration,
+	/// Values of different kinds are compared, which is an error, or never equal for the equality jumps and `ASSERTEQ`
+	MixedComparison
+}
+
+impl Lint {
+	pub const ALL: [Lint; 6] = [
+		Lint::UnusedVariable, Lint::UnusedLabel, Lint::UnreachableCode,
+		Lint::ReadBeforeSet, Lint::ShadowedDeclaration, Lint::MixedComparison
+	];
+
+	pub fn code(&self) -> &'static str {
+		match self {
+			Lint::UnusedVariable => "W001",
+			Lint::UnusedLabel => "W002",
+			Lint::UnreachableCode => "W003",
+			Lint::ReadBeforeSet => "W004",
+			Lint::ShadowedDeclaration => "W005",
+			Lint::MixedComparison => "W006"
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Lint::UnusedVariable => "unused-variable",
+			Lint::UnusedLabel => "unused-label",
+			Lint::UnreachableCode => "unreachable-code",
+			Lint::ReadBeforeSet => "read-before-set",
+			Lint::ShadowedDeclaration => "shadowed-declaration",
+			Lint::MixedComparison => "mixed-comparison"
+		}
+	}
+
+	/// Finds a lint by its code or its name
+	pub fn from_name(name: &str) -> Option<Self> {
+		Lint::ALL.iter().copied().find(|lint| lint.code().eq_ignore_ascii_case(name) || lint.name().eq_ignore_ascii_case(name))
+	}
+}
+
+/// How seriously a warning is taken
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Level {
+	/// The warning isn't reported
+	Allow,
+	Warn,
+	/// The warning is reported as an error
+	Deny
+}
+
+impl Level {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name.to_lowercase().as_str() {
+			"allow" => Some(Level::Allow),
+			"warn" => Some(Level::Warn),
+			"deny" => Some(Level::Deny),
+			_ => None
+		}
+	}
+}
+
+impl Display for Level {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			Level::Allow => "allowed",
+			Level::Warn => "warning",
+			Level::Deny => "error"
+		})
+	}
+}
+
+/// Which warnings are reported, and how seriously. Any that aren't mentioned are warnings
+#[derive(Clone, Default)]
+pub struct LintConfig {
+	levels: HashMap<Lint, Level>
+}
+
+impl LintConfig {
+	/// Reads a config with a line like `unused-variable = allow` or `W006 = deny` for each
+	/// lint it changes. Lines starting with `#` are comments
+	pub fn parse(text: &str) -> Result<Self, Error> {
+		let mut config = LintConfig::default();
+		for (number, line) in text.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			let invalid = |message: String| Error::InvalidLintConfig(format!("line {}: {}", number + 1, message));
+			let (lint, level) = line.split_once('=').ok_or_else(|| invalid("expected [lint] = [level]".to_string()))?;
+			let lint = Lint::from_name(lint.trim()).ok_or_else(|| invalid(format!("{} is not a lint", lint.trim())))?;
+			let level = Level::from_name(level.trim()).ok_or_else(|| invalid(format!("{} is not allow, warn or deny", level.trim())))?;
+			config.levels.insert(lint, level);
+		}
+		Ok(config)
+	}
+
+	pub fn with_level(mut self, lint: Lint, level: Level) -> Self {
+		self.levels.insert(lint, level);
+		self
+	}
+
+	pub fn level(&self, lint: Lint) -> Level {
+		self.levels.get(&lint).copied().unwrap_or(Level::Warn)
+	}
+}
+
+/// Something [`Program::lint`] found, and where it is
+#[derive(Clone, Debug)]
+pub struct Warning {
+	pub line: usize,
+	/// Which of the command's parameters the warning is about, counting from 0, if it's one in particular
+	pub param: Option<usize>,
+	pub lint: Lint,
+	pub level: Level,
+	pub message: String
+}
+
+impl Display for Warning {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} {} on line {}: {}", self.level, self.lint.code(), self.line, self.message)
+	}
+}
+
+/// A warning before its level is known: the line it's on counting from 0, the parameter, the lint and the message
+type Finding = (usize, Option<usize>, Lint, String);
+
+/// The type a parameter is known to have on the way into a line
+fn known_type(state: &State, param: Param, word: &str) -> Option<VarType> {
+	match (state.get(word), param) {
+		(Status::Declared(var_type, _), _) => *var_type,
+		(Status::Undeclared | Status::Freed, Param::Literal(_)) => literal_type(word),
+		_ => None
+	}
+}
+
+/// Numbers compare with each other whatever their types, but other types only compare with themselves
+fn comparable(a: VarType, b: VarType) -> bool {
+	let number = |var_type| matches!(var_type, VarType::Natural | VarType::Integer | VarType::Float);
+	a == b || (number(a) && number(b))
+}
+
+impl Program {
+	/// Finds things in the program that are probably mistakes, leaving out any that the config or a
+	/// `lint: allow` comment turns off. Errors are left to [`Program::check`]
+	pub fn lint(&mut self, config: &LintConfig) -> Vec<Warning> {
+		if self.load_source().is_err() {
+			return Vec::new();
+		}
+		let flow = self.flow();
+		let states = self.follow(&flow);
+
+		let mut found = Vec::new();
+		self.lint_unused(&flow, &mut found);
+		self.lint_unreachable(&flow, &states, &mut found);
+		for (line, state) in states.iter().enumerate() {
+			if let (Some(state), Some(command)) = (state, flow.line(line)) {
+				self.lint_line(line, command, state, &mut found);
+			}
+		}
+		found.sort_by_key(|(line, param, ..)| (*line, *param));
+
+		let allowed = self.allowed(&flow);
+		found.into_iter()
+			.filter(|(line, _, lint, _)| !allowed.contains(&(Some(*line), *lint)) && !allowed.contains(&(None, *lint)))
+			.map(|(line, param, lint, message)| Warning {line: line + 1, param, lint, level: config.level(lint), message})
+			.filter(|warning| warning.level != Level::Allow)
+			.collect()
+	}
+
+	/// Variables that are never read and labels that are never jumped to
+	fn lint_unused(&self, flow: &Flow, found: &mut Vec<Finding>) {
+		let mut read = HashSet::new();
+		let mut jumped_to = HashSet::new();
+		for command in (0..flow.lines.len()).filter_map(|line| flow.line(line)) {
+			for (index, param, word) in command.params() {
+				match param {
+					Param::Operand(_) => {read.insert(word);},
+					// a literal might name a variable, or be a list of them
+					Param::Literal(_) => read.extend(word.trim().trim_start_matches('[').trim_end_matches(']').split(',').map(str::trim)),
+					Param::Label => {jumped_to.insert(word);},
+					Param::Word if command.name == "ON" && index == 2 => {jumped_to.insert(word);},
+					_ => ()
+				}
+				// a str with a label's name in it might be jumped to with JMPVAR
+				if let Some(name) = word.strip_prefix('"').and_then(|word| word.strip_suffix('"')) {
+					jumped_to.insert(name);
+				}
+			}
+		}
+
+		let mut reported = HashSet::new();
+		for line in 0..flow.lines.len() {
+			let Some(command) = flow.line(line) else {continue};
+			match (command.name.as_str(), command.words.first()) {
+				("DECL", Some(name)) if !read.contains(name.as_str()) && reported.insert(name) => {
+					found.push((line, Some(0), Lint::UnusedVariable, format!("{} is declared but never read", name)));
+				},
+				("LABEL", Some(name)) if !jumped_to.contains(name.as_str()) => {
+					found.push((line, Some(0), Lint::UnusedLabel, format!("nothing jumps to or calls {}", name)));
+				},
+				_ => ()
+			}
+		}
+	}
+
+	/// The first line of each run of lines that can't be reached
+	fn lint_unreachable(&self, flow: &Flow, states: &[Option<State>], found: &mut Vec<Finding>) {
+		let mut in_run = false;
+		for (line, state) in states.iter().enumerate() {
+			match &flow.lines[line] {
+				Ok(None) => continue,
+				Ok(Some(command)) if MARKERS.contains(&command.name.as_str()) => continue,
+				_ => ()
+			}
+			if state.is_none() && !in_run {
+				found.push((line, None, Lint::UnreachableCode, "this line can never run".to_string()));
+			}
+			in_run = state.is_none();
+		}
+	}
+
+	fn lint_line(&self, line: usize, command: &Line, state: &State, found: &mut Vec<Finding>) {
+		let mut read = HashSet::new();
+		for (index, param, word) in command.params() {
+			let word = word.trim();
+			let unset = matches!((param, state.get(word)), (Param::Operand(_) | Param::Literal(_), Status::Declared(_, false)));
+			if unset && read.insert(word) {
+				found.push((line, Some(index), Lint::ReadBeforeSet, format!("{} is read before it's given a value", word)));
+			}
+		}
+
+		match (command.name.as_str(), command.words.first()) {
+			("DECL", Some(name)) if matches!(state.get(name), Status::Declared(..)) => {
+				found.push((line, Some(0), Lint::ShadowedDeclaration, format!("{} is already declared, so declaring it again resets it", name)));
+			},
+			(name, _) => if let Some((_, first)) = COMPARISONS.iter().find(|(command, _)| *command == name) {
+				let params: Vec<(usize, Param, &str)> = command.params().skip(*first).take(2).collect();
+				let [(_, param1, word1), (_, param2, word2)] = params[..] else {return};
+				let (Some(type1), Some(type2)) = (known_type(state, param1, word1), known_type(state, param2, word2)) else {return};
+				if !comparable(type1, type2) {
+					let outcome = if EQUALITY.contains(&name) {"they're never equal"} else {"they can't be ordered"};
+					let message = format!("{} is {} but {} is {}, so {}", word1, a(type1), word2, a(type2), outcome);
+					found.push((line, Some(*first), Lint::MixedComparison, message));
+				}
+			}
+		}
+	}
+
+	/// The lints turned off by `lint: allow` comments for the command line after each one,
+	/// or with no line if they're turned off for the whole file by `lint: allow-file`
+	fn allowed(&self, flow: &Flow) -> HashSet<(Option<usize>, Lint)> {
+		let mut allowed = HashSet::new();
+		let mut next_line = Vec::new();
+		for (line, text) in self.lines.iter().enumerate() {
+			if !matches!(flow.lines[line], Ok(None)) {
+				allowed.extend(next_line.drain(..).map(|lint| (Some(line), lint)));
+				continue;
+			}
+			let words: Vec<&str> = text.split_whitespace().collect();
+			let Some(start) = words.iter().position(|word| word.eq_ignore_ascii_case("lint:")) else {continue};
+			let lints = words.iter().skip(start + 2).filter_map(|word| Lint::from_name(word.trim_matches(',')));
+			match words.get(start + 1).map(|word| word.to_lowercase()).as_deref() {
+				Some("allow") => next_line.extend(lints),
+				Some("allow-file") => allowed.extend(lints.map(|lint| (None, lint))),
+				_ => ()
+			}
+		}
+		allowed
+	}
+}
@@ -1,842 +1,349 @@
-use std::collections::HashMap;
-use std::fmt::Display;
-use std::convert::TryInto;
-
-struct UnparsedCommand {
-	pub command_name : String,
-	pub parameters : Vec<String>
-}
-
-impl UnparsedCommand {
-	fn from_line(line: String, command_parameter_num_map: &HashMap<String, u8>) -> Option<Self> {
-		let words : Vec<&str> = line.split_ascii_whitespace().collect();
-		if command_parameter_num_map.contains_key(&(**words.get(0).unwrap()).to_string().to_uppercase()) {
-			let command_name = (**words.get(0).unwrap()).to_string();
-			let param_num = command_parameter_num_map.get(&command_name).unwrap();
-			let parameters = Vec::from(&words[1..=(*param_num as usize)]).iter().map(|s| (**s).to_string()).collect();
-			Some(UnparsedCommand {command_name, parameters})
-		} else {
-			None
+use bota_basic::{Bytecode, Level, Limits, LintConfig, Permissions, Program, RuntimeError};
+use std::path::{Path, PathBuf};
+
+/// Runs the tests in every `.bas` file in a directory, printing how each one went.
+/// Returns whether all of them passed
+fn run_test_dir(dir: &str, seed: Option<u64>, fake_clock: Option<u64>, permissions: &Permissions) -> bool {
+	let mut paths: Vec<std::path::PathBuf> = match std::fs::read_dir(dir) {
+		Ok(entries) => entries.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+			.filter(|path| path.extension().is_some_and(|extension| extension == "bas"))
+			.collect(),
+		Err(error) => {
+			eprintln!("couldn't read {}: {}", dir, error);
+			return false;
+		}
+	};
+	paths.sort();
+
+	let (mut passed, mut failed) = (0, 0);
+	for path in paths {
+		let file = match std::fs::read_to_string(&path) {
+			Ok(file) => file,
+			Err(error) => {
+				println!("FAIL {}: {}", path.display(), error);
+				failed += 1;
+				continue;
+			}
+		};
+		let mut program = Program::new(file).with_permissions(permissions.clone());
+		if let Some(seed) = seed {
+			program = program.with_seed(seed);
+		}
+		if let Some(unix_millis) = fake_clock {
+			program = program.with_fake_clock(unix_millis);
+		}
+		match program.run_tests() {
+			Ok(outcomes) => for outcome in outcomes {
+				match &outcome.result {
+					Ok(0) => println!("PASS {}:{} {}", path.display(), outcome.line, outcome.name),
+					Ok(code) => println!("FAIL {}:{} {}: exited with code {}", path.display(), outcome.line, outcome.name, code),
+					Err(error) => println!("FAIL {}:{} {}: {}", path.display(), outcome.line, outcome.name, error)
+				}
+				if outcome.passed() {
+					passed += 1;
+				} else {
+					failed += 1;
+				}
+			},
+			Err(error) => {
+				println!("FAIL {}: {}", path.display(), error);
+				failed += 1;
+			}
 		}
 	}
+	println!("{} passed, {} failed", passed, failed);
+	failed == 0
 }
 
-#[derive(Clone, PartialEq, PartialOrd)]
-enum Variable {
-	Natural(u32),
-	Int(i32),
-	Float(f32),
-	Char(char),
-	Bool(bool),
-	Str(String),
-	List(Vec<Variable>)
+fn source_program(source: String, optimize: bool) -> Program {
+	let program = Program::new(source);
+	if optimize {program.with_optimizer()} else {program}
 }
 
-impl Variable {
-	pub fn to_float(&self) -> f32 {
-		match self {
-			Variable::Natural(n) => *n as f32,
-			Variable::Int(i) => *i as f32,
-			Variable::Float(f) => *f,
-			_ => panic!()
-		}
+/// Where compiled scripts are cached. `BOTA_BASIC_CACHE` overrides the usual cache directory
+fn cache_dir() -> Option<PathBuf> {
+	if let Some(dir) = std::env::var_os("BOTA_BASIC_CACHE") {
+		return Some(dir.into());
 	}
+	std::env::var_os("XDG_CACHE_HOME").map(PathBuf::from)
+		.or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+		.map(|dir| dir.join("bota_basic"))
 }
 
-impl std::fmt::Debug for Variable {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		write!(f, "{}", self)
-	}
+/// Compiles a script, reusing the bytecode from the last time it was compiled if it's cached.
+/// Cached bytecode is only used if it was made by this version and still links against the built-in commands
+fn compile_cached(source: String, optimize: bool, cache: bool) -> Result<Bytecode, RuntimeError> {
+	let hash = Bytecode::hash_source(&source);
+	let name = format!("{:016x}-{}{}.bbc", hash, env!("CARGO_PKG_VERSION"), if optimize {"-O"} else {""});
+	let path = cache_dir().filter(|_| cache).map(|dir| dir.join(name));
+	if let Some(path) = &path {
+		let cached = std::fs::read(path).ok().and_then(|bytes| Bytecode::from_bytes(&bytes).ok())
+			.filter(|bytecode| bytecode.source_hash() == hash)
+			.filter(|bytecode| Program::from_bytecode(bytecode.clone()).start().is_ok());
+		if let Some(bytecode) = cached {
+			return Ok(bytecode);
+		}
+	}
+	let bytecode = source_program(source, optimize).compile()?;
+	if let Some(path) = path {
+		// the cache only saves time, so it's fine if it can't be written
+		let _ = std::fs::create_dir_all(path.parent().unwrap()).and_then(|_| std::fs::write(&path, bytecode.to_bytes()));
+	}
+	Ok(bytecode)
 }
 
-impl Display for Variable {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		match self {
-			Variable::Natural(n) => write!(f, "{}", n),
-			Variable::Int(i) => write!(f, "{}", i),
-			Variable::Float(float) => write!(f, "{}", float),
-			Variable::Char(c) => write!(f, "{}", c),
-			Variable::Bool(b) => write!(f, "{}", b),
-			Variable::Str(s) => write!(f, "{}", s),
-			Variable::List(l) => write!(f, "{:?}", l)
-		}
-	}
+fn read_bytecode(filename: &str) -> Result<Bytecode, String> {
+	let bytes = std::fs::read(filename).map_err(|error| format!("couldn't read {}: {}", filename, error))?;
+	Bytecode::from_bytes(&bytes).map_err(|error| format!("couldn't load {}: {}", filename, error))
 }
 
-#[derive(Clone)]
-enum VarType {
-	Natural,
-	Integer,
-	Float,
-	Character,
-	Boolean,
-	Str,
-	List
+/// Prints the bytecode a `.bas` file compiles to, or that's in a `.bbc` file
+fn disassemble_file(filename: &str, optimize: bool) -> Result<(), String> {
+	let mut program = if filename.ends_with(".bbc") {
+		Program::from_bytecode(read_bytecode(filename)?)
+	} else {
+		source_program(std::fs::read_to_string(filename).map_err(|error| format!("couldn't read {}: {}", filename, error))?, optimize)
+	};
+	print!("{}", program.disassemble().map_err(|error| error.to_string())?);
+	Ok(())
 }
 
-#[derive(Clone)]
-struct Label(usize);
-
-#[derive(Clone)]
-enum Number {
-	Natural(u32),
-	Integer(i32),
-	Float(f32)
+/// Compiles `input` to a `.bbc` file, which is `output` or the input with its extension changed
+fn compile_file(input: &str, output: Option<String>, optimize: bool) -> Result<(), String> {
+	let source = std::fs::read_to_string(input).map_err(|error| format!("couldn't read {}: {}", input, error))?;
+	let bytecode = source_program(source, optimize).compile().map_err(|error| error.to_string())?;
+	let output = output.map_or_else(|| Path::new(input).with_extension("bbc"), PathBuf::from);
+	std::fs::write(&output, bytecode.to_bytes()).map_err(|error| format!("couldn't write {}: {}", output.display(), error))
 }
 
-impl Number {
-	pub fn from_var(var: Variable) -> Self {
-		match var {
-			Variable::Natural(n) => Number::Natural(n),
-			Variable::Int(i) => Number::Integer(i),
-			Variable::Float(f) => Number::Float(f),
-			_ => panic!()
-		}
+/// Prints every problem the type checker finds in a file. Returns whether there weren't any
+fn check_file(filename: &str) -> Result<bool, String> {
+	let source = std::fs::read_to_string(filename).map_err(|error| format!("couldn't read {}: {}", filename, error))?;
+	let errors = Program::new(source).check();
+	for error in &errors {
+		println!("{}:{}: {}", filename, error.line, error.problem);
 	}
-
-	pub fn to_float(&self) -> f32 {
-		match self {
-			Number::Natural(n) => *n as f32,
-			Number::Integer(i) => *i as f32,
-			Number::Float(f) => *f
-		}
-	}
-}
-
-enum Command<'a> {
-	Add(&'a mut Variable, Variable, Variable),
-	Sub(&'a mut Variable, Number, Number),
-	Mul(&'a mut Variable, Number, Number),
-	Div(&'a mut Variable, Number, Number),
-	Mod(&'a mut Variable, Number, Number),
-	Round(&'a mut Variable, f32),
-	Floor(&'a mut Variable, f32),
-	Ceil(&'a mut Variable, f32),
-	And(&'a mut Variable, bool, bool),
-	Or(&'a mut Variable, bool, bool),
-	Xor(&'a mut Variable, bool, bool),
-	Not(&'a mut Variable, bool),
-	Decl(String, VarType),
-	Set(&'a mut Variable, Variable),
-	Free(String),
-	Label(String),
-	Jmp(Label),
-	Jeq(Label, Variable, Variable),
-	Jgt(Label, Variable, Variable),
-	Jlt(Label, Variable, Variable),
-	Jne(Label, Variable, Variable),
-	Print(String),
-	Input(&'a mut Variable),
-	Convert(&'a mut Variable, Variable),
-	Slice(&'a mut Variable, Vec<Variable>, u32, u32),
-	Index(&'a mut Variable, Vec<Variable>, u32),
-	Len(&'a mut Variable, Vec<Variable>),
-	Insert(&'a mut Vec<Variable>, u32, Variable)
+	Ok(errors.is_empty())
 }
 
-enum CommandResponse {
-	Declare(String, VarType),
-	Label(String),
-	Free(String),
-	Jump(Label),
-	Nothing
+/// The lint config for a script, from the nearest `bota_basic.lint` in its directory or the ones above it
+fn lint_config(filename: &str) -> Result<LintConfig, String> {
+	let path = std::fs::canonicalize(filename).map_err(|error| format!("couldn't read {}: {}", filename, error))?;
+	let Some(config) = path.ancestors().skip(1).map(|dir| dir.join("bota_basic.lint")).find(|config| config.is_file()) else {
+		return Ok(LintConfig::default());
+	};
+	let text = std::fs::read_to_string(&config).map_err(|error| format!("couldn't read {}: {}", config.display(), error))?;
+	LintConfig::parse(&text).map_err(|error| format!("{}: {}", config.display(), error))
 }
 
-impl<'a> Command<'a> {
-
-	pub fn run(&mut self) -> CommandResponse {
-		match self {
-			Command::Add(ref mut l, o1, o2) => Self::add(l, o1.clone(), o2.clone()),
-			Command::Sub(ref mut l, o1, o2) => Self::sub(l, o1.clone(), o2.clone()),
-			Command::Mul(ref mut l, o1, o2) => Self::mul(l, o1.clone(), o2.clone()),
-			Command::Div(ref mut l, o1, o2) => Self::div(l, o1.clone(), o2.clone()),
-			Command::Mod(ref mut l, o1, o2) => Self::modulo(l, o1.clone(), o2.clone()),
-			Command::Round(ref mut l, o1) => Self::round(l, *o1),
-			Command::Floor(ref mut l, o1) => Self::floor(l, *o1),
-			Command::Ceil(ref mut l, o1) => Self::ceil(l, *o1),
-			Command::And(ref mut l, o1, o2) => Self::and(l, *o1, *o2),
-			Command::Or(ref mut l, o1, o2) => Self::or(l, *o1, *o2),
-			Command::Xor(ref mut l, o1, o2) => Self::xor(l, *o1, *o2),
-			Command::Not(ref mut l, o1) => Self::not(l, *o1),
-			Command::Decl(name, var_type) => return Self::decl((**name).to_string(), var_type.clone()),
-			Command::Set(ref mut l, literal) => Self::set(l, literal.clone()),
-			Command::Free(var_name) => return Self::free((**var_name).to_string()),
-			Command::Label(name) => return Self::label((**name).to_string()),
-			Command::Jmp(label) => return Self::jmp(label.clone()),
-			Command::Jeq(label, o1, o2) => return Self::jeq(label.clone(), o1.clone(), o2.clone()),
-			Command::Jgt(label, o1, o2) => return Self::jgt(label.clone(), o1.clone(), o2.clone()),
-			Command::Jlt(label, o1, o2) => return Self::jlt(label.clone(), o1.clone(), o2.clone()),
-			Command::Jne(label, o1, o2) => return Self::jne(label.clone(), o1.clone(), o2.clone()),
-			Command::Print(text) => Self::print((**text).to_string()),
-			Command::Input(ref mut location) => Self::input(location),
-			Command::Convert(ref mut location, variable) => Self::convert(location, variable),
-			Command::Slice(ref mut location, list, start, end) => Self::slice(location, list.to_vec(), *start, *end),
-			Command::Index(ref mut location, list, index) => Self::index(location, list.to_vec(), *index),
-			Command::Len(ref mut location, list) => Self::len(location, list.to_vec()),
-			Command::Insert(ref mut list, index, item) => Self::insert(list, *index, item.clone())
-		};
-		CommandResponse::Nothing
-	}
-
-	fn add(location: &mut Variable, op1: Variable, op2: Variable) {
-		if let Variable::List(ref mut location) = location {
-			if let Variable::List(ref op1) = op1 {
-				if let Variable::List(ref op2) = op2 {
-					location.clear();
-					location.append(&mut op1.clone());
-					location.append(&mut op2.clone());
-				} else {
-					location.clear();
-					location.append(&mut op1.clone());
-					location.push(op2.clone());
-				}
-			} else {
-				location.clear();
-				location.push(op1.clone());
-				location.push(op2.clone());
-			}
-		} else if let Variable::Str(ref mut string) = location {
-			string.clear();
-			string.push_str(&op1.to_string());
-			string.push_str(&op2.to_string());
-		} else if let Variable::Natural(ref mut num) = location {
-			*num = (op1.to_float() + op2.to_float()).round().abs() as u32;
-		} else if let Variable::Int(ref mut num) = location {
-			*num = (op1.to_float() + op2.to_float()).round() as i32;
-		} else if let Variable::Float(ref mut num) = location {
-			*num = op1.to_float() + op2.to_float();
-		} else {
-			panic!();
-		}
-	}
-
-	fn sub(location: &mut Variable, op1: Number, op2: Number) {
-		if let Variable::Natural(ref mut n) = location {
-			*n = (op1.to_float() + op2.to_float()).round().abs() as u32;
-		} else if let Variable::Int(ref mut n) = location {
-			*n = (op1.to_float() + op2.to_float()).round() as i32;
-		} else if let Variable::Float(ref mut n) = location {
-			*n = op1.to_float() + op2.to_float();
-		} else {
-			panic!();
-		}
-	}
-
-	fn mul(location: &mut Variable, op1: Number, op2: Number) {
-		if let Variable::Natural(ref mut n) = location {
-			*n = (op1.to_float() * op2.to_float()).round().abs() as u32;
-		} else if let Variable::Int(ref mut n) = location {
-			*n = (op1.to_float() * op2.to_float()).round() as i32;
-		} else if let Variable::Float(ref mut n) = location {
-			*n = op1.to_float() * op2.to_float();
-		} else {
-			panic!();
-		}
+/// Prints the warnings the linter finds in a file. Returns whether none of them were errors
+fn lint_file(filename: &str) -> Result<bool, String> {
+	let config = lint_config(filename)?;
+	let source = std::fs::read_to_string(filename).map_err(|error| format!("couldn't read {}: {}", filename, error))?;
+	let warnings = Program::new(source).lint(&config);
+	for warning in &warnings {
+		println!("{}:{}: {} {}: {}", filename, warning.line, warning.level, warning.lint.code(), warning.message);
 	}
+	Ok(warnings.iter().all(|warning| warning.level != Level::Deny))
+}
 
-	fn div(location: &mut Variable, op1: Number, op2: Number) {
-		if let Variable::Natural(ref mut n) = location {
-			*n = (op1.to_float() / op2.to_float()).round().abs() as u32;
-		} else if let Variable::Int(ref mut n) = location {
-			*n = (op1.to_float() / op2.to_float()).round() as i32;
-		} else if let Variable::Float(ref mut n) = location {
-			*n = op1.to_float() / op2.to_float();
+/// Formats each file in place or, when checking, only says which ones would change.
+/// Returns whether every file was already formatted
+fn format_files(filenames: &[String], check: bool) -> Result<bool, String> {
+	let mut formatted = true;
+	for filename in filenames {
+		let source = std::fs::read_to_string(filename).map_err(|error| format!("couldn't read {}: {}", filename, error))?;
+		let output = Program::new(source.clone()).format();
+		if output == source {
+			continue;
+		}
+		formatted = false;
+		if check {
+			println!("{} isn't formatted", filename);
 		} else {
-			panic!();
+			std::fs::write(filename, output).map_err(|error| format!("couldn't write {}: {}", filename, error))?;
 		}
 	}
+	Ok(formatted)
+}
 
-	fn modulo(location: &mut Variable, op1: Number, op2: Number) {
-		if let Variable::Natural(ref mut n) = location {
-			*n = (op1.to_float() % op2.to_float()).round().abs() as u32;
-		} else if let Variable::Int(ref mut n) = location {
-			*n = (op1.to_float() % op2.to_float()).round() as i32;
-		} else if let Variable::Float(ref mut n) = location {
-			*n = op1.to_float() % op2.to_float();
-		} else {
-			panic!();
-		}
-	}
+/// Prints an error with the command line and stops
+fn usage_error(message: String) -> ! {
+	eprintln!("{}", message);
+	std::process::exit(1);
+}
 
-	fn round(location: &mut Variable, op1: f32) {
-		if let Variable::Natural(ref mut n) = location {
-			*n = op1.round().abs() as u32;
-		} else if let Variable::Int(ref mut n) = location {
-			*n = op1.round() as i32;
-		} else if let Variable::Float(ref mut n) = location {
-			*n = op1.round();
-		} else {
-			panic!();
-		}
-	}
+/// Reads the value given after a flag, stopping with an error if it's missing or isn't `what`
+fn flag_value<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>, flag: &str, what: &str) -> T {
+	let value = args.next().unwrap_or_else(|| usage_error(format!("{} needs a value", flag)));
+	value.parse().unwrap_or_else(|_| usage_error(format!("{} must be {}, not {}", flag, what, value)))
+}
 
-	fn floor(location: &mut Variable, op1:f32) {
-		if let Variable::Natural(ref mut n) = location {
-			*n = op1.floor().abs() as u32;
-		} else if let Variable::Int(ref mut n) = location {
-			*n = op1.floor() as i32;
-		} else if let Variable::Float(ref mut n) = location {
-			*n = op1.floor();
-		} else {
-			panic!();
+fn main() {
+	let mut args = std::env::args().skip(1);
+	let mut seed = None;
+	let mut fake_clock = None;
+	let mut filename = None;
+	let mut limits = Limits::default();
+	let mut save_on_exit = None;
+	let mut resume = None;
+	let mut vm = false;
+	let mut cache = true;
+	let mut optimize = false;
+	// scripts can use the console, and anything else has to be allowed
+	let mut permissions = Permissions {stdin: true, stdout: true, ..Permissions::none()};
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--allow-env" => permissions.env = true,
+			"--allow-clock" => permissions.clock = true,
+			"--allow-file" => permissions.files.push(args.next().unwrap_or_else(|| usage_error("--allow-file needs a path".to_string())).into()),
+			"--allow-process" => permissions.process = true,
+			"--allow-net" => permissions.network = true,
+			"--allow-all" => permissions = Permissions::all(),
+			"--seed" => seed = Some(flag_value(&mut args, "--seed", "a natural number")),
+			"--fake-clock" => fake_clock = Some(flag_value(&mut args, "--fake-clock", "a number of milliseconds")),
+			"--max-instructions" => limits.instructions = Some(flag_value(&mut args, "--max-instructions", "a number of lines")),
+			"--time-limit" => limits.time = Some(std::time::Duration::from_millis(flag_value(&mut args, "--time-limit", "a number of milliseconds"))),
+			"--save-on-exit" => save_on_exit = Some(args.next().expect("--save-on-exit needs a path")),
+			"--vm" => vm = true,
+			"--no-cache" => cache = false,
+			"-O" => optimize = true,
+			"--resume" => resume = Some(args.next().expect("--resume needs a path")),
+			_ => {
+				filename = Some(arg);
+				break;
+			}
 		}
 	}
 
-	fn ceil(location: &mut Variable, op1: f32) {
-		if let Variable::Natural(ref mut n) = location {
-			*n = op1.ceil() as u32;
-		} else if let Variable::Int(ref mut n) = location {
-			*n = op1.ceil() as i32;
-		} else if let Variable::Float(ref mut n) = location {
-			*n = op1.ceil();
-		} else {
-			panic!();
-		}
+	if filename.as_deref() == Some("test") {
+		let dir = args.next().unwrap_or_else(|| ".".to_string());
+		let passed = run_test_dir(&dir, seed, fake_clock, &permissions);
+		std::process::exit(if passed {0} else {1});
 	}
 
-	fn and(location: &mut Variable, op1: bool, op2: bool) {
-		if let Variable::Bool(ref mut b) = location {
-			*b = op1 && op2;
-		} else {
-			panic!()
+	if filename.as_deref() == Some("compile") {
+		let mut input = None;
+		let mut output = None;
+		while let Some(arg) = args.next() {
+			match arg.as_str() {
+				"-o" => output = Some(args.next().expect("-o needs a path")),
+				"-O" => optimize = true,
+				_ => input = Some(arg)
+			}
 		}
-	}
-
-	fn or(location: &mut Variable, op1: bool, op2: bool) {
-		if let Variable::Bool(ref mut b) = location {
-			*b = op1 || op2;
-		} else {
-			panic!()
+		if let Err(error) = compile_file(&input.expect("compile needs a file"), output, optimize) {
+			eprintln!("{}", error);
+			std::process::exit(1);
 		}
+		return;
 	}
 
-	fn xor(location: &mut Variable, op1: bool, op2: bool) {
-		if let Variable::Bool(ref mut b) = location {
-			*b = op1 != op2;
-		} else {
-			panic!()
+	if filename.as_deref() == Some("check") {
+		match check_file(&args.next().expect("check needs a file")) {
+			Ok(passed) => std::process::exit(if passed {0} else {1}),
+			Err(error) => {
+				eprintln!("{}", error);
+				std::process::exit(1);
+			}
 		}
 	}
 
-	fn not(location: &mut Variable, op1: bool) {
-		if let Variable::Bool(ref mut b) = location {
-			*b = !op1;
-		} else {
-			panic!()
+	if filename.as_deref() == Some("lint") {
+		match lint_file(&args.next().expect("lint needs a file")) {
+			Ok(passed) => std::process::exit(if passed {0} else {1}),
+			Err(error) => {
+				eprintln!("{}", error);
+				std::process::exit(1);
+			}
 		}
 	}
 
-	fn decl(var_name: String, var_type: VarType) -> CommandResponse {
-		CommandResponse::Declare(var_name, var_type)
-	}
-
-	fn set(location: &mut Variable, literal: Variable) {
-		if let Variable::Bool(ref mut b) = location {
-			if let Variable::Bool(nb) = literal {
-				*b = nb;
-			} else {
-				panic!();
-			}
-		} else if let Variable::Char(ref mut c) = location {
-			if let Variable::Char(nc) = literal {
-				*c = nc;
-			} else {
-				panic!();
-			}
-		} else if let Variable::Float(ref mut f) = location {
-			if let Variable::Float(nf) = literal {
-				*f = nf;
-			} else {
-				panic!();
-			}
-		} else if let Variable::Int(ref mut i) = location {
-			if let Variable::Int(ni) = literal {
-				*i = ni;
-			} else {
-				panic!();
-			}
-		} else if let Variable::List(ref mut l) = location {
-			if let Variable::List(nl) = literal {
-				*l = nl.clone();
-			} else {
-				panic!();
-			}
-		} else if let Variable::Natural(ref mut n) = location {
-			if let Variable::Natural(nn) = literal {
-				*n = nn;
-			} else {
-				panic!();
-			}
-		} else if let Variable::Str(ref mut s) = location {
-			if let Variable::Str(ns) = literal {
-				*s = ns.clone();
-			} else {
-				panic!();
+	if filename.as_deref() == Some("fmt") {
+		let (flags, filenames): (Vec<String>, Vec<String>) = args.partition(|arg| arg == "--check");
+		let check = !flags.is_empty();
+		match format_files(&filenames, check) {
+			Ok(formatted) => std::process::exit(if formatted || !check {0} else {1}),
+			Err(error) => {
+				eprintln!("{}", error);
+				std::process::exit(1);
 			}
 		}
 	}
 
-	fn free(location: String) -> CommandResponse {
-		CommandResponse::Free(location.clone())
-	}
-
-	fn label(name: String) -> CommandResponse {
-		CommandResponse::Label(name.clone())
-	}
-
-	fn jmp(label: Label) -> CommandResponse {
-		CommandResponse::Jump(label)
-	}
-
-	fn jeq(label: Label, o1: Variable, o2: Variable) -> CommandResponse {
-		if o1 == o2 {
-			CommandResponse::Jump(label)
-		} else {
-			CommandResponse::Nothing
+	if filename.as_deref() == Some("disasm") {
+		let mut input = None;
+		for arg in args {
+			match arg.as_str() {
+				"-O" => optimize = true,
+				_ => input = Some(arg)
+			}
 		}
-	}
-
-	fn jgt(label: Label, o1: Variable, o2: Variable) -> CommandResponse {
-		if o1 > o2 {
-			CommandResponse::Jump(label)
-		} else {
-			CommandResponse::Nothing
+		if let Err(error) = disassemble_file(&input.expect("disasm needs a file"), optimize) {
+			eprintln!("{}", error);
+			std::process::exit(1);
 		}
+		return;
 	}
 
-	fn jlt(label: Label, o1: Variable, o2: Variable) -> CommandResponse {
-		if o1 < o2 {
-			CommandResponse::Jump(label)
-		} else {
-			CommandResponse::Nothing
+	let mut program = if resume.is_some() {
+		// the snapshot has its own program and arguments, so others can't be given
+		if let Some(filename) = filename {
+			eprintln!("--resume carries on from a snapshot, so {} can't be run as well", filename);
+			std::process::exit(1);
 		}
-	}
-
-	fn jne(label: Label, o1: Variable, o2: Variable) -> CommandResponse {
-		if o1 != o2 {
-			CommandResponse::Jump(label)
+		Program::new(String::new())
+	} else if let Some(filename) = filename {
+		let loaded = if filename.ends_with(".bbc") {
+			read_bytecode(&filename).map(Program::from_bytecode)
 		} else {
-			CommandResponse::Nothing
-		}
-	}
-
-	fn print(string: String) {
-		print!("{}", string);
-	}
-
-	fn input(location: &mut Variable) {
-		if let Variable::Str(ref mut s) = location {
-			let reader = std::io::stdin();
-			s.clear();
-			reader.read_line(s);
-		}
-	}
-
-	// TODO convert to match
-	fn convert(location: &mut Variable, variable: &Variable) {
-		if let Variable::Bool(ref mut b) = location {
-			if let Variable::Bool(b2) = variable {
-				*b = *b2;
-			} else if let Variable::Char(c) = variable {
-				if *c == 'f' || *c == 'F' {
-					*b = false;
-				} else {
-					*b = true;
-				}
-			} else if let Variable::Float(f) = variable {
-				if *f == 0.0 {
-					*b = false;
-				} else {
-					*b = true;
-				}
-			} else if let Variable::Int(i) = variable {
-				if *i == 0 {
-					*b = false;
-				} else {
-					*b = true;
-				}
-			} else if let Variable::List(l) = variable {
-				if l.is_empty() {
-					*b = false;
-				} else {
-					*b = true;
-				}
-			} else if let Variable::Natural(n) = variable {
-				if *n == 0 {
-					*b = false;
-				} else {
-					*b = true;
-				}
-			} else if let Variable::Str(s) = variable {
-				if s.is_empty() {
-					*b = false;
-				} else {
-					*b = true;
-				}
-			}
-		} else if let Variable::Char(ref mut c) = location {
-			if let Variable::Bool(b) = variable {
-				if *b {
-					*c = 't';
-				} else {
-					*c = 'f';
-				}
-			} else if let Variable::Char(oc) = variable {
-				*c = *oc;
-			} else {
-				panic!();
-			}
-		} else if let Variable::Float(ref mut f) = location {
-			if let Variable::Bool(b) = variable {
-				if *b {
-					*f = 1.0;
+			std::fs::read_to_string(&filename).map_err(|error| format!("couldn't read {}: {}", filename, error))
+				.and_then(|file| if vm || optimize {
+					compile_cached(file, optimize, cache).map(Program::from_bytecode).map_err(|error| error.to_string())
 				} else {
-					*f = 0.0;
-				}
-			} else if let Variable::Float(of2) = variable {
-				*f = *of2;
-			} else if let Variable::Int(i) = variable {
-				*f = *i as f32;
-			} else if let Variable::Natural(n) = variable {
-				*f = *n as f32;
-			} else if let Variable::Str(s) = variable {
-				*f = s.parse().unwrap();
-			} else {
-				panic!()
-			}
-		} else if let Variable::Int(ref mut i) = location {
-			match variable {
-				Variable::Bool(b) => *i = if *b {1} else {0},
-				Variable::Float(f) => *i = f.round() as i32,
-				Variable::Int(i2) => *i = *i2,
-				Variable::Natural(n) => *i = *n as i32,
-				Variable::Str(s) => *i = s.parse().unwrap(),
-				_ => panic!()
-			}
-		} else if let Variable::List(ref mut l) = location {
-			match variable {
-				Variable::List(l2) => *l = l2.clone(),
-				Variable::Str(s) => {
-					l.clear();
-					for character in s.chars() {
-						l.push(Variable::Char(character))
-					}
-				},
-				_ => *l = vec![variable.clone()]
-			}
-		} else if let Variable::Natural(ref mut n) = location {
-			match variable {
-				Variable::Float(f) => *n = f.round().abs() as u32,
-				Variable::Int(i) => *n = i.abs() as u32,
-				Variable::Natural(n2) => *n = *n2,
-				Variable::Bool(b) => *n = if *b {1} else {0},
-				Variable::Str(s) => *n = s.parse().unwrap(),
-				_ => panic!()
+					Ok(Program::new(file))
+				})
+		};
+		match loaded {
+			Ok(program) => program.with_args(args.collect()),
+			Err(error) => {
+				eprintln!("{}", error);
+				std::process::exit(1);
 			}
-		} else if let Variable::Str(ref mut s) = location {
-			*s = format!("{}", variable);
-		}
-	}
-
-	fn slice(location: &mut Variable, list: Vec<Variable>, start: u32, end: u32) {
-		if let Variable::List(ref mut l) = location {
-			*l = list[start as usize..end as usize].iter().map(|v| v.clone()).collect();
-		} else {panic!()}
-	}
-
-	fn index(location: &mut Variable, list: Vec<Variable>, index: u32) {
-		let value = list[index as usize].clone();
-		Self::set(location, value);
-	}
-
-	fn len(location: &mut Variable, list: Vec<Variable>) {
-		match location {
-			Variable::Float(ref mut f) => *f = list.len() as f32,
-			Variable::Int(ref mut i) => *i = list.len() as i32,
-			Variable::Natural(ref mut n) => *n = list.len() as u32,
-			_ => panic!()
-		}
-	}
-
-	fn insert(location: &mut Vec<Variable>, index: u32, item: Variable) {
-		if index as usize == location.len() {
-			location.push(item);
-		} else {
-			location.insert(index as usize, item);
-		}
-	}
-}
-
-#[derive(Clone)]
-struct Program {
-	program: String,
-	vars: HashMap<String, Variable>,
-	labels: HashMap<String, Label>,
-	current_line: usize
-}
-
-impl Program {
-
-	pub fn new(program: String) -> Self {
-		Program {
-			program,
-			vars: HashMap::new(),
-			labels: HashMap::new(),
-			current_line: 0
-		}
-	}
-
-	fn parse_literal(&self, literal: String) -> Variable {
-		let literal = literal.trim().to_string();
-		if self.vars.contains_key(&literal) {
-			self.vars.get(&literal).unwrap().clone()
-		} else if literal.starts_with('-') {
-			Variable::Int(literal.parse().unwrap())
-		} else if literal.starts_with('+') {
-			Variable::Int(literal.split_at(1).1.parse().unwrap())
-		} else if literal.starts_with('\"') {
-			Variable::Str(literal.split_at(1).1.to_string())
-		} else if literal == "TRUE" {
-			Variable::Bool(true)
-		} else if literal == "FALSE" {
-			Variable::Bool(false)
-		} else if literal.starts_with('[') {
-			let items : Vec<String> = literal.split_terminator(',').map(|s| s.to_string()).collect();
-			Variable::List(items.iter().map(|i| self.parse_literal(i.to_string())).collect())
-		} else if literal.starts_with('\'') {
-			Variable::Char(literal.chars().nth(2).unwrap())
-		} else {
-			Variable::Natural(literal.parse().unwrap())
 		}
+	} else {
+		println!("Please give a filename");
+		return;
+	};
+	program = program.with_permissions(permissions).with_limits(limits);
+	if let Some(seed) = seed {
+		program = program.with_seed(seed);
 	}
-
-	fn get_mut_var(&mut self, name: String) -> &mut Variable {
-		self.vars.get_mut(&name).unwrap()
-	}
-
-	fn get_var(&self, name: String) -> Variable {
-		self.vars.get(&name).unwrap().clone()
-	}
-
-	fn get_num_var(&self, name: String) -> Number {
-		Number::from_var(self.get_var(name).clone())
-	}
-
-	fn get_nat_var(&self, name: String) -> u32 {
-		self.get_num_var(name).to_float().round().abs() as u32
-	}
-
-	fn get_float_var(&self, name: String) -> f32 {
-		self.get_num_var(name).to_float()
-	}
-
-	fn get_bool_var(&self, name: String) -> bool {
-		let var = self.get_var(name);
-		if let Variable::Bool(b) = var {
-			b
-		} else {
-			panic!()
-		}
+	if let Some(unix_millis) = fake_clock {
+		program = program.with_fake_clock(unix_millis);
 	}
 
-	fn get_str_var(&self, name: String) -> String {
-		let var = self.get_var(name);
-		if let Variable::Str(string) = var {
-			string.to_string()
-		} else {
-			panic!()
+	let result = if let Some(path) = resume {
+		let restored = std::fs::read(&path).map_err(|error| error.to_string())
+			.and_then(|snapshot| program.restore(&snapshot).map_err(|error| error.to_string()));
+		if let Err(error) = restored {
+			eprintln!("couldn't resume from {}: {}", path, error);
+			std::process::exit(1);
 		}
-	}
-
-	fn get_list_var(&self, name: String) -> Vec<Variable> {
-		let var = self.get_var(name);
-		if let Variable::List(l) = var {
-			l.clone()
-		} else {
-			panic!()
-		}
-	}
-
-	fn get_label(&self, name: String) -> Label {
-		self.labels.get(&name).unwrap().clone()
-	}
-
-	fn run_line(&mut self, line: String, command_parameter_num_map: &HashMap<String, u8>) {
-		let command = UnparsedCommand::from_line(line, command_parameter_num_map);
-		if let Some(command) = command {
-			match match command.command_name.as_str() {
-				"ADD" => {
-					let var1 = self.get_var(command.parameters[1].clone());
-					let var2 = self.get_var(command.parameters[2].clone());
-					Command::Add(self.get_mut_var(command.parameters[0].clone()), var1, var2).run()
-				},
-				"SUB" => {
-					let var1 = self.get_num_var(command.parameters[1].clone());
-					let var2 = self.get_num_var(command.parameters[2].clone());
-					Command::Sub(self.get_mut_var(command.parameters[0].clone()), var1, var2).run()
-				},
-				"MUL" => {
-					let var1 = self.get_num_var(command.parameters[1].clone());
-					let var2 = self.get_num_var(command.parameters[2].clone());
-					Command::Mul(self.get_mut_var(command.parameters[0].clone()), var1, var2).run()
-				},
-				"DIV" => {
-					let var1 = self.get_num_var(command.parameters[1].clone());
-					let var2 = self.get_num_var(command.parameters[2].clone());
-					Command::Div(self.get_mut_var(command.parameters[0].clone()), var1, var2).run()
-				},
-				"MOD" => {
-					let var1 = self.get_num_var(command.parameters[1].clone());
-					let var2 = self.get_num_var(command.parameters[2].clone());
-					Command::Mod(self.get_mut_var(command.parameters[0].clone()), var1, var2).run()
-				},
-				"ROUND" => {
-					let var = self.get_float_var(command.parameters[1].clone());
-					Command::Round(self.get_mut_var(command.parameters[0].clone()), var).run()
-				},
-				"FLOOR" => {
-					let var = self.get_float_var(command.parameters[1].clone());
-					Command::Floor(self.get_mut_var(command.parameters[0].clone()), var).run()
-				},
-				"CEIL" => {
-					let var = self.get_float_var(command.parameters[1].clone());
-					Command::Ceil(self.get_mut_var(command.parameters[0].clone()), var).run()
-				},
-				"AND" => {
-					let var1 = self.get_bool_var(command.parameters[1].clone());
-					let var2 = self.get_bool_var(command.parameters[2].clone());
-					Command::And(self.get_mut_var(command.parameters[0].clone()), var1, var2).run()
-				},
-				"OR" => {
-					let var1 = self.get_bool_var(command.parameters[1].clone());
-					let var2 = self.get_bool_var(command.parameters[2].clone());
-					Command::Or(self.get_mut_var(command.parameters[0].clone()), var1, var2).run()
-				},
-				"XOR" => {
-					let var1 = self.get_bool_var(command.parameters[1].clone());
-					let var2 = self.get_bool_var(command.parameters[2].clone());
-					Command::Xor(self.get_mut_var(command.parameters[0].clone()), var1, var2).run()
-				},
-				"NOT" => {
-					let var = self.get_bool_var(command.parameters[1].clone());
-					Command::Not(self.get_mut_var(command.parameters[0].clone()), var).run()
-				},
-				"DECL" => {
-					Command::Label(command.parameters[0].clone()).run()
-				},
-				"SET" => {
-					let literal = self.parse_literal(command.parameters[1].clone());
-					Command::Set(self.get_mut_var(command.parameters[0].clone()), literal).run()
-				},
-				"FREE" => {
-					Command::Free(command.parameters[0].clone()).run()
-				},
-				"LABEL" => Command::Label(command.parameters[0].clone()).run(),
-				"JMP" => Command::Jmp(self.get_label(command.parameters[0].clone())).run(),
-				"JEQ" => Command::Jeq(self.get_label(command.parameters[0].clone()), self.get_var(command.parameters[1].clone()), self.get_var(command.parameters[2].clone())).run(),
-				"JNE" => Command::Jne(self.get_label(command.parameters[0].clone()), self.get_var(command.parameters[1].clone()), self.get_var(command.parameters[2].clone())).run(),
-				"JGT" => Command::Jgt(self.get_label(command.parameters[0].clone()), self.get_var(command.parameters[1].clone()), self.get_var(command.parameters[2].clone())).run(),
-				"JLT" => Command::Jeq(self.get_label(command.parameters[0].clone()), self.get_var(command.parameters[1].clone()), self.get_var(command.parameters[2].clone())).run(),
-				"PRINT" => Command::Print(self.get_str_var(command.parameters[0].clone())).run(),
-				"INPUT" => Command::Input(self.get_mut_var(command.parameters[0].clone())).run(),
-				"CONVERT" => {
-					let var = self.get_var(command.parameters[1].clone());
-					Command::Convert(self.get_mut_var(command.parameters[0].clone()), var).run()
-				},
-				"SLICE" => {
-					let list = self.get_list_var(command.parameters[1].clone());
-					let start = self.get_nat_var(command.parameters[2].clone());
-					let end = self.get_nat_var(command.parameters[3].clone());
-					Command::Slice(self.get_mut_var(command.parameters[0].clone()), list, start, end).run()
-				},
-				"INDEX" => {
-					let list = self.get_list_var(command.parameters[1].clone());
-					let index = self.get_nat_var(command.parameters[2].clone());
-					Command::Index(self.get_mut_var(command.parameters[0].clone()), list, index).run()
-				},
-				"LEN" => {
-					let list = self.get_list_var(command.parameters[1].clone());
-					Command::Len(self.get_mut_var(command.parameters[0].clone()), list).run()
-				}
-				"INSERT" => {
-					let index = self.get_nat_var(command.parameters[1].clone());
-					let item = self.get_var(command.parameters[2].clone());
-					let list: &mut Vec<Variable> = if let Variable::List(ref mut l) = self.get_mut_var(command.parameters[0].clone()) {
-						l
-					} else {panic!()};
-					Command::Insert(list, index, item).run()
-				}
-				_ => panic!()
-			} {
-				CommandResponse::Declare(s, t) => {self.vars.insert(s, match t {
-						VarType::Boolean => Variable::Bool(false),
-						VarType::Character => Variable::Char('\0'),
-						VarType::Float => Variable::Float(0.0),
-						VarType::Integer => Variable::Int(0),
-						VarType::List => Variable::List(vec![]),
-						VarType::Natural => Variable::Natural(0),
-						VarType::Str => Variable::Str(String::new())
-					});},
-				CommandResponse::Free(s) => {self.vars.remove(&s).unwrap();},
-				CommandResponse::Jump(label) => {self.current_line = label.0;},
-				CommandResponse::Label(name) => {self.labels.insert(name, Label(self.current_line));},
-				CommandResponse::Nothing => ()
-			}
+		program.resume()
+	} else {
+		program.run_program()
+	};
+	if let Some(path) = save_on_exit {
+		if let Err(error) = std::fs::write(&path, program.snapshot()) {
+			eprintln!("couldn't save to {}: {}", path, error);
 		}
 	}
-
-	pub fn run_program(&mut self) {
-		let file = self.program.clone();
-		let lines : Vec<&str> = file.lines().collect();
-		let command_parameter_num_map = command_parameter_num_map();
-		self.current_line = 0;
-		self.labels = HashMap::new();
-		self.vars = HashMap::new();
-		while self.current_line < lines.len() {
-			self.run_line((**lines.get(self.current_line).unwrap()).to_string(), &command_parameter_num_map);
-			self.current_line += 1;
+	match result {
+		Ok(code) => std::process::exit(code),
+		Err(error) => {
+			eprintln!("{}", error);
+			std::process::exit(1);
 		}
 	}
 }
-
-fn command_parameter_num_map() -> HashMap<String, u8> {
-	let mut map = HashMap::new();
-	map.insert("ADD".to_string(), 3);
-	map.insert("SUB".to_string(), 3);
-	map.insert("MUL".to_string(), 3);
-	map.insert("DIV".to_string(), 3);
-	map.insert("MOD".to_string(), 3);
-	map.insert("ROUND".to_string(), 2);
-	map.insert("FLOOR".to_string(), 2);
-	map.insert("CEIL".to_string(), 2);
-	map.insert("AND".to_string(), 3);
-	map.insert("OR".to_string(), 3);
-	map.insert("XOR".to_string(), 3);
-	map.insert("NOT".to_string(), 2);
-	map.insert("DECL".to_string(), 2);
-	map.insert("SET".to_string(), 2);
-	map.insert("FREE".to_string(), 1);
-	map.insert("LABEL".to_string(), 1);
-	map.insert("JMP".to_string(), 1);
-	map.insert("JEQ".to_string(), 3);
-	map.insert("JGT".to_string(), 3);
-	map.insert("JLT".to_string(), 3);
-	map.insert("JGT".to_string(), 3);
-	map.insert("JNE".to_string(), 3);
-	map.insert("PRINT".to_string(), 1);
-	map.insert("INPUT".to_string(), 1);
-	map.insert("CONVERT".to_string(), 2);
-	map.insert("SLICE".to_string(), 4);
-	map.insert("INDEX".to_string(), 3);
-	map.insert("LEN".to_string(), 2);
-	map
-}
-
-fn main() {
-	if let Some(filename) = std::env::args().nth(1) {
-		let file = std::fs::read_to_string(filename).unwrap();
-		Program::new(file).run_program();
-	} else {
-		println!("Please give a filename");
-	}
-}
@@ -0,0 +1,281 @@
+//! An optional pass over compiled bytecode, which works out what it can before the program runs.
+//!
+//! * Constant propagation: while it's known what's in a variable, loading it pushes the value
+//!   instead, and commands that only compute a value from known ones are run ahead of time.
+//! * Dead stores: a computed value that's replaced before anything could see it isn't stored.
+//! * Jump threading: a jump to a label that only jumps on to another label goes straight there,
+//!   unless that would skip leaving a `TRY` block.
+//! * Unreachable code: lines that can't be run, like those after a `JMP` and before the next
+//!   label, lose their instructions.
+//!
+//! Every line stays where it is, so errors are reported on the same lines. Commands the host has
+//! added or put in place of built-in ones are left alone, and programs may run fewer lines once it's done.
+
+use crate::bytecode::{Bytecode, Op};
+use crate::{CommandResponse, Param, Program, Registry, VarType, Variable};
+use std::collections::{HashMap, HashSet};
+
+/// Commands which only compute their locations from their parameters, so they can be run ahead of time
+const FOLDABLE: &[&str] = &[
+	"ADD", "SUB", "MUL", "DIV", "MOD", "ROUND", "FLOOR", "CEIL",
+	"SQRT", "POW", "ABS", "MIN", "MAX", "SIN", "COS", "TAN", "ATAN2", "LOG", "EXP", "CLAMP", "SIGN",
+	"AND", "OR", "XOR", "NOT", "BAND", "BOR", "BXOR", "BNOT", "SHL", "SHR",
+	"EQ", "NE", "LT", "GT", "LE", "GE", "SET", "CONVERT", "SLICE", "INDEX", "LEN", "INSERT", "SORT"
+];
+
+/// Commands which don't change any variables other than their locations
+const LOCAL: &[&str] = &[
+	"PRINT", "INPUT", "LABEL", "JMP", "JEQ", "JNE", "JGT", "JLT", "JGE", "JLE", "JIF", "JIFNOT", "JMPVAR",
+	"TRY", "ENDTRY", "ASSERT", "ASSERTEQ", "RANDOM", "SHUFFLE", "CHOOSE", "SEED",
+	"NOW", "CLOCK", "SLEEP", "FORMATDATE", "PARSEDATE", "GETENV"
+];
+
+/// Commands which jump to the label in their first parameter
+const JUMPS: &[&str] = &["JMP", "JEQ", "JNE", "JGT", "JLT", "JGE", "JLE", "JIF", "JIFNOT", "CALL"];
+
+/// Commands after which the next line isn't run
+const TERMINATORS: &[&str] = &["JMP", "RETURN", "EXIT"];
+
+/// Variables the program declares without a `DECL`
+const IMPLICIT: &[&str] = &["PI", "E", "ARGS", "ERR", "ERRLINE", "ITEM", "ACC", "RESULT", "A", "B"];
+
+impl Bytecode {
+	pub(crate) fn optimize(&mut self, registry: &Registry) {
+		let mut lines: Vec<Vec<Op>> = self.line_starts.windows(2).map(|range| self.ops[range[0]..range[1]].to_vec()).collect();
+		// whether each command is the built-in one, since nothing can be assumed about the host's
+		let builtin: Vec<bool> = self.commands.iter().map(|(name, _)| registry.get(name).is_some_and(|spec| spec.builtin)).collect();
+		self.propagate_constants(&mut lines, &builtin);
+		self.thread_jumps(&mut lines, &builtin);
+		self.remove_unreachable(&mut lines, &builtin);
+
+		self.ops = Vec::new();
+		self.line_starts = Vec::new();
+		for line in lines {
+			self.line_starts.push(self.ops.len());
+			self.ops.extend(line);
+		}
+		self.line_starts.push(self.ops.len());
+	}
+
+	/// The built-in command a line runs, which is followed only by the stores to its locations.
+	/// Lines that run a command from the host don't have one
+	fn command_name(&self, line: &[Op], builtin: &[bool]) -> Option<&str> {
+		line.iter().rev().find_map(|op| match op {
+			Op::Invoke(command, _) => Some(Some(self.commands[*command].0.as_str()).filter(|_| builtin[*command])),
+			_ => None
+		}).flatten()
+	}
+
+	/// The name in a line's first instruction, if it's a constant str, like the name given to `DECL` or `JMP`
+	fn first_name(&self, line: &[Op]) -> Option<&str> {
+		match line.first() {
+			Some(Op::Const(constant) | Op::Label(constant)) => match &self.constants[*constant] {
+				Variable::Str(name) => Some(name),
+				_ => None
+			},
+			_ => None
+		}
+	}
+
+	/// Lines which can be jumped to, after which the program carries on from the next line.
+	/// Running a `LABEL` line moves its label there, so those count even if it's defined again later
+	fn landings(&self, lines: &[Vec<Op>], builtin: &[bool]) -> HashSet<usize> {
+		let mut landings: HashSet<usize> = self.labels.values().copied().collect();
+		landings.extend((0..lines.len()).filter(|line| self.command_name(&lines[*line], builtin) == Some("LABEL")));
+		for blocks in [&self.catch_blocks, &self.test_blocks] {
+			landings.extend(blocks.keys().chain(blocks.values()));
+		}
+		landings
+	}
+
+	/// Follows what's known to be in each variable through each run of lines that can only be
+	/// entered from the top, replacing loads with constants and running foldable commands
+	fn propagate_constants(&mut self, lines: &mut [Vec<Op>], builtin: &[bool]) {
+		let landings = self.landings(lines, builtin);
+		// a literal only loads a variable if there's one by that name, which needs to be declared
+		// somewhere, and a command from the host might declare the one it's given
+		let mut declarable: HashSet<String> = IMPLICIT.iter().map(|name| name.to_string()).collect();
+		for line in lines.iter() {
+			if let (Some("DECL" | "CATCH") | None, Some(name)) = (self.command_name(line, builtin), self.first_name(line)) {
+				declarable.insert(name.to_string());
+			}
+		}
+		let builtins = Registry::builtins();
+		let mut scratch = Program::new(String::new());
+
+		let mut known: HashMap<usize, Variable> = HashMap::new();
+		// the line of each folded store that nothing could have seen yet
+		let mut unseen: HashMap<usize, usize> = HashMap::new();
+		for line in 0..lines.len() {
+			if line > 0 && landings.contains(&(line - 1)) {
+				known.clear();
+				unseen.clear();
+			}
+			if lines[line].is_empty() {
+				continue;
+			}
+			let name = self.command_name(&lines[line], builtin).map(str::to_string);
+			let mut args = Some(Vec::new());
+			let mut ops = Vec::new();
+			for op in lines[line].clone() {
+				let value = match op {
+					Op::Load(slot, param) => known.get(&slot).cloned().map(|value| (value, param)),
+					Op::LoadOr(slot, constant, param) => match known.get(&slot) {
+						Some(value) => Some((value.clone(), param)),
+						None if !declarable.contains(&self.slots[slot]) => Some((self.constants[constant].clone(), param)),
+						None => None
+					},
+					Op::Const(constant) => Some((self.constants[constant].clone(), Param::Word)),
+					_ => None
+				};
+				match value.and_then(|(value, param)| param.check(value).ok()) {
+					Some(value) => {
+						if let Some(args) = &mut args {
+							args.push(value.clone());
+						}
+						ops.push(if let Op::Const(_) = op {op} else {Op::Const(self.constant(value))});
+					},
+					None => {
+						if !matches!(op, Op::Invoke(..) | Op::Store(_)) {
+							args = None;
+						}
+						ops.push(op);
+					}
+				}
+			}
+
+			let foldable = name.as_deref().is_some_and(|name| FOLDABLE.contains(&name));
+			let folded = args.filter(|_| foldable).and_then(|args| self.fold(name.as_deref().unwrap(), args, &builtins, &mut scratch));
+			let stores: Vec<usize> = lines[line].iter().filter_map(|op| match op {Op::Store(slot) => Some(*slot), _ => None}).collect();
+			match folded {
+				Some(values) => {
+					ops = Vec::new();
+					for (slot, value) in stores.iter().zip(values) {
+						if let Some(earlier) = unseen.insert(*slot, line) {
+							// the earlier value is replaced before anything reads it
+							let position = lines[earlier].iter().position(|op| *op == Op::Store(*slot)).unwrap();
+							lines[earlier].drain(position - 1..=position);
+						}
+						ops.push(Op::Const(self.constant(value.clone())));
+						ops.push(Op::Store(*slot));
+						known.insert(*slot, value);
+					}
+				},
+				None => {
+					// the line could fail, and whatever handles it could read anything
+					unseen.clear();
+					for slot in &stores {
+						known.remove(slot);
+					}
+					match (name.as_deref(), self.first_name(&lines[line])) {
+						(Some("DECL"), Some(var)) => {
+							let var_type = match lines[line].get(1) {
+								Some(Op::Const(constant)) => self.constants[*constant].to_str().ok().and_then(|name| VarType::from_name(name).ok()),
+								_ => None
+							};
+							if let Some(slot) = self.slots.iter().position(|slot| slot == var) {
+								match var_type {
+									Some(var_type) => {known.insert(slot, var_type.zero());},
+									None => {known.remove(&slot);}
+								}
+							}
+						},
+						(Some("FREE"), Some(var)) => {
+							if let Some(slot) = self.slots.iter().position(|slot| slot == var) {
+								known.remove(&slot);
+							}
+						},
+						(Some(name), _) if LOCAL.contains(&name) || FOLDABLE.contains(&name) => (),
+						_ => known.clear()
+					}
+				}
+			}
+			lines[line] = ops;
+		}
+	}
+
+	/// Runs a foldable built-in command on known values, giving back the new values of its locations.
+	/// Nothing is folded if the command fails, so that it still fails when the program runs
+	fn fold(&self, name: &str, mut args: Vec<Variable>, builtins: &Registry, scratch: &mut Program) -> Option<Vec<Variable>> {
+		let spec = builtins.get(name)?;
+		match (spec.handler)(scratch, &mut args) {
+			Ok(CommandResponse::Nothing) => Some(spec.signature.params.iter().zip(args)
+				.filter(|(param, _)| matches!(param, Param::Location(_)))
+				.map(|(_, value)| value)
+				.collect()),
+			_ => None
+		}
+	}
+
+	/// If jumping to a label only leads to a `JMP`, the label that jumps to
+	fn jumps_on_to(&self, lines: &[Vec<Op>], label: &str, builtin: &[bool]) -> Option<String> {
+		let mut line = self.labels.get(label)? + 1;
+		while let Some(ops) = lines.get(line) {
+			match self.command_name(ops, builtin) {
+				None if ops.is_empty() => line += 1,
+				Some("LABEL") => line += 1,
+				Some("JMP") => return self.first_name(ops).filter(|label| self.labels.contains_key(*label)).map(str::to_string),
+				_ => return None
+			}
+		}
+		None
+	}
+
+	/// The `TRY` lines of the blocks a label is inside of
+	fn try_blocks_around(&self, label: &str) -> HashSet<usize> {
+		let line = self.labels[label];
+		self.try_blocks.iter().filter(|(start, catch)| (**start..**catch).contains(&line)).map(|(start, _)| *start).collect()
+	}
+
+	fn thread_jumps(&mut self, lines: &mut [Vec<Op>], builtin: &[bool]) {
+		// running a LABEL line again moves its label, so a label on two lines can't be followed ahead of time
+		let mut defined = HashSet::new();
+		for line in lines.iter() {
+			if let (Some("LABEL"), Some(label)) = (self.command_name(line, builtin), self.first_name(line)) {
+				if !defined.insert(label) {
+					return;
+				}
+			}
+		}
+
+		for line in 0..lines.len() {
+			if !self.command_name(&lines[line], builtin).is_some_and(|name| JUMPS.contains(&name)) {
+				continue;
+			}
+			let Some(start) = self.first_name(&lines[line]).map(str::to_string) else {continue};
+			// every jump leaves the TRY blocks it lands outside of, so a label can only be skipped
+			// when it's in the same ones as where the jumps end up
+			let mut label = start.clone();
+			let mut passed = vec![start.clone()];
+			while let Some(next) = self.jumps_on_to(lines, &label, builtin) {
+				if passed.contains(&next) {
+					break;
+				}
+				passed.push(next.clone());
+				label = next;
+			}
+			while passed.len() > 1 && passed.iter().any(|passed| self.try_blocks_around(passed) != self.try_blocks_around(&label)) {
+				passed.pop();
+				label = passed.last().unwrap().clone();
+			}
+			if label != start {
+				lines[line][0] = Op::Label(self.constant(Variable::Str(label)));
+			}
+		}
+	}
+
+	fn remove_unreachable(&self, lines: &mut [Vec<Op>], builtin: &[bool]) {
+		let landings = self.landings(lines, builtin);
+		let mut reachable = true;
+		for (line, ops) in lines.iter_mut().enumerate() {
+			if line > 0 && landings.contains(&(line - 1)) {
+				reachable = true;
+			}
+			if !reachable {
+				ops.clear();
+			} else if self.command_name(ops, builtin).is_some_and(|name| TERMINATORS.contains(&name)) {
+				reachable = false;
+			}
+		}
+	}
+}
@@ -0,0 +1,325 @@
+//! Saving a running program to bytes, and picking it back up later.
+//!
+//! A snapshot starts with `BOTASNAP` and a version number, followed by the source or bytecode
+//! of the program and everything that changes as it runs. Numbers are little-endian, and strs and
+//! lists are prefixed with their length.
+
+use crate::{Bytecode, Clock, Error, Label, Program, TryHandler, Variable};
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+
+const MAGIC: &[u8] = b"BOTASNAP";
+const VERSION: u32 = 2;
+
+#[derive(Default)]
+pub(crate) struct Writer {
+	pub bytes: Vec<u8>
+}
+
+impl Writer {
+	pub fn u8(&mut self, value: u8) {
+		self.bytes.push(value);
+	}
+
+	pub fn u32(&mut self, value: u32) {
+		self.bytes.extend_from_slice(&value.to_le_bytes());
+	}
+
+	pub fn u64(&mut self, value: u64) {
+		self.bytes.extend_from_slice(&value.to_le_bytes());
+	}
+
+	pub fn usize(&mut self, value: usize) {
+		self.u64(value as u64);
+	}
+
+	pub fn str(&mut self, value: &str) {
+		self.usize(value.len());
+		self.bytes.extend_from_slice(value.as_bytes());
+	}
+
+	pub fn variable(&mut self, value: &Variable) {
+		match value {
+			Variable::Natural(n) => {self.u8(0); self.u32(*n);},
+			Variable::Int(i) => {self.u8(1); self.u32(*i as u32);},
+			Variable::Float(f) => {self.u8(2); self.u32(f.to_bits());},
+			Variable::Char(c) => {self.u8(3); self.u32(*c as u32);},
+			Variable::Bool(b) => {self.u8(4); self.u8(*b as u8);},
+			Variable::Str(s) => {self.u8(5); self.str(s);},
+			Variable::List(l) => {
+				self.u8(6);
+				self.usize(l.len());
+				for item in l {
+					self.variable(item);
+				}
+			}
+		}
+	}
+}
+
+pub(crate) struct Reader<'a> {
+	bytes: &'a [u8],
+	position: usize,
+	/// Makes the error for bytes that don't make sense
+	invalid: fn(String) -> Error
+}
+
+impl<'a> Reader<'a> {
+	pub fn new(bytes: &'a [u8], invalid: fn(String) -> Error) -> Self {
+		Reader {bytes, position: 0, invalid}
+	}
+
+	pub fn invalid(&self, message: &str) -> Error {
+		(self.invalid)(message.to_string())
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.position == self.bytes.len()
+	}
+
+	pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+		let end = self.position.checked_add(len).filter(|end| *end <= self.bytes.len())
+			.ok_or_else(|| self.invalid("it ends too early"))?;
+		let bytes = &self.bytes[self.position..end];
+		self.position = end;
+		Ok(bytes)
+	}
+
+	pub fn u8(&mut self) -> Result<u8, Error> {
+		Ok(self.bytes(1)?[0])
+	}
+
+	pub fn u32(&mut self) -> Result<u32, Error> {
+		Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+	}
+
+	pub fn u64(&mut self) -> Result<u64, Error> {
+		Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+	}
+
+	pub fn usize(&mut self) -> Result<usize, Error> {
+		usize::try_from(self.u64()?).map_err(|_| self.invalid("a number is too big"))
+	}
+
+	/// Reads how many items follow, each of which takes at least a byte
+	pub fn count(&mut self) -> Result<usize, Error> {
+		let count = self.usize()?;
+		if count > self.bytes.len() - self.position {
+			return Err(self.invalid("it ends too early"));
+		}
+		Ok(count)
+	}
+
+	pub fn str(&mut self) -> Result<String, Error> {
+		let len = self.usize()?;
+		String::from_utf8(self.bytes(len)?.to_vec()).map_err(|_| self.invalid("a str isn't UTF-8"))
+	}
+
+	pub fn variable(&mut self) -> Result<Variable, Error> {
+		Ok(match self.u8()? {
+			0 => Variable::Natural(self.u32()?),
+			1 => Variable::Int(self.u32()? as i32),
+			2 => Variable::Float(f32::from_bits(self.u32()?)),
+			3 => Variable::Char(char::from_u32(self.u32()?).ok_or_else(|| self.invalid("a char isn't valid"))?),
+			4 => Variable::Bool(self.u8()? != 0),
+			5 => Variable::Str(self.str()?),
+			6 => {
+				let len = self.count()?;
+				let mut list = Vec::new();
+				for _ in 0..len {
+					list.push(self.variable()?);
+				}
+				Variable::List(list)
+			},
+			tag => return Err((self.invalid)(format!("{} isn't a type of variable", tag)))
+		})
+	}
+
+	pub fn option<T>(&mut self, read: impl FnOnce(&mut Self) -> Result<T, Error>) -> Result<Option<T>, Error> {
+		match self.u8()? {
+			0 => Ok(None),
+			_ => read(self).map(Some)
+		}
+	}
+}
+
+impl Program {
+	/// Saves the program as it is now, so that it can be picked up later with [`Program::restore`].
+	/// The host's commands, `Io`, limits and permissions aren't saved
+	pub fn snapshot(&self) -> Vec<u8> {
+		let mut writer = Writer::default();
+		writer.bytes.extend_from_slice(MAGIC);
+		writer.u32(VERSION);
+		match &self.bytecode {
+			Some(bytecode) => {
+				let bytes = bytecode.to_bytes();
+				writer.u8(1);
+				writer.usize(bytes.len());
+				writer.bytes.extend_from_slice(&bytes);
+			},
+			None => {writer.u8(0); writer.str(&self.program);}
+		}
+
+		writer.usize(self.args.len());
+		for arg in &self.args {
+			writer.str(arg);
+		}
+		writer.usize(self.current_line);
+		writer.usize(self.call_stack.len());
+		for line in &self.call_stack {
+			writer.usize(*line);
+		}
+
+		// sorted, so that the same state always gives the same snapshot
+		let mut vars: Vec<(&String, &Variable)> = self.vars.iter().collect();
+		vars.sort_by_key(|(name, _)| *name);
+		writer.usize(vars.len());
+		for (name, value) in vars {
+			writer.str(name);
+			writer.variable(value);
+		}
+		let mut labels: Vec<(&String, &Label)> = self.labels.iter().collect();
+		labels.sort_by_key(|(name, _)| *name);
+		writer.usize(labels.len());
+		for (name, label) in labels {
+			writer.str(name);
+			writer.usize(label.0);
+		}
+
+		writer.usize(self.try_handlers.len());
+		for handler in &self.try_handlers {
+			writer.usize(handler.catch_line);
+			writer.usize(handler.call_depth);
+		}
+		match &self.on_error {
+			Some((label, depth)) => {writer.u8(1); writer.usize(label.0); writer.usize(*depth);},
+			None => writer.u8(0)
+		}
+		match self.exit_code {
+			Some(code) => {writer.u8(1); writer.u32(code as u32);},
+			None => writer.u8(0)
+		}
+
+		match self.seed {
+			Some(seed) => {writer.u8(1); writer.u64(seed);},
+			None => writer.u8(0)
+		}
+		writer.u64(self.rng.state);
+		match &self.clock {
+			Clock::System(start) => {writer.u8(0); writer.u64(start.elapsed().as_millis() as u64);},
+			Clock::Fake {unix_millis, elapsed_millis} => {writer.u8(1); writer.u64(*unix_millis); writer.u64(*elapsed_millis);}
+		}
+		writer.bytes
+	}
+
+	/// Picks up a program saved with [`Program::snapshot`], replacing this one's source and state.
+	/// Afterwards, [`Program::step`] and [`Program::resume`] carry on from where it was saved.
+	/// If the snapshot isn't valid, this program is left as it was
+	pub fn restore(&mut self, snapshot: &[u8]) -> Result<(), Error> {
+		let mut reader = Reader::new(snapshot, Error::InvalidSnapshot);
+		if reader.bytes(MAGIC.len()).ok() != Some(MAGIC) {
+			return Err(Error::InvalidSnapshot("it isn't a snapshot".to_string()));
+		}
+		let version = reader.u32()?;
+		if version != VERSION {
+			return Err(Error::InvalidSnapshot(format!("version {} isn't supported", version)));
+		}
+		// the saved program is loaded on its own, so that nothing here changes until the whole snapshot has been checked
+		let mut loaded = if reader.u8()? == 0 {
+			Program::new(reader.str()?)
+		} else {
+			let len = reader.count()?;
+			let mut loaded = Program::new(String::new());
+			loaded.bytecode = Some(Bytecode::from_bytes(reader.bytes(len)?)?);
+			loaded
+		};
+		loaded.commands = self.commands.clone();
+		loaded.vm = self.vm;
+		loaded.optimize = self.optimize;
+		loaded.load().map_err(|error| error.error)?;
+
+		let len = reader.count()?;
+		let args = (0..len).map(|_| reader.str()).collect::<Result<Vec<_>, _>>()?;
+		let current_line = reader.usize()?;
+		let len = reader.count()?;
+		let call_stack = (0..len).map(|_| reader.usize()).collect::<Result<Vec<_>, _>>()?;
+
+		let len = reader.count()?;
+		let mut vars = Vec::new();
+		for _ in 0..len {
+			vars.push((reader.str()?, reader.variable()?));
+		}
+		let len = reader.count()?;
+		let mut labels = HashMap::new();
+		for _ in 0..len {
+			let name = reader.str()?;
+			labels.insert(name, Label(reader.usize()?));
+		}
+
+		let len = reader.count()?;
+		let mut try_handlers = Vec::new();
+		for _ in 0..len {
+			let catch_line = reader.usize()?;
+			// the TRY line isn't saved, since it's the one with this CATCH
+			let try_line = loaded.try_blocks.iter().find(|(_, catch)| **catch == catch_line).map_or(usize::MAX, |(line, _)| *line);
+			try_handlers.push(TryHandler {try_line, catch_line, call_depth: reader.usize()?});
+		}
+		let on_error = reader.option(|reader| Ok((Label(reader.usize()?), reader.usize()?)))?;
+		let exit_code = reader.option(|reader| Ok(reader.u32()? as i32))?;
+
+		let seed = reader.option(|reader| reader.u64())?;
+		let rng_state = reader.u64()?;
+		let clock = match reader.u8()? {
+			0 => {
+				let elapsed = std::time::Duration::from_millis(reader.u64()?);
+				let now = std::time::Instant::now();
+				Clock::System(now.checked_sub(elapsed).unwrap_or(now))
+			},
+			_ => Clock::Fake {unix_millis: reader.u64()?, elapsed_millis: reader.u64()?}
+		};
+		if !reader.is_empty() {
+			return Err(Error::InvalidSnapshot("there's more after the end".to_string()));
+		}
+
+		let lines = loaded.line_count();
+		let in_program = |line: usize| line < lines;
+		let valid = current_line <= lines
+			&& call_stack.iter().all(|line| in_program(*line))
+			&& labels.values().all(|label| in_program(label.0))
+			&& try_handlers.iter().all(|handler| loaded.catch_blocks.contains_key(&handler.catch_line))
+			&& on_error.as_ref().is_none_or(|(label, _)| in_program(label.0));
+		if !valid {
+			return Err(Error::InvalidSnapshot("it points outside of the program".to_string()));
+		}
+
+		self.program = loaded.program;
+		self.bytecode = loaded.bytecode;
+		self.lines = loaded.lines;
+		self.compiled = loaded.compiled;
+		self.try_blocks = loaded.try_blocks;
+		self.catch_blocks = loaded.catch_blocks;
+		self.test_blocks = loaded.test_blocks;
+		self.block_names = loaded.block_names;
+		self.vars = loaded.vars;
+		self.vars.clear();
+		for (name, value) in vars {
+			self.vars.insert(&name, value);
+		}
+		self.testing = false;
+		self.args = args;
+		self.current_line = current_line;
+		self.call_stack = call_stack;
+		self.labels = labels;
+		self.try_handlers = try_handlers;
+		self.on_error = on_error;
+		self.fault_line = None;
+		self.exit_code = exit_code;
+		self.seed = seed;
+		self.rng.state = rng_state;
+		self.clock = clock;
+		self.outcome = None;
+		self.reset_usage();
+		self.started = true;
+		Ok(())
+	}
+}
@@ -0,0 +1,99 @@
+//! Finding the variables and labels named in a program's source, for editors.
+
+use crate::check::{Status, BUILT_IN_VARS, ROUTINE_VARS};
+use crate::{word_spans, Param, Program, VarType};
+use std::collections::HashSet;
+use std::ops::Range;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SymbolKind {
+	Variable,
+	Label
+}
+
+/// A variable or label named somewhere in the source
+#[derive(Clone, Debug)]
+pub struct Symbol {
+	pub name: String,
+	pub kind: SymbolKind,
+	pub line: usize,
+	/// Where the name is on its line, in bytes
+	pub span: Range<usize>,
+	/// Whether the line declares the variable or defines the label, rather than using it
+	pub definition: bool
+}
+
+impl Program {
+	/// Every variable and label named in the program's source, in order
+	pub fn symbols(&self) -> Vec<Symbol> {
+		let lines: Vec<&str> = self.program.lines().collect();
+		// a literal only names a variable if there's one by that name
+		let mut variables: HashSet<&str> = BUILT_IN_VARS.iter()
+			.chain(ROUTINE_VARS.iter().flat_map(|(_, vars)| vars.iter()))
+			.map(|(name, _)| *name)
+			.collect();
+		for line in &lines {
+			let spans = word_spans(line);
+			if let [command, name, ..] = &spans[..] {
+				if matches!(line[command.clone()].to_uppercase().as_str(), "DECL" | "CATCH") {
+					variables.insert(&line[name.clone()]);
+				}
+			}
+		}
+
+		let mut symbols = Vec::new();
+		for (number, line) in lines.iter().enumerate() {
+			let spans = word_spans(line);
+			let Some(spec) = spans.first().and_then(|first| self.commands.get(&line[first.clone()])) else {continue};
+			let command = line[spans[0].clone()].to_uppercase();
+			let symbol = |kind, span: Range<usize>, definition| Symbol {name: line[span.clone()].to_string(), kind, line: number + 1, span, definition};
+			for (index, (param, span)) in spec.signature.params.iter().zip(&spans[1..]).enumerate() {
+				let word = &line[span.clone()];
+				match (param, command.as_str(), index) {
+					(Param::Location(_) | Param::Operand(_), _, _) => symbols.push(symbol(SymbolKind::Variable, span.clone(), false)),
+					(Param::Literal(_), _, _) => {
+						// each item of a list literal can be a variable
+						let mut start = span.start;
+						for item in word.split(',') {
+							let name = item.trim().trim_start_matches('[').trim_end_matches(']').trim();
+							if variables.contains(name) {
+								let name_start = start + item.find(name).unwrap();
+								symbols.push(symbol(SymbolKind::Variable, name_start..name_start + name.len(), false));
+							}
+							start += item.len() + 1;
+						}
+					},
+					(Param::Label, _, _) => symbols.push(symbol(SymbolKind::Label, span.clone(), false)),
+					(Param::Word, "DECL" | "CATCH", 0) => symbols.push(symbol(SymbolKind::Variable, span.clone(), true)),
+					(Param::Word, "FREE", 0) => symbols.push(symbol(SymbolKind::Variable, span.clone(), false)),
+					(Param::Word, "LABEL", 0) => symbols.push(symbol(SymbolKind::Label, span.clone(), true)),
+					(Param::Word, "ON", 2) if word != "0" => symbols.push(symbol(SymbolKind::Label, span.clone(), false)),
+					_ => ()
+				}
+			}
+		}
+		symbols
+	}
+
+	/// The type a variable has on a line, if it's the same however the line is reached. Otherwise
+	/// it's the type the variable is declared with, as long as every `DECL` of it agrees
+	pub fn type_at(&mut self, line: usize, name: &str) -> Option<VarType> {
+		if self.load_source().is_ok() {
+			let flow = self.flow();
+			let states = self.follow(&flow);
+			if let Some(Status::Declared(Some(var_type), _)) = states.get(line.wrapping_sub(1)).and_then(Option::as_ref).map(|state| state.get(name)) {
+				return Some(*var_type);
+			}
+		}
+
+		let mut declared = self.lines.iter().filter_map(|line| {
+			let words: Vec<&str> = line.split_whitespace().collect();
+			match words[..] {
+				[command, var, var_type, ..] if command.eq_ignore_ascii_case("DECL") && var == name => VarType::from_name(var_type.to_string()).ok(),
+				_ => None
+			}
+		});
+		let var_type = declared.next().or_else(|| BUILT_IN_VARS.iter().find(|(var, _)| *var == name).and_then(|(_, var_type)| *var_type))?;
+		declared.all(|other| other == var_type).then_some(var_type)
+	}
+}
@@ -0,0 +1,109 @@
+//! Saves compiled scripts with `bota_basic compile`, runs them, and caches the bytecode of scripts run on the VM.
+
+use bota_basic::{Bytecode, CommandResponse, Error, Kind, Param, Program};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const HELLO: &str = "DECL s str\nSET s \"hello\"\nPRINT s\n";
+
+/// A fresh directory holding the given scripts
+fn script_dir(name: &str, scripts: &[(&str, &str)]) -> PathBuf {
+	let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+	let _ = std::fs::remove_dir_all(&dir);
+	std::fs::create_dir_all(&dir).unwrap();
+	for (filename, source) in scripts {
+		std::fs::write(dir.join(filename), source).unwrap();
+	}
+	dir
+}
+
+/// Runs the interpreter with its cache in `cache`
+fn bota_basic(cache: &Path, args: &[&Path]) -> Output {
+	Command::new(env!("CARGO_BIN_EXE_bota_basic")).env("BOTA_BASIC_CACHE", cache).args(args).output().unwrap()
+}
+
+fn stdout(output: Output) -> String {
+	assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+	String::from_utf8(output.stdout).unwrap()
+}
+
+/// Where the cached bytecode for a script goes
+fn cached_path(cache: &Path, source: &str) -> PathBuf {
+	cache.join(format!("{:016x}-{}.bbc", Bytecode::hash_source(source), env!("CARGO_PKG_VERSION")))
+}
+
+#[test]
+fn compiles_next_to_the_script() {
+	let dir = script_dir("compiles_next_to_the_script", &[("hello.bas", HELLO)]);
+	let cache = dir.join("cache");
+	stdout(bota_basic(&cache, &[Path::new("compile"), &dir.join("hello.bas")]));
+	let bytes = std::fs::read(dir.join("hello.bbc")).unwrap();
+	assert_eq!(Bytecode::from_bytes(&bytes).unwrap().source_hash(), Bytecode::hash_source(HELLO));
+	assert_eq!(stdout(bota_basic(&cache, &[&dir.join("hello.bbc")])), "hello");
+}
+
+#[test]
+fn compiles_to_a_chosen_file() {
+	let dir = script_dir("compiles_to_a_chosen_file", &[("hello.bas", HELLO)]);
+	let cache = dir.join("cache");
+	let output = dir.join("out.bbc");
+	stdout(bota_basic(&cache, &[Path::new("compile"), &dir.join("hello.bas"), Path::new("-o"), &output, Path::new("-O")]));
+	assert!(!dir.join("hello.bbc").exists());
+	assert_eq!(stdout(bota_basic(&cache, &[&output])), "hello");
+}
+
+#[test]
+fn compile_errors_are_reported() {
+	let dir = script_dir("compile_errors_are_reported", &[("broken.bas", "TRY\n")]);
+	let output = bota_basic(&dir.join("cache"), &[Path::new("compile"), &dir.join("broken.bas")]);
+	assert_eq!(output.status.code(), Some(1));
+	assert!(!dir.join("broken.bbc").exists());
+}
+
+#[test]
+fn vm_scripts_are_cached() {
+	let dir = script_dir("vm_scripts_are_cached", &[("hello.bas", HELLO)]);
+	let cache = dir.join("cache");
+	assert_eq!(stdout(bota_basic(&cache, &[Path::new("--vm"), &dir.join("hello.bas")])), "hello");
+	let cached = std::fs::read(cached_path(&cache, HELLO)).unwrap();
+	assert_eq!(Bytecode::from_bytes(&cached).unwrap().source_hash(), Bytecode::hash_source(HELLO));
+
+	// bytecode that can't be read is compiled again
+	std::fs::write(cached_path(&cache, HELLO), b"junk").unwrap();
+	assert_eq!(stdout(bota_basic(&cache, &[Path::new("--vm"), &dir.join("hello.bas")])), "hello");
+	assert_eq!(std::fs::read(cached_path(&cache, HELLO)).unwrap(), cached);
+}
+
+#[test]
+fn cached_bytecode_that_does_not_link_is_compiled_again() {
+	let dir = script_dir("cached_bytecode_that_does_not_link_is_compiled_again", &[("hello.bas", HELLO)]);
+	let cache = dir.join("cache");
+	// the same script, compiled with a PRINT that takes something else
+	let stale = Program::new(HELLO.to_string())
+		.with_command("PRINT", vec![Param::Operand(Kind::Any)], |_, _| Ok(CommandResponse::Nothing))
+		.compile()
+		.unwrap();
+	std::fs::create_dir_all(&cache).unwrap();
+	std::fs::write(cached_path(&cache, HELLO), stale.to_bytes()).unwrap();
+
+	assert_eq!(stdout(bota_basic(&cache, &[Path::new("--vm"), &dir.join("hello.bas")])), "hello");
+	assert_ne!(std::fs::read(cached_path(&cache, HELLO)).unwrap(), stale.to_bytes());
+}
+
+#[test]
+fn no_cache_skips_the_cache() {
+	let dir = script_dir("no_cache_skips_the_cache", &[("hello.bas", HELLO)]);
+	let cache = dir.join("cache");
+	assert_eq!(stdout(bota_basic(&cache, &[Path::new("--vm"), Path::new("--no-cache"), &dir.join("hello.bas")])), "hello");
+	assert!(!cache.exists());
+}
+
+#[test]
+fn from_bytes_checks_the_checksum() {
+	let mut bytes = Program::new(HELLO.to_string()).compile().unwrap().to_bytes();
+	*bytes.last_mut().unwrap() ^= 1;
+	match Bytecode::from_bytes(&bytes) {
+		Err(Error::InvalidBytecode(message)) => assert_eq!(message, "the checksum doesn't match"),
+		other => panic!("expected the bytecode to be rejected, but it gave {:?}", other.map(|_| ()).map_err(|error| error.to_string()))
+	}
+}
@@ -0,0 +1,31 @@
+//! Stops with an error, rather than panicking, when the command line doesn't make sense.
+
+use std::process::Command;
+
+/// Runs `bota_basic` with the given arguments, which should be rejected, giving back what it printed
+fn rejected(args: &[&str]) -> String {
+	let output = Command::new(env!("CARGO_BIN_EXE_bota_basic")).args(args).output().unwrap();
+	assert_eq!(output.status.code(), Some(1), "{:?}", args);
+	assert!(output.stdout.is_empty());
+	String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn bad_seeds() {
+	assert_eq!(rejected(&["--seed", "abc", "script.bas"]), "--seed must be a natural number, not abc\n");
+	assert_eq!(rejected(&["--seed", "-1", "script.bas"]), "--seed must be a natural number, not -1\n");
+	assert_eq!(rejected(&["--seed"]), "--seed needs a value\n");
+}
+
+#[test]
+fn bad_fake_clocks() {
+	assert_eq!(rejected(&["--fake-clock", "soon", "script.bas"]), "--fake-clock must be a number of milliseconds, not soon\n");
+	assert_eq!(rejected(&["--fake-clock"]), "--fake-clock needs a value\n");
+}
+
+#[test]
+fn bad_limits() {
+	assert_eq!(rejected(&["--max-instructions", "10k", "script.bas"]), "--max-instructions must be a number of lines, not 10k\n");
+	assert_eq!(rejected(&["--time-limit", "1s", "script.bas"]), "--time-limit must be a number of milliseconds, not 1s\n");
+	assert_eq!(rejected(&["--time-limit"]), "--time-limit needs a value\n");
+}
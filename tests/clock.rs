@@ -0,0 +1,32 @@
+//! Reads the time from a fake clock, which only moves when the program sleeps.
+
+use bota_basic::{Error, MemoryIo, Program};
+
+/// Runs a program on a fake clock, printing the time it puts in `t`
+fn time(source: &str, unix_millis: u64) -> Result<String, Error> {
+	let io = MemoryIo::default();
+	let source = format!("{}DECL out str\nCONVERT out t\nPRINT out\n", source);
+	Program::new(source).with_fake_clock(unix_millis).with_io(io.clone()).run_program().map_err(|error| error.error)?;
+	Ok(io.output())
+}
+
+/// Past `u32::MAX` milliseconds, and past the last second an int can hold
+const LATE: u64 = 3_000_000_000_000;
+
+#[test]
+fn now_fits_in_a_natural() {
+	assert_eq!(time("DECL t natural\nNOW t\n", LATE).unwrap(), "3000000000");
+	assert_eq!(time("DECL t float\nNOW t\n", LATE).unwrap(), "3000000000");
+}
+
+#[test]
+fn now_is_too_large_for_an_int() {
+	assert!(matches!(time("DECL t int\nNOW t\n", LATE), Err(Error::InvalidArgument("the time is too large for an int"))));
+}
+
+#[test]
+fn clock_is_too_large_once_it_has_run_long_enough() {
+	let slept = "DECL t natural\nSLEEP 4294967295\nSLEEP 1\nCLOCK t\n";
+	assert!(matches!(time(slept, LATE), Err(Error::InvalidArgument("the time is too large for a natural"))));
+	assert_eq!(time("DECL t natural\nSLEEP 4294967295\nCLOCK t\n", LATE).unwrap(), "4294967295");
+}
@@ -0,0 +1,220 @@
+//! Runs every script in `tests/conformance` and compares what it does against
+//! the files next to it:
+//!
+//! * `name.out` is the expected stdout
+//! * `name.code` is the expected exit code, or 0 if it's missing
+//! * `name.err` is the expected stderr, which isn't checked if it's missing
+//! * `name.in` is given to the script as stdin
+//! * `name.check` is what `Program::check` finds wrong with the script, which should be nothing if it's missing
+//! * `name.lint` is what `Program::lint` warns about, which isn't checked if it's missing
+//! * `name.fmt` is the script after `Program::format`, which isn't checked if it's missing
+//!
+//! Every script is also run on the bytecode VM, straight from its source, from saved bytecode
+//! and with the optimizer, which should all behave the same as the interpreter. So should every
+//! script once it's been formatted.
+//! Scripts run with a fixed seed and a fake clock, so their output never changes.
+//! Errors are expected on stderr and give an exit code of 1, as they do from the command line.
+
+use bota_basic::{Bytecode, Level, Lint, LintConfig, MemoryIo, Program, Registry};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+const SEED: u64 = 1;
+const FAKE_CLOCK: u64 = 1_700_000_000_000;
+
+fn corpus_dir() -> PathBuf {
+	Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("conformance")
+}
+
+fn scripts() -> Vec<PathBuf> {
+	let mut scripts: Vec<PathBuf> = std::fs::read_dir(corpus_dir())
+		.expect("the conformance directory should exist")
+		.map(|entry| entry.unwrap().path())
+		.filter(|path| path.extension().is_some_and(|ext| ext == "bas"))
+		.collect();
+	scripts.sort();
+	scripts
+}
+
+fn read_optional(path: PathBuf) -> Option<String> {
+	std::fs::read_to_string(path).ok()
+}
+
+/// What a script did: its stdout, exit code and stderr
+#[derive(PartialEq, Debug)]
+struct Run {
+	stdout: String,
+	code: i32,
+	stderr: String
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Mode {
+	Interpreter,
+	Vm,
+	/// On the VM, from bytecode that's been saved to bytes and read back
+	SavedBytecode,
+	Optimized,
+	/// Interpreted after going through the formatter
+	Formatted
+}
+
+fn run_script(script: &Path, mode: Mode) -> Run {
+	let input = read_optional(script.with_extension("in")).unwrap_or_default();
+	let io = MemoryIo::new(&input);
+	let source = std::fs::read_to_string(script).unwrap();
+	let program = match mode {
+		Mode::Interpreter => Program::new(source),
+		Mode::Vm => Program::new(source).with_vm(),
+		Mode::Optimized => Program::new(source).with_optimizer(),
+		Mode::Formatted => Program::new(Program::new(source).format()),
+		Mode::SavedBytecode => match Program::new(source).compile() {
+			Ok(bytecode) => Program::from_bytecode(Bytecode::from_bytes(&bytecode.to_bytes()).unwrap()),
+			Err(error) => return Run {stdout: String::new(), code: 1, stderr: format!("{}\n", error)}
+		}
+	};
+	let mut program = program
+		.with_seed(SEED)
+		.with_fake_clock(FAKE_CLOCK)
+		.with_io(io.clone());
+	let (code, stderr) = match program.run_program() {
+		Ok(code) => (code, String::new()),
+		Err(error) => (1, format!("{}\n", error))
+	};
+	Run {stdout: io.output(), code, stderr}
+}
+
+/// Runs a script, returning a description of each way it differs from what's expected
+fn check_script(script: &Path) -> Vec<String> {
+	let expected_out = read_optional(script.with_extension("out"))
+		.unwrap_or_else(|| panic!("{} has no .out file", script.display()));
+	let expected_code: i32 = read_optional(script.with_extension("code"))
+		.map(|code| code.trim().parse().expect("exit codes should be numbers"))
+		.unwrap_or(0);
+	let expected_err = read_optional(script.with_extension("err"));
+	let run = run_script(script, Mode::Interpreter);
+
+	let mut problems = Vec::new();
+	if run.stdout != expected_out {
+		problems.push(format!("expected stdout {:?}, got {:?}", expected_out, run.stdout));
+	}
+	if run.code != expected_code {
+		problems.push(format!("expected exit code {}, got {}", expected_code, run.code));
+	}
+	if let Some(expected_err) = expected_err {
+		if run.stderr != expected_err {
+			problems.push(format!("expected stderr {:?}, got {:?}", expected_err, run.stderr));
+		}
+	}
+	problems
+}
+
+#[test]
+fn corpus_output_matches() {
+	let mut failures = Vec::new();
+	for script in scripts() {
+		for problem in check_script(&script) {
+			failures.push(format!("{}: {}", script.file_name().unwrap().to_string_lossy(), problem));
+		}
+	}
+	assert!(failures.is_empty(), "conformance failures:\n{}", failures.join("\n"));
+}
+
+/// The bytecode VM should do exactly what the interpreter does
+#[test]
+fn vm_matches_interpreter() {
+	let mut failures = Vec::new();
+	for script in scripts() {
+		let interpreted = run_script(&script, Mode::Interpreter);
+		for mode in [Mode::Vm, Mode::SavedBytecode, Mode::Optimized] {
+			let compiled = run_script(&script, mode);
+			if interpreted != compiled {
+				failures.push(format!("{}: interpreted {:?}, {:?} {:?}", script.file_name().unwrap().to_string_lossy(), interpreted, mode, compiled));
+			}
+		}
+	}
+	assert!(failures.is_empty(), "the VM differs from the interpreter:\n{}", failures.join("\n"));
+}
+
+#[test]
+fn check_finds_expected_problems() {
+	let mut failures = Vec::new();
+	for script in scripts() {
+		let expected = read_optional(script.with_extension("check")).unwrap_or_default();
+		let source = std::fs::read_to_string(&script).unwrap();
+		let found: String = Program::new(source).check().iter().map(|error| format!("{}\n", error)).collect();
+		if found != expected {
+			failures.push(format!("{}: expected {:?}, found {:?}", script.file_name().unwrap().to_string_lossy(), expected, found));
+		}
+	}
+	assert!(failures.is_empty(), "the type checker disagrees:\n{}", failures.join("\n"));
+}
+
+#[test]
+fn lint_finds_expected_warnings() {
+	let mut failures = Vec::new();
+	for script in scripts() {
+		let Some(expected) = read_optional(script.with_extension("lint")) else {continue};
+		let source = std::fs::read_to_string(&script).unwrap();
+		let found: String = Program::new(source).lint(&LintConfig::default()).iter().map(|warning| format!("{}\n", warning)).collect();
+		if found != expected {
+			failures.push(format!("{}: expected {:?}, found {:?}", script.file_name().unwrap().to_string_lossy(), expected, found));
+		}
+	}
+	assert!(failures.is_empty(), "the linter disagrees:\n{}", failures.join("\n"));
+}
+
+#[test]
+fn lint_config_sets_levels() {
+	let config = LintConfig::parse("# comments are ignored\nunused-label = allow\nW003 = deny\n").unwrap();
+	assert_eq!(config.level(Lint::UnusedLabel), Level::Allow);
+	assert_eq!(config.level(Lint::UnreachableCode), Level::Deny);
+	assert_eq!(config.level(Lint::UnusedVariable), Level::Warn);
+	assert!(LintConfig::parse("unused-label = sometimes").is_err());
+
+	let source = std::fs::read_to_string(corpus_dir().join("lint.bas")).unwrap();
+	let warnings = Program::new(source).lint(&config);
+	assert!(warnings.iter().all(|warning| warning.lint != Lint::UnusedLabel));
+	assert!(warnings.iter().any(|warning| warning.lint == Lint::UnreachableCode && warning.level == Level::Deny));
+}
+
+/// Formatting shouldn't change what a script does, and formatting it again shouldn't change it
+#[test]
+fn formatting_keeps_behaviour() {
+	let mut failures = Vec::new();
+	for script in scripts() {
+		let name = script.file_name().unwrap().to_string_lossy().to_string();
+		let formatted = Program::new(std::fs::read_to_string(&script).unwrap()).format();
+		if let Some(expected) = read_optional(script.with_extension("fmt")) {
+			if formatted != expected {
+				failures.push(format!("{}: expected {:?}, formatted {:?}", name, expected, formatted));
+			}
+		}
+		if Program::new(formatted.clone()).format() != formatted {
+			failures.push(format!("{}: formatting it again changes it", name));
+		}
+		let (interpreted, reformatted) = (run_script(&script, Mode::Interpreter), run_script(&script, Mode::Formatted));
+		if interpreted != reformatted {
+			failures.push(format!("{}: interpreted {:?}, formatted {:?}", name, interpreted, reformatted));
+		}
+	}
+	assert!(failures.is_empty(), "formatting changed scripts:\n{}", failures.join("\n"));
+}
+
+#[test]
+fn corpus_covers_every_command() {
+	let mut used = HashSet::new();
+	for script in scripts() {
+		let source = std::fs::read_to_string(&script).unwrap();
+		for line in source.lines() {
+			if let Some(word) = line.split_whitespace().next() {
+				used.insert(word.to_uppercase());
+			}
+		}
+	}
+
+	let builtins = Registry::builtins();
+	let mut missing: Vec<&str> = builtins.names().filter(|name| !used.contains(*name)).collect();
+	missing.sort();
+	assert!(missing.is_empty(), "no conformance script uses {}", missing.join(", "));
+}
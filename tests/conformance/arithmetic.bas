@@ -0,0 +1,68 @@
+DECL nl str
+SET nl "\n"
+DECL out str
+DECL a int
+DECL b int
+DECL r int
+SET a 17
+SET b 5
+
+ADD r a b
+CONVERT out r
+PRINT out
+PRINT nl
+SUB r a b
+CONVERT out r
+PRINT out
+PRINT nl
+SUB r b a
+CONVERT out r
+PRINT out
+PRINT nl
+MUL r a b
+CONVERT out r
+PRINT out
+PRINT nl
+DIV r a b
+CONVERT out r
+PRINT out
+PRINT nl
+MOD r a b
+CONVERT out r
+PRINT out
+PRINT nl
+
+DECL x float
+DECL y float
+DECL f float
+SET x 7.5
+SET y 2
+DIV f x y
+CONVERT out f
+PRINT out
+PRINT nl
+SUB f y x
+CONVERT out f
+PRINT out
+PRINT nl
+
+DECL n natural
+SUB n b a
+CONVERT out n
+PRINT out
+PRINT nl
+
+DECL g float
+SET g -2.5
+ROUND r g
+CONVERT out r
+PRINT out
+PRINT nl
+FLOOR r g
+CONVERT out r
+PRINT out
+PRINT nl
+CEIL r g
+CONVERT out r
+PRINT out
+PRINT nl
@@ -0,0 +1,70 @@
+DECL nl str
+SET nl "\n"
+DECL sp str
+SET sp " "
+DECL out str
+DECL r bool
+DECL n natural
+DECL f float
+SET n 2
+SET f 2.0
+
+EQ r n f
+CONVERT out r
+PRINT out
+PRINT sp
+NE r n f
+CONVERT out r
+PRINT out
+PRINT sp
+SET f 2.5
+LT r n f
+CONVERT out r
+PRINT out
+PRINT sp
+GT r n f
+CONVERT out r
+PRINT out
+PRINT sp
+LE r n n
+CONVERT out r
+PRINT out
+PRINT sp
+GE r f n
+CONVERT out r
+PRINT out
+PRINT nl
+
+DECL s1 str
+DECL s2 str
+SET s1 "apple"
+SET s2 "apricot"
+LT r s1 s2
+CONVERT out r
+PRINT out
+PRINT sp
+DECL l1 list
+DECL l2 list
+SET l1 [1,2,3]
+SET l2 [1,2]
+GT r l1 l2
+CONVERT out r
+PRINT out
+PRINT sp
+EQ r l1 l2
+CONVERT out r
+PRINT out
+PRINT nl
+
+TRY
+LT r s1 n
+CATCH e
+PRINT e
+PRINT nl
+ENDTRY
+TRY
+EQ r s1 n
+CATCH e2
+PRINT e2
+PRINT nl
+ENDTRY
@@ -0,0 +1,74 @@
+DECL nl str
+SET nl "\n"
+DECL sp str
+SET sp " "
+DECL out str
+DECL i int
+DECL one int
+DECL five int
+SET one 1
+SET five 5
+
+LABEL loop
+ADD i i one
+CONVERT out i
+PRINT out
+PRINT sp
+JLT loop i five
+PRINT nl
+
+LABEL countdown
+SUB i i one
+CONVERT out i
+PRINT out
+PRINT sp
+JGT countdown i one
+PRINT nl
+
+JEQ equal i one
+PRINT nl
+LABEL equal
+DECL msg str
+SET msg "equal\n"
+PRINT msg
+
+JNE skip1 i five
+SET msg "not printed\n"
+PRINT msg
+LABEL skip1
+JGE skip2 i one
+PRINT msg
+LABEL skip2
+JLE skip3 i one
+PRINT msg
+LABEL skip3
+
+DECL flag bool
+SET flag TRUE
+JIFNOT skip4 flag
+SET msg "flag set\n"
+PRINT msg
+LABEL skip4
+JIF skip5 flag
+PRINT msg
+LABEL skip5
+
+DECL target str
+SET target "greet"
+CALL routine
+CALL routine
+JMPVAR target
+SET msg "not printed\n"
+PRINT msg
+
+LABEL routine
+SET msg "in routine\n"
+PRINT msg
+RETURN
+
+LABEL greet
+SET msg "greeted\n"
+PRINT msg
+JMP end
+PRINT msg
+LABEL end
@@ -0,0 +1,23 @@
+DECL nl str
+SET nl "\n"
+DECL out str
+DECL n natural
+LEN n ARGS
+CONVERT out n
+PRINT out
+PRINT nl
+GETENV out "BOTA_BASIC_CONFORMANCE_UNSET"
+DECL empty str
+DECL unset bool
+EQ unset out empty
+CONVERT out unset
+PRINT out
+PRINT nl
+TRY
+EXIT 4000000000
+CATCH e
+PRINT e
+PRINT nl
+ENDTRY
+EXIT 3
+PRINT nl
@@ -0,0 +1,43 @@
+DECL nl str
+SET nl "\n"
+DECL out str
+DECL l list
+DECL n natural
+DECL big natural
+SET l [1,2,3]
+SET big 7
+
+TRY
+INDEX n l big
+SET out "not printed\n"
+PRINT out
+CATCH e
+PRINT e
+PRINT nl
+ENDTRY
+
+TRY
+INDEX n l n
+SET out "no error\n"
+PRINT out
+CATCH e2
+PRINT e2
+ENDTRY
+
+ON ERROR GOTO handler
+CALL broken
+SET out "not printed\n"
+PRINT out
+
+LABEL broken
+INDEX n l big
+RETURN
+
+LABEL handler
+PRINT ERR
+PRINT nl
+CONVERT out ERRLINE
+PRINT out
+PRINT nl
+ON ERROR GOTO 0
+INDEX n l big
@@ -0,0 +1,18 @@
+rem Formatting keeps comments like this one
+decl   total natural
+Decl n    natural
+decl one natural
+DECL out str
+  set n 3
+set one 1
+label loop
+' add it up
+  add total total n
+sub n n one
+   jgt loop n one
+try
+convert out total
+catch   e
+print e
+endtry
+print out
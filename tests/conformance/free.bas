@@ -0,0 +1,6 @@
+DECL greeting str
+SET greeting "bye\n"
+PRINT greeting
+FREE greeting
+PRINT greeting
+PRINT greeting
@@ -0,0 +1,16 @@
+DECL line str
+DECL total int
+DECL n int
+DECL out str
+INPUT line
+CONVERT n line
+ADD total total n
+INPUT line
+CONVERT n line
+ADD total total n
+CONVERT out total
+SET line "total: "
+PRINT line
+PRINT out
+SET line "\n"
+PRINT line
@@ -0,0 +1,12 @@
+DECL s str
+TRY
+JMP a
+LABEL b
+FREE nothere
+CATCH e
+SET s "caught"
+PRINT s
+ENDTRY
+EXIT 0
+LABEL a
+JMP b
@@ -0,0 +1,21 @@
+DECL nl str
+SET nl "\n"
+DECL count natural
+DECL name str
+DECL unused int
+DECL flag bool
+SET name "lint"
+ADD count count count
+JEQ done name count
+CONVERT name flag
+PRINT name
+PRINT nl
+DECL name str
+lint: allow read-before-set
+PRINT name
+JMP done
+PRINT nl
+LABEL nowhere
+PRINT nl
+LABEL done
+PRINT nl
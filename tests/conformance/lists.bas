@@ -0,0 +1,80 @@
+DECL nl str
+SET nl "\n"
+DECL out str
+DECL l list
+DECL r list
+DECL n natural
+DECL zero natural
+DECL two natural
+SET l [5,3,8,1]
+SET two 2
+
+SLICE r l zero two
+CONVERT out r
+PRINT out
+PRINT nl
+INDEX n l two
+CONVERT out n
+PRINT out
+PRINT nl
+LEN n l
+CONVERT out n
+PRINT out
+PRINT nl
+DECL item str
+SET item "new"
+INSERT l two item
+CONVERT out l
+PRINT out
+PRINT nl
+
+SET l [5,3,8,1,'a',FALSE]
+SORT r l
+CONVERT out r
+PRINT out
+PRINT nl
+SORT r l DESC
+CONVERT out r
+PRINT out
+PRINT nl
+
+SET l [5,3,8,1]
+SORTBY r l descending
+CONVERT out r
+PRINT out
+PRINT nl
+MAP r l double
+CONVERT out r
+PRINT out
+PRINT nl
+FILTER r l big
+CONVERT out r
+PRINT out
+PRINT nl
+SET n 100
+REDUCE n l sum
+CONVERT out n
+PRINT out
+PRINT nl
+JMP end
+
+LABEL descending
+GT RESULT A B
+RETURN
+
+LABEL double
+ADD RESULT ITEM ITEM
+RETURN
+
+LABEL big
+DECL four natural
+SET four 4
+GT RESULT ITEM four
+FREE four
+RETURN
+
+LABEL sum
+ADD RESULT ACC ITEM
+RETURN
+
+LABEL end
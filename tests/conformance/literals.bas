@@ -0,0 +1,63 @@
+DECL nl str
+SET nl "\n"
+DECL out str
+DECL s str
+SET s "hello, world"
+PRINT s
+PRINT nl
+SET s "tab\there \"quoted\" back\\slash\n"
+PRINT s
+
+DECL c char
+SET c 'x'
+CONVERT out c
+PRINT out
+PRINT nl
+
+DECL l list
+SET l [1,-2,3.5,'z',TRUE,"s"]
+CONVERT out l
+PRINT out
+PRINT nl
+SET l []
+CONVERT out l
+PRINT out
+PRINT nl
+
+DECL i int
+SET i -0x10
+CONVERT out i
+PRINT out
+PRINT nl
+DECL n natural
+SET n 1_000_000
+CONVERT out n
+PRINT out
+PRINT nl
+DECL f float
+SET f -0.25
+CONVERT out f
+PRINT out
+PRINT nl
+DECL b bool
+SET b TRUE
+CONVERT out b
+PRINT out
+PRINT nl
+DECL wide natural
+TRY
+SET wide 4294967297
+CATCH e
+PRINT e
+PRINT nl
+ENDTRY
+TRY
+SET i -0x80000001
+CATCH e2
+PRINT e2
+PRINT nl
+ENDTRY
+SET wide 0xFFFFFFFF
+CONVERT out wide
+PRINT out
+PRINT nl
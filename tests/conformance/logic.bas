@@ -0,0 +1,76 @@
+DECL nl str
+SET nl "\n"
+DECL sp str
+SET sp " "
+DECL out str
+DECL t bool
+DECL f bool
+DECL r bool
+SET t TRUE
+SET f FALSE
+
+AND r t f
+CONVERT out r
+PRINT out
+PRINT sp
+OR r t f
+CONVERT out r
+PRINT out
+PRINT sp
+XOR r t t
+CONVERT out r
+PRINT out
+PRINT sp
+NOT r f
+CONVERT out r
+PRINT out
+PRINT nl
+
+DECL a natural
+DECL b natural
+DECL n natural
+SET a 0b1100
+SET b 0b1010
+BAND n a b
+CONVERT out n BIN
+PRINT out
+PRINT sp
+BOR n a b
+CONVERT out n BIN
+PRINT out
+PRINT sp
+BXOR n a b
+CONVERT out n BIN
+PRINT out
+PRINT nl
+
+DECL i int
+DECL j int
+SET i 5
+BNOT j i
+CONVERT out j
+PRINT out
+PRINT sp
+DECL k int
+SET k 4
+SHL j i k
+CONVERT out j HEX
+PRINT out
+PRINT sp
+DECL one int
+SET one 1
+SHR j i one
+CONVERT out j
+PRINT out
+PRINT nl
+
+DECL hex str
+SET hex "ff"
+CONVERT n hex HEX
+CONVERT out n
+PRINT out
+PRINT sp
+SET n 0o1_7
+CONVERT out n
+PRINT out
+PRINT nl
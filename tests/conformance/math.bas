@@ -0,0 +1,105 @@
+DECL nl str
+SET nl "\n"
+DECL out str
+DECL f float
+DECL a float
+DECL b float
+DECL i int
+
+SET a 16
+SQRT f a
+CONVERT out f
+PRINT out
+PRINT nl
+
+SET a 2
+SET b 10
+POW f a b
+CONVERT out f
+PRINT out
+PRINT nl
+
+SET a -4.25
+ABS f a
+CONVERT out f
+PRINT out
+PRINT nl
+
+MIN f a b
+CONVERT out f
+PRINT out
+PRINT nl
+MAX f a b
+CONVERT out f
+PRINT out
+PRINT nl
+
+DECL zero float
+SIN f zero
+CONVERT out f
+PRINT out
+PRINT nl
+COS f zero
+CONVERT out f
+PRINT out
+PRINT nl
+TAN f zero
+CONVERT out f
+PRINT out
+PRINT nl
+
+SET a 1
+ATAN2 f a a
+MUL f f a
+DIV b PI f
+CONVERT out b
+PRINT out
+PRINT nl
+
+CONVERT out E
+PRINT out
+PRINT nl
+LOG f a
+CONVERT out f
+PRINT out
+PRINT nl
+EXP f zero
+CONVERT out f
+PRINT out
+PRINT nl
+
+DECL lo int
+DECL hi int
+DECL v int
+SET lo 0
+SET hi 10
+SET v 42
+CLAMP i v lo hi
+CONVERT out i
+PRINT out
+PRINT nl
+SET v -3
+SIGN i v
+CONVERT out i
+PRINT out
+PRINT nl
+SET v 16777217
+ABS i v
+CONVERT out i
+PRINT out
+PRINT nl
+SET lo 0
+SET hi 20000000
+CLAMP i v lo hi
+CONVERT out i
+PRINT out
+PRINT nl
+MAX i v lo
+CONVERT out i
+PRINT out
+PRINT nl
+SET lo -16777217
+MIN i v lo
+CONVERT out i
+PRINT out
+PRINT nl
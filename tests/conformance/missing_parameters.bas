@@ -0,0 +1,3 @@
+Lines that don't start with a command are ignored
+DECL x int
+ADD x x
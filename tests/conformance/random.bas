@@ -0,0 +1,50 @@
+DECL nl str
+SET nl "\n"
+DECL out str
+DECL n int
+DECL lo int
+DECL hi int
+DECL l list
+DECL r list
+SET lo 1
+SET hi 6
+SET l [1,2,3,4,5]
+
+SEED 42
+RANDOM n lo hi
+CONVERT out n
+PRINT out
+PRINT nl
+SHUFFLE r l
+CONVERT out r
+PRINT out
+PRINT nl
+CHOOSE n l
+CONVERT out n
+PRINT out
+PRINT nl
+
+SEED 42
+RANDOM n lo hi
+CONVERT out n
+PRINT out
+PRINT nl
+
+SET lo 16777217
+RANDOM n lo lo
+CONVERT out n
+PRINT out
+PRINT nl
+DECL big natural
+DECL top natural
+SET top 4294967295
+RANDOM big top top
+CONVERT out big
+PRINT out
+PRINT nl
+TRY
+SEED -1
+CATCH e
+PRINT e
+PRINT nl
+ENDTRY
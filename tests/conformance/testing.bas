@@ -0,0 +1,31 @@
+DECL nl str
+SET nl "\n"
+DECL a int
+DECL b int
+SET a 2
+SET b 2
+ASSERTEQ a b
+DECL ok bool
+EQ ok a b
+ASSERT ok "a should equal b"
+
+TRY
+NOT ok ok
+ASSERT ok "a should not equal b"
+CATCH e
+PRINT e
+PRINT nl
+ENDTRY
+TRY
+SET b 3
+ASSERTEQ a b
+CATCH e2
+PRINT e2
+PRINT nl
+ENDTRY
+
+TEST skipped
+DECL message str
+SET message "tests are skipped\n"
+PRINT message
+ENDTEST
@@ -0,0 +1,56 @@
+DECL nl str
+SET nl "\n"
+DECL out str
+DECL n int
+DECL ms natural
+DECL fmt str
+
+NOW n
+CONVERT out n
+PRINT out
+PRINT nl
+SLEEP 1500
+CLOCK ms
+CONVERT out ms
+PRINT out
+PRINT nl
+NOW n
+SET fmt "%Y-%m-%d %H:%M:%S"
+FORMATDATE out n fmt
+PRINT out
+PRINT nl
+
+DECL date str
+SET date "2000-02-29 12:00:00"
+PARSEDATE n date fmt
+CONVERT out n
+PRINT out
+PRINT nl
+
+SET date "2023-02-29 12:00:00"
+TRY
+PARSEDATE n date fmt
+CATCH e
+PRINT e
+PRINT nl
+ENDTRY
+SET date "2000-01-01 24:00:00"
+TRY
+PARSEDATE n date fmt
+CATCH e2
+PRINT e2
+PRINT nl
+ENDTRY
+SET date "2000-01-01 12:99:00"
+TRY
+PARSEDATE n date fmt
+CATCH e3
+PRINT e3
+PRINT nl
+ENDTRY
+TRY
+SLEEP -5
+CATCH e4
+PRINT e4
+PRINT nl
+ENDTRY
@@ -0,0 +1,31 @@
+DECL nl str
+SET nl "\n"
+DECL l list
+DECL n natural
+DECL big natural
+DECL target str
+SET big 7
+SET target "second"
+
+TRY
+JMP first
+CATCH e
+PRINT e
+PRINT nl
+ENDTRY
+LABEL first
+TRY
+JMPVAR target
+CATCH e2
+PRINT e2
+PRINT nl
+ENDTRY
+LABEL second
+
+ON ERROR GOTO handler
+INDEX n l big
+EXIT 1
+
+LABEL handler
+PRINT ERR
+PRINT nl
@@ -0,0 +1,18 @@
+DECL s str
+SET s "checked\n"
+PRINT s
+EXIT 0
+
+TEST mistakes
+DECL n int
+DECL flag bool
+DECL s str
+AND flag n flag
+PRINT n
+FREE s
+PRINT s
+PRINT count
+DECL count str
+ASSERT flag 5
+JMP nowhere
+ENDTEST
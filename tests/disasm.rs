@@ -0,0 +1,167 @@
+//! Lists the bytecode a program compiles to, through `Program::disassemble` and `bota_basic disasm`.
+
+use bota_basic::Program;
+use std::path::PathBuf;
+use std::process::Command;
+
+const SCRIPT: &str = "\
+DECL n int
+DECL one int
+DECL out str
+SET one 1
+LABEL top
+ADD n n one
+JLT top n one
+CONVERT out n
+PRINT out
+";
+
+const LISTING: &str = "\
+line 1: DECL n int
+  0000  CONST    #0:\"n\"
+  0001  CONST    #1:\"int\"
+  0002  INVOKE   DECL 2
+line 2: DECL one int
+  0003  CONST    #2:\"one\"
+  0004  CONST    #3:\"int\"
+  0005  INVOKE   DECL 2
+line 3: DECL out str
+  0006  CONST    #4:\"out\"
+  0007  CONST    #5:\"str\"
+  0008  INVOKE   DECL 2
+line 4: SET one 1
+  0009  LOAD     $0:one
+  0010  LOADOR   $1:1 #6:1
+  0011  INVOKE   SET 2
+  0012  STORE    $0:one
+line 5: LABEL top
+  0013  CONST    #7:\"top\"
+  0014  INVOKE   LABEL 1
+line 6: ADD n n one
+  0015  LOAD     $2:n
+  0016  LOAD     $2:n
+  0017  LOAD     $0:one
+  0018  INVOKE   ADD 3
+  0019  STORE    $2:n
+line 7: JLT top n one
+  0020  LABEL    #8:\"top\" -> line 5 @0013
+  0021  LOAD     $2:n
+  0022  LOAD     $0:one
+  0023  INVOKE   JLT 3
+line 8: CONVERT out n
+  0024  LOAD     $3:out
+  0025  LOAD     $2:n
+  0026  INVOKE   CONVERT 2
+  0027  STORE    $3:out
+line 9: PRINT out
+  0028  LOAD     $3:out
+  0029  INVOKE   PRINT 1
+
+labels:
+  top  line 5  @0013
+
+slots:
+  $0  one
+  $1  1
+  $2  n
+  $3  out
+
+variables:
+  n  read on 6, 7, 8  written on 1, 6
+  one  read on 6, 7  written on 2, 4
+  out  read on 9  written on 3, 8
+";
+
+/// Where the optimizer's listing differs: `SET` stores a constant, and everything after it moves up by two
+const OPTIMIZED: &str = "\
+line 1: DECL n int
+  0000  CONST    #0:\"n\"
+  0001  CONST    #1:\"int\"
+  0002  INVOKE   DECL 2
+line 2: DECL one int
+  0003  CONST    #2:\"one\"
+  0004  CONST    #3:\"int\"
+  0005  INVOKE   DECL 2
+line 3: DECL out str
+  0006  CONST    #4:\"out\"
+  0007  CONST    #5:\"str\"
+  0008  INVOKE   DECL 2
+line 4: SET one 1
+  0009  CONST    #11:1
+  0010  STORE    $0:one
+line 5: LABEL top
+  0011  CONST    #7:\"top\"
+  0012  INVOKE   LABEL 1
+line 6: ADD n n one
+  0013  LOAD     $2:n
+  0014  LOAD     $2:n
+  0015  LOAD     $0:one
+  0016  INVOKE   ADD 3
+  0017  STORE    $2:n
+line 7: JLT top n one
+  0018  LABEL    #8:\"top\" -> line 5 @0011
+  0019  LOAD     $2:n
+  0020  LOAD     $0:one
+  0021  INVOKE   JLT 3
+line 8: CONVERT out n
+  0022  LOAD     $3:out
+  0023  LOAD     $2:n
+  0024  INVOKE   CONVERT 2
+  0025  STORE    $3:out
+line 9: PRINT out
+  0026  LOAD     $3:out
+  0027  INVOKE   PRINT 1
+
+labels:
+  top  line 5  @0011
+
+slots:
+  $0  one
+  $1  1
+  $2  n
+  $3  out
+
+variables:
+  n  read on 6, 7, 8  written on 1, 6
+  one  read on 6, 7  written on 2, 4
+  out  read on 9  written on 3, 8
+";
+
+/// Writes the script somewhere `bota_basic` can read it
+fn script_file(name: &str) -> PathBuf {
+	let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+	let _ = std::fs::remove_dir_all(&dir);
+	std::fs::create_dir_all(&dir).unwrap();
+	std::fs::write(dir.join("script.bas"), SCRIPT).unwrap();
+	dir.join("script.bas")
+}
+
+fn disasm(args: &[&str], file: &PathBuf) -> String {
+	let output = Command::new(env!("CARGO_BIN_EXE_bota_basic")).arg("disasm").args(args).arg(file).output().unwrap();
+	assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+	String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn lists_lines_labels_slots_and_variables() {
+	assert_eq!(Program::new(SCRIPT.to_string()).disassemble().unwrap(), LISTING);
+}
+
+#[test]
+fn lists_optimized_bytecode() {
+	assert_eq!(Program::new(SCRIPT.to_string()).with_optimizer().disassemble().unwrap(), OPTIMIZED);
+}
+
+#[test]
+fn disasm_subcommand() {
+	let file = script_file("disasm_subcommand");
+	assert_eq!(disasm(&[], &file), LISTING);
+	assert_eq!(disasm(&["-O"], &file), OPTIMIZED);
+}
+
+#[test]
+fn disasm_subcommand_takes_flags_after_the_file() {
+	let file = script_file("disasm_subcommand_takes_flags_after_the_file");
+	let output = Command::new(env!("CARGO_BIN_EXE_bota_basic")).arg("disasm").arg(&file).arg("-O").output().unwrap();
+	assert_eq!(String::from_utf8(output.stdout).unwrap(), OPTIMIZED);
+}
@@ -0,0 +1,57 @@
+//! Sends `PRINT` and `INPUT` somewhere other than the console.
+
+use bota_basic::{Error, MemoryIo, Program, ScriptedIo};
+
+const ECHO: &str = "\
+DECL line str
+INPUT line
+PRINT line
+INPUT line
+PRINT line
+";
+
+#[test]
+fn scripted_io_answers_each_input_in_turn() {
+	let io = ScriptedIo::new(vec!["one".to_string(), "two".to_string(), "three".to_string()]);
+	let code = Program::new(ECHO.to_string()).with_io(io.clone()).run_program().unwrap();
+	assert_eq!(code, 0);
+	assert_eq!(io.output(), "one\ntwo\n");
+	assert_eq!(io.remaining(), 1);
+}
+
+#[test]
+fn scripted_io_fails_once_it_runs_out() {
+	let io = ScriptedIo::new(vec!["one".to_string()]);
+	let error = Program::new(ECHO.to_string()).with_io(io.clone()).run_program().unwrap_err();
+	assert_eq!(error.line, 4);
+	assert!(matches!(error.error, Error::Io(_)));
+	assert_eq!(io.output(), "one\n");
+}
+
+#[test]
+fn memory_io_gives_empty_lines_at_the_end() {
+	let io = MemoryIo::new("one\n");
+	assert_eq!(Program::new(ECHO.to_string()).with_io(io.clone()).run_program().unwrap(), 0);
+	assert_eq!(io.output(), "one\n");
+}
+
+#[test]
+fn waiting_io_stops_the_program() {
+	let io = MemoryIo::waiting();
+	let error = Program::new(ECHO.to_string()).with_io(io.clone()).run_program().unwrap_err();
+	assert_eq!(error.line, 2);
+	assert!(matches!(error.error, Error::WaitingForInput));
+	assert_eq!(io.output(), "");
+}
+
+#[test]
+fn input_only_goes_into_strs() {
+	let source = "DECL n natural\nINPUT n\n";
+	let io = MemoryIo::new("5\n");
+	let error = Program::new(source.to_string()).with_io(io.clone()).run_program().unwrap_err();
+	assert_eq!(error.line, 2);
+	assert!(matches!(error.error, Error::WrongType(_)), "{}", error);
+
+	let problems = Program::new(source.to_string()).check();
+	assert_eq!(problems.iter().map(|problem| problem.line).collect::<Vec<_>>(), [2]);
+}
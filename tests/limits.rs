@@ -0,0 +1,84 @@
+//! Stops programs which go over their limits, however the program tries to carry on.
+
+use bota_basic::{Error, Limit, Limits, MemoryIo, Program};
+use std::time::{Duration, Instant};
+
+fn run(source: &str, limits: Limits) -> Result<i32, bota_basic::RuntimeError> {
+	Program::new(source.to_string()).with_io(MemoryIo::default()).with_limits(limits).run_program()
+}
+
+/// Runs a program which should go over a limit, giving back which one and the line it happened on
+fn exceeded(source: &str, limits: Limits) -> (Limit, usize) {
+	match run(source, limits) {
+		Err(bota_basic::RuntimeError {line, error: Error::LimitExceeded(limit)}) => (limit, line),
+		other => panic!("expected the program to go over a limit, but it gave {:?}", other.map_err(|error| error.to_string()))
+	}
+}
+
+const FOREVER: &str = "LABEL top\nJMP top\n";
+
+#[test]
+fn instructions() {
+	let limits = Limits {instructions: Some(100), ..Limits::default()};
+	assert_eq!(exceeded(FOREVER, limits.clone()).0, Limit::Instructions);
+	assert_eq!(run("DECL n int\n", limits).unwrap(), 0);
+}
+
+#[test]
+fn time() {
+	let limits = Limits {time: Some(Duration::from_millis(50)), ..Limits::default()};
+	assert_eq!(exceeded(FOREVER, limits).0, Limit::Time);
+}
+
+#[test]
+fn sleep_stops_at_the_time_limit() {
+	let limits = Limits {time: Some(Duration::from_millis(100)), ..Limits::default()};
+	let started = Instant::now();
+	assert_eq!(exceeded("SLEEP 5000\n", limits), (Limit::Time, 1));
+	assert!(started.elapsed() < Duration::from_secs(2), "the sleep wasn't cut short");
+}
+
+#[test]
+fn fake_sleeps_take_no_time() {
+	let limits = Limits {time: Some(Duration::from_millis(100)), ..Limits::default()};
+	let mut program = Program::new("SLEEP 5000\n".to_string()).with_fake_clock(0).with_limits(limits);
+	assert_eq!(program.run_program().unwrap(), 0);
+}
+
+#[test]
+fn memory() {
+	let source = "DECL l list\nDECL s str\nSET s \"some text\"\nLABEL top\nADD l l s\nJMP top\n";
+	assert_eq!(exceeded(source, Limits {memory: Some(1000), ..Limits::default()}), (Limit::Memory, 5));
+}
+
+#[test]
+fn length() {
+	let source = "DECL s str\nSET s \"ab\"\nLABEL top\nADD s s s\nJMP top\n";
+	assert_eq!(exceeded(source, Limits {length: Some(16), ..Limits::default()}), (Limit::Length, 4));
+}
+
+#[test]
+fn call_depth() {
+	let source = "LABEL down\nCALL down\n";
+	assert_eq!(exceeded(source, Limits {call_depth: Some(10), ..Limits::default()}), (Limit::CallDepth, 2));
+}
+
+#[test]
+fn limits_can_not_be_caught() {
+	let source = "ON ERROR GOTO handler\nTRY\nLABEL top\nJMP top\nCATCH e\nENDTRY\nLABEL handler\nEXIT 2\n";
+	assert_eq!(exceeded(source, Limits {instructions: Some(100), ..Limits::default()}).0, Limit::Instructions);
+}
+
+#[test]
+fn each_limit_has_its_own_message() {
+	let messages: Vec<String> = [Limit::Instructions, Limit::Time, Limit::Memory, Limit::Length, Limit::CallDepth].iter()
+		.map(|limit| Error::LimitExceeded(*limit).to_string())
+		.collect();
+	assert_eq!(messages, [
+		"the program went over its instruction limit",
+		"the program went over its time limit",
+		"the program went over its memory limit",
+		"the program went over its length limit",
+		"the program went over its call depth limit"
+	]);
+}
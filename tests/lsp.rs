@@ -0,0 +1,133 @@
+//! Talks to the language server the way an editor would, and checks what it answers.
+//! Its JSON is always written the same way, so answers are compared as text.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+const URI: &str = "file:///nowhere/script.bas";
+const SOURCE: &str = "DECL count int 0\\nDECL done bool false\\nLABEL loop\\nADD count count count\\nEQ done count count\\nJIF loop done\\nFREE count\\nPRINT count\\n";
+
+struct Server {
+	child: Child,
+	stdout: BufReader<ChildStdout>,
+	next_id: usize
+}
+
+impl Server {
+	fn start() -> Self {
+		let mut child = Command::new(env!("CARGO_BIN_EXE_bota_basic_lsp"))
+			.stdin(Stdio::piped())
+			.stdout(Stdio::piped())
+			.spawn()
+			.expect("the language server should start");
+		let stdout = BufReader::new(child.stdout.take().unwrap());
+		Server {child, stdout, next_id: 1}
+	}
+
+	fn send(&mut self, message: &str) {
+		let stdin = self.child.stdin.as_mut().unwrap();
+		write!(stdin, "Content-Length: {}\r\n\r\n{}", message.len(), message).unwrap();
+		stdin.flush().unwrap();
+	}
+
+	fn receive(&mut self) -> String {
+		let mut length = 0;
+		loop {
+			let mut header = String::new();
+			self.stdout.read_line(&mut header).unwrap();
+			match header.trim().strip_prefix("Content-Length: ") {
+				Some(value) => length = value.parse().unwrap(),
+				None if header.trim().is_empty() => break,
+				None => ()
+			}
+		}
+		let mut body = vec![0; length];
+		self.stdout.read_exact(&mut body).unwrap();
+		String::from_utf8(body).unwrap()
+	}
+
+	fn notify(&mut self, method: &str, params: &str) {
+		self.send(&format!(r#"{{"jsonrpc":"2.0","method":"{}","params":{}}}"#, method, params));
+	}
+
+	/// Sends a request and gives back the result or error in its response
+	fn request(&mut self, method: &str, params: &str) -> String {
+		let id = self.next_id;
+		self.next_id += 1;
+		self.send(&format!(r#"{{"jsonrpc":"2.0","id":{},"method":"{}","params":{}}}"#, id, method, params));
+		let response = self.receive();
+		let prefix = format!(r#"{{"jsonrpc":"2.0","id":{},"#, id);
+		response.strip_prefix(&prefix).and_then(|rest| rest.strip_suffix('}')).unwrap_or_else(|| panic!("unexpected response {}", response)).to_string()
+	}
+
+	fn at(&mut self, method: &str, line: usize, character: usize, extra: &str) -> String {
+		let params = format!(r#"{{"textDocument":{{"uri":"{}"}},"position":{{"line":{},"character":{}}}{}}}"#, URI, line, character, extra);
+		self.request(method, &params)
+	}
+}
+
+fn open() -> Server {
+	let mut server = Server::start();
+	assert!(server.request("initialize", "{}").contains(r#""renameProvider":true"#));
+	server.notify("initialized", "{}");
+	server.notify("textDocument/didOpen", &format!(r#"{{"textDocument":{{"uri":"{}","languageId":"botabasic","version":1,"text":"{}"}}}}"#, URI, SOURCE));
+	server
+}
+
+fn range(line: usize, start: usize, end: usize) -> String {
+	format!(r#"{{"start":{{"line":{},"character":{}}},"end":{{"line":{},"character":{}}}}}"#, line, start, line, end)
+}
+
+fn close(mut server: Server) {
+	assert_eq!(server.request("shutdown", "null"), r#""result":null"#);
+	server.notify("exit", "null");
+	assert!(server.child.wait().unwrap().success());
+}
+
+#[test]
+fn diagnostics_are_published() {
+	let mut server = open();
+	let diagnostics = server.receive();
+	assert!(diagnostics.contains(r#""method":"textDocument/publishDiagnostics""#), "{}", diagnostics);
+	assert!(diagnostics.contains(&format!(r#"{{"range":{},"severity":1,"source":"bota_basic","message":"count is used after it's freed"}}"#, range(7, 6, 11))), "{}", diagnostics);
+	close(server);
+}
+
+#[test]
+fn definitions_and_references() {
+	let mut server = open();
+	server.receive();
+	let definition = server.at("textDocument/definition", 5, 5, "");
+	assert_eq!(definition, format!(r#""result":[{{"uri":"{}","range":{}}}]"#, URI, range(2, 6, 10)));
+
+	let references = server.at("textDocument/references", 0, 6, r#","context":{"includeDeclaration":false}"#);
+	assert_eq!(references.matches(r#""uri""#).count(), 7, "{}", references);
+	assert!(!references.contains(&range(0, 5, 10)));
+	close(server);
+}
+
+#[test]
+fn hover_and_completion() {
+	let mut server = open();
+	server.receive();
+	assert!(server.at("textDocument/hover", 4, 5, "").contains("done: bool"));
+	assert!(server.at("textDocument/hover", 3, 1, "").contains("ADD [location] [variable] [variable]"));
+
+	let commands = server.at("textDocument/completion", 3, 1, "");
+	assert!(commands.contains(r#"{"label":"DECL","kind":3,"#), "{}", commands);
+	let labels = server.at("textDocument/completion", 5, 4, "");
+	assert_eq!(labels, r#""result":[{"label":"loop","kind":18,"detail":"label"}]"#);
+	let variables = server.at("textDocument/completion", 4, 8, "");
+	assert!(variables.contains(r#"{"label":"count","kind":6,"detail":"int"}"#), "{}", variables);
+	close(server);
+}
+
+#[test]
+fn rename_edits_every_use() {
+	let mut server = open();
+	server.receive();
+	let rename = server.at("textDocument/rename", 2, 7, r#","newName":"again""#);
+	assert_eq!(rename.matches(r#""newText":"again""#).count(), 2, "{}", rename);
+	assert!(server.at("textDocument/rename", 2, 7, r#","newName":"a b""#).starts_with(r#""error""#));
+	close(server);
+}
@@ -0,0 +1,114 @@
+//! What the optimizer does to a program's bytecode, and what it leaves alone.
+
+use bota_basic::{CommandResponse, Kind, MemoryIo, Param, Program, Variable};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The instructions on one line of a program's optimized listing, without their addresses or constant numbers
+fn optimized_line(program: Program, line: usize) -> Vec<String> {
+	let listing = program.with_optimizer().disassemble().unwrap();
+	let header = format!("line {}:", line);
+	listing.lines()
+		.skip_while(|text| !text.starts_with(&header))
+		.skip(1)
+		.take_while(|text| text.starts_with("  "))
+		.map(|text| text.split_whitespace().skip(1)
+			.map(|word| if word.starts_with('#') {word.split_once(':').unwrap().1} else {word})
+			.collect::<Vec<_>>()
+			.join(" "))
+		.collect()
+}
+
+fn run(program: Program) -> String {
+	let io = MemoryIo::default();
+	assert_eq!(program.with_io(io.clone()).run_program().unwrap(), 0);
+	io.output()
+}
+
+const FOLD: &str = "\
+DECL a int
+DECL b int
+DECL c int
+SET a 2
+SET b 3
+ADD c a b
+MUL c c c
+DECL out str
+CONVERT out c
+PRINT out
+";
+
+#[test]
+fn folds_constants() {
+	let program = || Program::new(FOLD.to_string());
+	assert_eq!(optimized_line(program(), 7), ["CONST 25", "STORE $4:c"]);
+	assert_eq!(run(program().with_optimizer()), run(program()));
+}
+
+#[test]
+fn removes_dead_stores() {
+	// c is replaced on the next line before anything reads it
+	assert_eq!(optimized_line(Program::new(FOLD.to_string()), 6), Vec::<String>::new());
+
+	let source = "DECL a int\nDECL out str\nSET a 5\nSET a 6\nCONVERT out a\nPRINT out\n";
+	assert_eq!(optimized_line(Program::new(source.to_string()), 3), Vec::<String>::new());
+	assert_eq!(run(Program::new(source.to_string()).with_optimizer()), "6");
+}
+
+#[test]
+fn keeps_stores_an_error_handler_could_see() {
+	let source = "DECL a int\nDECL zero int\nDECL l list\nDECL out str\nTRY\nSET a 5\nINDEX a l zero\nSET a 6\nCATCH e\nCONVERT out a\nPRINT out\nENDTRY\n";
+	assert_eq!(run(Program::new(source.to_string()).with_optimizer()), "5");
+}
+
+#[test]
+fn threads_jumps() {
+	let source = "JMP first\nLABEL first\nJMP second\nLABEL second\nJMP done\nLABEL done\n";
+	assert_eq!(optimized_line(Program::new(source.to_string()), 1), ["LABEL \"done\" -> line 6 @0006", "INVOKE JMP 1"]);
+	assert_eq!(optimized_line(Program::new(source.to_string()), 3), ["LABEL \"done\" -> line 6 @0006", "INVOKE JMP 1"]);
+}
+
+#[test]
+fn does_not_thread_jumps_out_of_try_blocks() {
+	// jumping to `a` leaves the TRY block, so the error isn't caught even though the program goes back in
+	let source = "DECL s str\nTRY\nJMP a\nLABEL b\nFREE nothere\nCATCH e\nSET s \"caught\"\nPRINT s\nENDTRY\nEXIT 0\nLABEL a\nJMP b\n";
+	assert_eq!(optimized_line(Program::new(source.to_string()), 3), ["LABEL \"a\" -> line 11 @0019", "INVOKE JMP 1"]);
+	for program in [Program::new(source.to_string()), Program::new(source.to_string()).with_optimizer()] {
+		let io = MemoryIo::default();
+		let error = program.with_io(io.clone()).run_program().unwrap_err();
+		assert_eq!(error.line, 5);
+		assert_eq!(io.output(), "");
+	}
+
+	// but jumps that stay inside one are still threaded
+	let source = "TRY\nJMP first\nLABEL first\nJMP done\nLABEL done\nCATCH e\nENDTRY\n";
+	assert_eq!(optimized_line(Program::new(source.to_string()), 2), ["LABEL \"done\" -> line 5 @0005", "INVOKE JMP 1"]);
+}
+
+#[test]
+fn does_not_fold_replaced_commands() {
+	let calls = Rc::new(RefCell::new(0));
+	// an ADD that subtracts instead, which can only be known when it runs
+	let program = || {
+		let counter = calls.clone();
+		Program::new(FOLD.to_string()).with_command("ADD", vec![Param::Location(Kind::Integer), Param::Operand(Kind::Integer), Param::Operand(Kind::Integer)], move |_, args| {
+			*counter.borrow_mut() += 1;
+			args[0] = Variable::Int(args[1].to_integer()? as i32 - args[2].to_integer()? as i32);
+			Ok(CommandResponse::Nothing)
+		})
+	};
+	assert_eq!(optimized_line(program(), 6).last().map(String::as_str), Some("STORE $4:c"));
+	assert!(optimized_line(program(), 6).contains(&"INVOKE ADD 3".to_string()));
+	*calls.borrow_mut() = 0;
+	assert_eq!(run(program().with_optimizer()), "1");
+	assert_eq!(*calls.borrow(), 1);
+}
+
+#[test]
+fn does_not_follow_replaced_jumps() {
+	// a JMP that never jumps, so the line after it can still be reached
+	let source = "DECL s str\nSET s \"reached\"\nJMP skip\nPRINT s\nLABEL skip\n";
+	let program = || Program::new(source.to_string()).with_command("JMP", vec![Param::Label], |_, _| Ok(CommandResponse::Nothing));
+	assert_eq!(optimized_line(program(), 4).last().map(String::as_str), Some("INVOKE PRINT 1"));
+	assert_eq!(run(program().with_optimizer()), "reached");
+}
@@ -0,0 +1,142 @@
+//! Stops programs from using what they haven't been allowed to.
+
+use bota_basic::{Capability, CommandResponse, Error, Kind, MemoryIo, Param, Permissions, Program};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Runs a program with only the given permissions, giving back what it wasn't allowed and the line it tried on
+fn denied(source: &str, permissions: Permissions) -> (Capability, usize) {
+	let io = MemoryIo::new("typed\n");
+	match Program::new(source.to_string()).with_io(io).with_permissions(permissions).run_program() {
+		Err(bota_basic::RuntimeError {line, error: Error::PermissionDenied(capability)}) => (capability, line),
+		other => panic!("expected the program to be denied, but it gave {:?}", other.map_err(|error| error.to_string()))
+	}
+}
+
+fn allowed(source: &str, permissions: Permissions) -> String {
+	let io = MemoryIo::new("typed\n");
+	assert_eq!(Program::new(source.to_string()).with_io(io.clone()).with_permissions(permissions).run_program().unwrap(), 0);
+	io.output()
+}
+
+#[test]
+fn stdin() {
+	let source = "DECL s str\nINPUT s\n";
+	assert_eq!(denied(source, Permissions::none()), (Capability::Stdin, 2));
+	allowed(source, Permissions {stdin: true, ..Permissions::none()});
+}
+
+#[test]
+fn stdout() {
+	let source = "DECL s str\nSET s \"hi\"\nPRINT s\n";
+	assert_eq!(denied(source, Permissions::none()), (Capability::Stdout, 3));
+	assert_eq!(allowed(source, Permissions {stdout: true, ..Permissions::none()}), "hi");
+}
+
+#[test]
+fn env() {
+	let source = "DECL s str\nGETENV s \"PATH\"\n";
+	assert_eq!(denied(source, Permissions::none()), (Capability::Env, 2));
+	allowed(source, Permissions {env: true, ..Permissions::none()});
+}
+
+#[test]
+fn clock() {
+	for source in ["DECL t int\nNOW t\n", "DECL t int\nCLOCK t\n", "DECL t int\nSLEEP 1\n"].iter() {
+		assert_eq!(denied(source, Permissions::none()), (Capability::Clock, 2), "{}", source);
+		allowed(source, Permissions {clock: true, ..Permissions::none()});
+	}
+}
+
+#[test]
+fn fake_clocks_are_always_allowed() {
+	let mut program = Program::new("DECL t int\nNOW t\nSLEEP 10\nCLOCK t\n".to_string()).with_fake_clock(0).with_permissions(Permissions::none());
+	assert_eq!(program.run_program().unwrap(), 0);
+}
+
+/// A fresh directory holding `allowed/inside.txt` and `other/outside.txt`
+fn file_dir(name: &str) -> PathBuf {
+	let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+	let _ = std::fs::remove_dir_all(&dir);
+	std::fs::create_dir_all(dir.join("allowed")).unwrap();
+	std::fs::create_dir_all(dir.join("other")).unwrap();
+	std::fs::write(dir.join("allowed/inside.txt"), "").unwrap();
+	std::fs::write(dir.join("other/outside.txt"), "").unwrap();
+	dir
+}
+
+/// Runs a host command which checks it can use the file at `path` before doing anything
+fn open(path: &Path, permissions: Permissions) -> Result<i32, Error> {
+	let source = format!("DECL path str\nSET path {:?}\nOPEN path\n", path.to_str().unwrap());
+	Program::new(source)
+		.with_command("OPEN", vec![Param::Operand(Kind::Str)], |program, args| {
+			program.permissions().check(Capability::File(args[0].to_str()?.into()))?;
+			Ok(CommandResponse::Nothing)
+		})
+		.with_permissions(permissions)
+		.run_program()
+		.map_err(|error| error.error)
+}
+
+#[test]
+fn files_on_the_allow_list() {
+	let dir = file_dir("files_on_the_allow_list");
+	let permissions = || Permissions {files: vec![dir.join("allowed")], ..Permissions::none()};
+	assert_eq!(open(&dir.join("allowed/inside.txt"), permissions()).unwrap(), 0);
+	assert_eq!(open(&dir.join("allowed/./inside.txt"), permissions()).unwrap(), 0);
+	// files that haven't been made yet can be, as long as they'd be in an allowed directory
+	assert_eq!(open(&dir.join("allowed/new.txt"), permissions()).unwrap(), 0);
+	assert_eq!(open(&dir.join("allowed"), permissions()).unwrap(), 0);
+
+	for path in [dir.join("other/outside.txt"), dir.join("allowed/../other/outside.txt"), dir.join("other/new.txt"), dir.clone()].iter() {
+		match open(path, permissions()) {
+			Err(Error::PermissionDenied(Capability::File(denied))) => assert_eq!(&denied, path),
+			other => panic!("expected {} to be denied, but it gave {:?}", path.display(), other.map_err(|error| error.to_string()))
+		}
+	}
+	assert!(open(&dir.join("other/outside.txt"), Permissions::none()).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn links_out_of_allowed_directories_are_followed() {
+	let dir = file_dir("links_out_of_allowed_directories_are_followed");
+	std::os::unix::fs::symlink(dir.join("other"), dir.join("allowed/link")).unwrap();
+	let permissions = Permissions {files: vec![dir.join("allowed")], ..Permissions::none()};
+	assert!(!permissions.allows(&Capability::File(dir.join("allowed/link/outside.txt"))));
+	assert!(permissions.allows(&Capability::File(dir.join("allowed/inside.txt"))));
+}
+
+#[test]
+fn all_allows_every_file() {
+	let dir = file_dir("all_allows_every_file");
+	assert_eq!(open(&dir.join("other/outside.txt"), Permissions::all()).unwrap(), 0);
+	assert!(Permissions::all().allows(&Capability::File(PathBuf::from("/nowhere/at/all"))));
+}
+
+#[test]
+fn processes_and_the_network() {
+	for capability in [Capability::Process, Capability::Network].iter() {
+		assert!(!Permissions::none().allows(capability));
+		assert!(matches!(Permissions::none().check(capability.clone()), Err(Error::PermissionDenied(ref denied)) if denied == capability));
+		assert!(Permissions::all().allows(capability));
+	}
+	assert!(Permissions {process: true, ..Permissions::none()}.allows(&Capability::Process));
+	assert!(!Permissions {process: true, ..Permissions::none()}.allows(&Capability::Network));
+	assert!(Permissions {network: true, ..Permissions::none()}.allows(&Capability::Network));
+}
+
+#[test]
+fn command_line_flags() {
+	let dir = file_dir("command_line_flags");
+	std::fs::write(dir.join("hello.bas"), "DECL s str\nSET s \"hello\"\nPRINT s\n").unwrap();
+	let run = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_bota_basic")).args(args).arg(dir.join("hello.bas")).output().unwrap();
+
+	let output = run(&["--allow-file", dir.join("allowed").to_str().unwrap(), "--allow-process", "--allow-net"]);
+	assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+	assert_eq!(output.stdout, b"hello");
+
+	let output = Command::new(env!("CARGO_BIN_EXE_bota_basic")).arg("--allow-file").output().unwrap();
+	assert_eq!(output.status.code(), Some(1));
+	assert_eq!(String::from_utf8(output.stderr).unwrap(), "--allow-file needs a path\n");
+}
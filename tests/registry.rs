@@ -0,0 +1,81 @@
+//! Commands registered by the host, alongside or instead of the built-in ones.
+
+use bota_basic::{CommandResponse, Error, Kind, MemoryIo, Param, Program, Variable};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A program with a `DOUBLE [int location] [int]` command, which counts how often it's called
+fn with_double(source: &str, calls: Rc<RefCell<usize>>) -> Program {
+	Program::new(source.to_string()).with_command("DOUBLE", vec![Param::Location(Kind::Integer), Param::Operand(Kind::Integer)], move |_, args| {
+		*calls.borrow_mut() += 1;
+		args[0] = match args[1] {
+			Variable::Natural(n) => Variable::Natural(n * 2),
+			Variable::Int(i) => Variable::Int(i * 2),
+			_ => unreachable!()
+		};
+		Ok(CommandResponse::Nothing)
+	})
+}
+
+#[test]
+fn host_commands_are_called() {
+	let calls = Rc::new(RefCell::new(0));
+	let io = MemoryIo::default();
+	let source = "DECL n int\nDECL out str\nSET n 21\nDOUBLE n n\nCONVERT out n\nPRINT out\n";
+	assert_eq!(with_double(source, calls.clone()).with_io(io.clone()).run_program().unwrap(), 0);
+	assert_eq!(io.output(), "42");
+	assert_eq!(*calls.borrow(), 1);
+}
+
+#[test]
+fn host_command_kinds_are_checked() {
+	let calls = Rc::new(RefCell::new(0));
+	let location = with_double("DECL s str\nDECL n int\nDOUBLE s n\n", calls.clone()).run_program().unwrap_err();
+	assert_eq!(location.line, 3);
+	assert!(matches!(location.error, Error::WrongType(_)), "{}", location);
+
+	let operand = with_double("DECL s str\nDECL n int\nDOUBLE n s\n", calls.clone()).run_program().unwrap_err();
+	assert_eq!(operand.line, 3);
+	assert!(matches!(operand.error, Error::WrongType(_)), "{}", operand);
+
+	let missing = with_double("DECL n int\nDOUBLE n\n", calls.clone()).run_program().unwrap_err();
+	assert!(matches!(missing.error, Error::MissingParameters(_)), "{}", missing);
+	assert_eq!(*calls.borrow(), 0);
+}
+
+#[test]
+fn host_errors_stop_the_program() {
+	let program = Program::new("FAIL\n".to_string()).with_command("FAIL", vec![], |_, _| Err(Error::Host("it broke".to_string())));
+	let error = program.with_io(MemoryIo::default()).run_program().unwrap_err();
+	assert_eq!(error.to_string(), "error on line 1: it broke");
+}
+
+#[test]
+fn built_in_commands_can_be_replaced() {
+	let printed = Rc::new(RefCell::new(Vec::new()));
+	let log = printed.clone();
+	let io = MemoryIo::default();
+	let mut program = Program::new("DECL s str\nSET s \"hi\"\nprint s\n".to_string())
+		.with_io(io.clone())
+		.with_command("PRINT", vec![Param::Operand(Kind::Str)], move |_, args| {
+			log.borrow_mut().push(args[0].to_string());
+			Ok(CommandResponse::Nothing)
+		});
+	assert_eq!(program.run_program().unwrap(), 0);
+	assert_eq!(*printed.borrow(), ["hi"]);
+	assert_eq!(io.output(), "");
+}
+
+#[test]
+fn host_commands_can_not_open_blocks_on_other_lines() {
+	let responses: [fn() -> CommandResponse; 3] = [|| CommandResponse::Try, || CommandResponse::Catch, || CommandResponse::Test];
+	for response in responses {
+		for vm in [false, true] {
+			let program = Program::new("DECL n int\nBLOCK\n".to_string()).with_command("BLOCK", vec![], move |_, _| Ok(response()));
+			let program = if vm {program.with_vm()} else {program};
+			let error = program.with_io(MemoryIo::default()).run_program().unwrap_err();
+			assert_eq!(error.line, 2);
+			assert!(matches!(error.error, Error::InvalidBlock(_)), "{}", error);
+		}
+	}
+}
@@ -0,0 +1,133 @@
+//! Saves running programs with `Program::snapshot` and picks them back up with `Program::restore`.
+
+use bota_basic::{Error, MemoryIo, Program, Status};
+use std::process::Command;
+
+/// Counts to five inside a `TRY` block, printing each number from a routine
+const COUNT: &str = "\
+DECL i int
+DECL one int
+DECL five int
+DECL out str
+SET one 1
+SET five 5
+JMP start
+LABEL show
+CONVERT out i
+PRINT out
+RETURN
+LABEL start
+TRY
+LABEL top
+ADD i i one
+CALL show
+JLT top i five
+CATCH e
+ENDTRY
+";
+
+/// Runs `lines` lines of a program, then saves it and carries on in another, giving back everything printed
+fn split_run(lines: u64, make: impl Fn() -> Program) -> String {
+	let first = MemoryIo::default();
+	let mut program = make().with_io(first.clone());
+	let status = program.run_for(lines);
+	let snapshot = program.snapshot();
+
+	let second = MemoryIo::default();
+	let mut restored = make().with_io(second.clone());
+	restored.restore(&snapshot).unwrap();
+	let code = restored.resume().unwrap();
+	assert_eq!(code, 0, "after {} lines, which left the program {:?}", lines, status);
+	first.output() + &second.output()
+}
+
+#[test]
+fn round_trips_through_the_interpreter() {
+	for lines in 1..60 {
+		assert_eq!(split_run(lines, || Program::new(COUNT.to_string())), "12345", "after {} lines", lines);
+	}
+}
+
+#[test]
+fn round_trips_through_the_vm() {
+	for lines in 1..60 {
+		assert_eq!(split_run(lines, || Program::new(COUNT.to_string()).with_vm()), "12345", "after {} lines", lines);
+	}
+}
+
+#[test]
+fn restored_programs_can_be_stepped() {
+	let mut program = Program::new(COUNT.to_string()).with_io(MemoryIo::default());
+	program.run_for(20);
+	let io = MemoryIo::default();
+	let mut restored = Program::new(String::new()).with_io(io.clone());
+	restored.restore(&program.snapshot()).unwrap();
+	while let Status::Running = restored.step() {}
+	assert!(matches!(restored.step(), Status::Finished(0)));
+	assert!(!io.output().is_empty());
+}
+
+/// A snapshot taken partway through [`COUNT`]
+fn snapshot() -> Vec<u8> {
+	let mut program = Program::new(COUNT.to_string()).with_io(MemoryIo::default());
+	program.run_for(20);
+	program.snapshot()
+}
+
+fn restore_error(snapshot: &[u8]) -> String {
+	match Program::new(String::new()).restore(snapshot) {
+		Err(error @ Error::InvalidSnapshot(_)) => error.to_string(),
+		other => panic!("expected the snapshot to be rejected, but it gave {:?}", other.map_err(|error| error.to_string()))
+	}
+}
+
+#[test]
+fn rejects_bad_magic() {
+	let mut bytes = snapshot();
+	bytes[0] = b'X';
+	assert_eq!(restore_error(&bytes), "the snapshot is invalid: it isn't a snapshot");
+	assert_eq!(restore_error(b"BOTA"), "the snapshot is invalid: it isn't a snapshot");
+}
+
+#[test]
+fn rejects_other_versions() {
+	let mut bytes = snapshot();
+	bytes[8..12].copy_from_slice(&99u32.to_le_bytes());
+	assert_eq!(restore_error(&bytes), "the snapshot is invalid: version 99 isn't supported");
+}
+
+#[test]
+fn rejects_truncated_snapshots() {
+	let bytes = snapshot();
+	for len in 0..bytes.len() {
+		restore_error(&bytes[..len]);
+	}
+	let mut longer = bytes.clone();
+	longer.push(0);
+	assert_eq!(restore_error(&longer), "the snapshot is invalid: there's more after the end");
+}
+
+#[test]
+fn failed_restores_leave_the_program_alone() {
+	let io = MemoryIo::default();
+	let mut program = Program::new(COUNT.to_string()).with_io(io.clone());
+	program.run_for(20);
+	let before = program.snapshot();
+
+	let other = {
+		let mut other = Program::new("DECL x int\nSET x 1\n".to_string()).with_io(MemoryIo::default());
+		other.run_for(1);
+		other.snapshot()
+	};
+	assert!(program.restore(&other[..other.len() - 1]).is_err());
+	assert_eq!(program.snapshot(), before);
+	assert_eq!(program.resume().unwrap(), 0);
+	assert_eq!(io.output(), "12345");
+}
+
+#[test]
+fn resume_can_not_be_given_a_script() {
+	let output = Command::new(env!("CARGO_BIN_EXE_bota_basic")).args(["--resume", "saved.snap", "script.bas", "arg"]).output().unwrap();
+	assert_eq!(output.status.code(), Some(1));
+	assert_eq!(String::from_utf8(output.stderr).unwrap(), "--resume carries on from a snapshot, so script.bas can't be run as well\n");
+}
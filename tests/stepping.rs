@@ -0,0 +1,96 @@
+//! Runs programs a line or a few lines at a time, with `Program::step` and `Program::run_for`.
+
+use bota_basic::{Error, Limits, MemoryIo, Program, ScriptedIo, Status};
+use std::time::Duration;
+
+const COUNT: &str = "\
+DECL n int
+DECL one int
+SET one 1
+ADD n n one
+EXIT 2
+";
+
+const ECHO: &str = "\
+DECL line str
+INPUT line
+PRINT line
+";
+
+#[test]
+fn steps_until_finished() {
+	let mut program = Program::new(COUNT.to_string()).with_io(MemoryIo::default());
+	for _ in 0..4 {
+		assert!(matches!(program.step(), Status::Running));
+	}
+	assert!(matches!(program.step(), Status::Finished(2)));
+	// once it's finished, it stays that way
+	assert!(matches!(program.step(), Status::Finished(2)));
+}
+
+#[test]
+fn steps_until_errored() {
+	let mut program = Program::new("DECL n int\nFREE missing\nEXIT 3\n".to_string()).with_io(MemoryIo::default());
+	assert!(matches!(program.step(), Status::Running));
+	match program.step() {
+		Status::Errored(error) => assert_eq!(error.line, 2),
+		other => panic!("expected an error, but the program was {:?}", other)
+	}
+	assert!(matches!(program.step(), Status::Errored(_)));
+}
+
+#[test]
+fn waits_for_input_and_carries_on_once_it_comes() {
+	let io = MemoryIo::waiting();
+	let mut program = Program::new(ECHO.to_string()).with_io(io.clone());
+	assert!(matches!(program.step(), Status::Running));
+	assert!(matches!(program.step(), Status::WaitingForInput));
+	assert!(matches!(program.step(), Status::WaitingForInput));
+
+	io.push_input("hello\n");
+	assert!(matches!(program.step(), Status::Running));
+	assert!(matches!(program.step(), Status::Finished(0)));
+	assert_eq!(io.output(), "hello\n");
+}
+
+#[test]
+fn resumes_after_waiting() {
+	let io = MemoryIo::waiting();
+	let mut program = Program::new(ECHO.to_string()).with_io(io.clone());
+	assert!(matches!(program.run_for(10), Status::WaitingForInput));
+	io.push_input("again\n");
+	assert_eq!(program.resume().unwrap(), 0);
+	assert_eq!(io.output(), "again\n");
+}
+
+#[test]
+fn scripted_io_errors_instead_of_waiting() {
+	let io = ScriptedIo::new(Vec::new());
+	let mut program = Program::new(ECHO.to_string()).with_io(io);
+	match program.run_for(10) {
+		Status::Errored(error) => {
+			assert_eq!(error.line, 2);
+			assert!(matches!(error.error, Error::Io(_)));
+		},
+		other => panic!("expected an error, but the program was {:?}", other)
+	}
+}
+
+#[test]
+fn run_for_stops_after_that_many_lines() {
+	let mut program = Program::new(COUNT.to_string()).with_io(MemoryIo::default());
+	assert!(matches!(program.run_for(3), Status::Running));
+	assert!(matches!(program.run_for(1), Status::Running));
+	assert!(matches!(program.run_for(5), Status::Finished(2)));
+}
+
+#[test]
+fn time_between_steps_is_not_counted() {
+	let limits = Limits {time: Some(Duration::from_millis(100)), ..Limits::default()};
+	let mut program = Program::new(COUNT.to_string()).with_io(MemoryIo::default()).with_limits(limits);
+	for _ in 0..4 {
+		assert!(matches!(program.step(), Status::Running));
+		std::thread::sleep(Duration::from_millis(60));
+	}
+	assert!(matches!(program.step(), Status::Finished(2)));
+}
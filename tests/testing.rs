@@ -0,0 +1,83 @@
+//! Runs `TEST` blocks, through `Program::run_tests` and through `bota_basic test`.
+
+use bota_basic::{Error, Program};
+use std::path::PathBuf;
+use std::process::Command;
+
+const SCRIPT: &str = "\
+DECL top int
+TEST passes
+DECL leak int
+SET leak 1
+ASSERTEQ leak leak
+ENDTEST
+TEST isolated
+TRY
+FREE leak
+EXIT 2
+CATCH e
+ENDTRY
+TRY
+FREE top
+EXIT 2
+CATCH e2
+ENDTRY
+ENDTEST
+TEST asserts
+DECL flag bool
+ASSERT flag \"flag should be set\"
+ENDTEST
+TEST exits
+EXIT 3
+ENDTEST
+";
+
+/// A fresh directory holding the given scripts
+fn script_dir(name: &str, scripts: &[(&str, &str)]) -> PathBuf {
+	let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+	let _ = std::fs::remove_dir_all(&dir);
+	std::fs::create_dir_all(&dir).unwrap();
+	for (filename, source) in scripts {
+		std::fs::write(dir.join(filename), source).unwrap();
+	}
+	dir
+}
+
+#[test]
+fn each_block_runs_on_its_own() {
+	let outcomes = Program::new(SCRIPT.to_string()).run_tests().unwrap();
+	let summary: Vec<(&str, usize, bool)> = outcomes.iter().map(|outcome| (outcome.name.as_str(), outcome.line, outcome.passed())).collect();
+	assert_eq!(summary, [("passes", 2, true), ("isolated", 7, true), ("asserts", 19, false), ("exits", 23, false)]);
+
+	let error = outcomes[2].result.as_ref().unwrap_err();
+	assert_eq!(error.line, 21);
+	assert!(matches!(&error.error, Error::AssertionFailed(message) if message == "flag should be set"));
+	assert_eq!(outcomes[3].result.as_ref().unwrap(), &3);
+}
+
+#[test]
+fn blocks_are_skipped_when_running() {
+	assert_eq!(Program::new(SCRIPT.to_string()).run_program().unwrap(), 0);
+}
+
+#[test]
+fn test_subcommand_reports_failures() {
+	let dir = script_dir("test_subcommand_reports_failures", &[("script.bas", SCRIPT)]);
+	let output = Command::new(env!("CARGO_BIN_EXE_bota_basic")).arg("test").arg(&dir).output().unwrap();
+	let stdout = String::from_utf8(output.stdout).unwrap();
+	let script = dir.join("script.bas");
+	let expected = format!(
+		"PASS {0}:2 passes\nPASS {0}:7 isolated\nFAIL {0}:19 asserts: error on line 21: assertion failed: flag should be set\nFAIL {0}:23 exits: exited with code 3\n2 passed, 2 failed\n",
+		script.display()
+	);
+	assert_eq!(stdout, expected);
+	assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_subcommand_passes() {
+	let dir = script_dir("test_subcommand_passes", &[("script.bas", "TEST passes\nENDTEST\n")]);
+	let output = Command::new(env!("CARGO_BIN_EXE_bota_basic")).arg("test").arg(&dir).output().unwrap();
+	assert!(String::from_utf8(output.stdout).unwrap().ends_with("1 passed, 0 failed\n"));
+	assert_eq!(output.status.code(), Some(0));
+}